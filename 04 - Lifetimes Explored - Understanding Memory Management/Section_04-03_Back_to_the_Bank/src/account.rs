@@ -0,0 +1,254 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::clock::Date;
+use crate::currency::Currency;
+
+// Lifecycle of an account:
+//   Open    -> Frozen, Dormant, Closed
+//   Frozen  -> Open, Closed
+//   Dormant -> Open, Frozen, Closed
+//   Closed  -> (nothing, an account is read-only once closed)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountState {
+    Open,
+    Frozen,
+    Dormant,
+    Closed,
+}
+
+impl AccountState {
+    pub fn can_transition_to(self, to: AccountState) -> bool {
+        use AccountState::*;
+
+        matches!(
+            (self, to),
+            (Open, Frozen | Dormant | Closed)
+                | (Frozen, Open | Closed)
+                | (Dormant, Open | Frozen | Closed)
+        )
+    }
+}
+
+impl fmt::Display for AccountState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AccountState::Open => "open",
+            AccountState::Frozen => "frozen",
+            AccountState::Dormant => "dormant",
+            AccountState::Closed => "closed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Every state change is kept, with the reason and the day it happened.
+#[derive(Debug, Clone)]
+pub struct StateTransition {
+    pub from: AccountState,
+    pub to: AccountState,
+    pub reason: String,
+    pub at: Date,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountError {
+    // Withdrawals are not allowed while frozen (deposits are)
+    Frozen {
+        id: u32,
+    },
+    // A dormant account has to be reactivated before money can leave it
+    Dormant {
+        id: u32,
+    },
+    // Closed accounts are read-only
+    Closed {
+        id: u32,
+    },
+    IllegalTransition {
+        id: u32,
+        from: AccountState,
+        to: AccountState,
+    },
+    // The balance wouldn't fit in an i32
+    Overflow {
+        id: u32,
+    },
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccountError::Frozen { id } => write!(f, "account #{} is frozen", id),
+            AccountError::Dormant { id } => write!(f, "account #{} is dormant", id),
+            AccountError::Closed { id } => write!(f, "account #{} is closed", id),
+            AccountError::IllegalTransition { id, from, to } => {
+                write!(f, "account #{} can't go from {} to {}", id, from, to)
+            }
+            AccountError::Overflow { id } => {
+                write!(f, "account #{} can't hold that much money", id)
+            }
+        }
+    }
+}
+
+impl Error for AccountError {}
+
+#[derive(Debug, Clone)]
+pub struct Account {
+    id: u32,
+    balance: i32,
+    holder: String,
+    currency: Currency,
+    state: AccountState,
+    last_activity: Date,
+    transitions: Vec<StateTransition>,
+}
+
+impl Account {
+    pub fn new(id: u32, holder: String, opened_on: Date) -> Self {
+        Account {
+            id,
+            holder,
+            balance: 0,
+            currency: Currency::Eur,
+            state: AccountState::Open,
+            last_activity: opened_on,
+            transitions: vec![],
+        }
+    }
+
+    // Accounts are held in EUR unless told otherwise
+    pub fn in_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    pub fn balance(&self) -> i32 {
+        self.balance
+    }
+
+    pub fn state(&self) -> AccountState {
+        self.state
+    }
+
+    pub fn last_activity(&self) -> Date {
+        self.last_activity
+    }
+
+    pub fn transitions(&self) -> &[StateTransition] {
+        &self.transitions
+    }
+
+    // Frozen accounts still accept deposits.
+    pub fn check_deposit(&self) -> Result<(), AccountError> {
+        match self.state {
+            AccountState::Closed => Err(AccountError::Closed { id: self.id }),
+            AccountState::Open | AccountState::Frozen | AccountState::Dormant => Ok(()),
+        }
+    }
+
+    pub fn check_withdraw(&self) -> Result<(), AccountError> {
+        match self.state {
+            AccountState::Frozen => Err(AccountError::Frozen { id: self.id }),
+            AccountState::Dormant => Err(AccountError::Dormant { id: self.id }),
+            AccountState::Closed => Err(AccountError::Closed { id: self.id }),
+            AccountState::Open => Ok(()),
+        }
+    }
+
+    // What the balance would be once 'amount' is added (a negative amount takes money out)
+    pub fn balance_after(&self, amount: i32) -> Result<i32, AccountError> {
+        self.balance
+            .checked_add(amount)
+            .ok_or(AccountError::Overflow { id: self.id })
+    }
+
+    // A deposit on a dormant account counts as activity and reopens it.
+    pub fn deposit(&mut self, amount: i32, on: Date) -> Result<i32, AccountError> {
+        self.check_deposit()?;
+        let balance = self.balance_after(amount)?;
+        if self.state == AccountState::Dormant {
+            self.transition(AccountState::Open, "deposit on dormant account", on)?;
+        }
+
+        self.balance = balance;
+        self.last_activity = on;
+        Ok(self.balance)
+    }
+
+    pub fn withdraw(&mut self, amount: i32, on: Date) -> Result<i32, AccountError> {
+        self.check_withdraw()?;
+        let balance = self.balance_after(-amount)?;
+
+        self.balance = balance;
+        self.last_activity = on;
+        Ok(self.balance)
+    }
+
+    // Used by an operator to undo a mistake: goes through frozen or dormant accounts,
+    // but a closed account stays read-only.
+    pub fn correct(&mut self, amount: i32, on: Date) -> Result<i32, AccountError> {
+        if self.state == AccountState::Closed {
+            return Err(AccountError::Closed { id: self.id });
+        }
+        let balance = self.balance_after(amount)?;
+
+        self.balance = balance;
+        self.last_activity = on;
+        Ok(self.balance)
+    }
+
+    pub fn transition(
+        &mut self,
+        to: AccountState,
+        reason: &str,
+        on: Date,
+    ) -> Result<(), AccountError> {
+        if !self.state.can_transition_to(to) {
+            return Err(AccountError::IllegalTransition {
+                id: self.id,
+                from: self.state,
+                to,
+            });
+        }
+
+        self.transitions.push(StateTransition {
+            from: self.state,
+            to,
+            reason: reason.to_string(),
+            at: on,
+        });
+        self.state = to;
+        // Reopening counts as activity: otherwise a dormant account would go straight
+        // back to sleep the next day
+        if to == AccountState::Open {
+            self.last_activity = on;
+        }
+        Ok(())
+    }
+
+    // An open account with no activity during 'period_days' days becomes dormant.
+    pub fn is_inactive_since(&self, today: Date, period_days: u32) -> bool {
+        self.last_activity.days_until(today) >= period_days as i64
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} (#{}) has a balance of {} {} ({}).",
+            self.holder, self.id, self.balance, self.currency, self.state
+        )
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bank::Bank;
+use crate::clock::Date;
+use crate::transaction::TransactionKind;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    // The stored balance doesn't match the sum of the account's ledger entries
+    BalanceMismatch {
+        account: u32,
+        stored: i32,
+        recomputed: i32,
+    },
+    // A transfer without exactly one debit on 'from' and one credit on 'to'
+    // (for the same amount, or the converted amount between two currencies)
+    UnbalancedTransfer {
+        transaction: u64,
+        debit: i32,
+        credit: i32,
+        expected_debit: i32,
+        expected_credit: i32,
+    },
+    // A ledger entry pointing to an account the bank doesn't know about
+    UnknownAccount {
+        transaction: u64,
+        account: u32,
+    },
+    // A ledger entry pointing to a transaction that isn't in the ledger
+    OrphanEntry {
+        transaction: u64,
+        account: u32,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Discrepancy::BalanceMismatch {
+                account,
+                stored,
+                recomputed,
+            } => write!(
+                f,
+                "account #{}: stored balance {} but history adds up to {} (off by {})",
+                account,
+                stored,
+                recomputed,
+                stored - recomputed
+            ),
+            Discrepancy::UnbalancedTransfer {
+                transaction,
+                debit,
+                credit,
+                expected_debit,
+                expected_credit,
+            } => write!(
+                f,
+                "transfer {}: debit {} / credit {} (expected {} / {})",
+                transaction, debit, credit, expected_debit, expected_credit
+            ),
+            Discrepancy::UnknownAccount {
+                transaction,
+                account,
+            } => write!(
+                f,
+                "transaction {} has a leg on unknown account #{}",
+                transaction, account
+            ),
+            Discrepancy::OrphanEntry {
+                transaction,
+                account,
+            } => write!(
+                f,
+                "ledger entry on account #{} refers to missing transaction {}",
+                account, transaction
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AuditReport {
+    pub date: Date,
+    pub accounts_checked: usize,
+    pub transfers_checked: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    // 0 when everything adds up, 1 otherwise (so a nightly job can fail loudly)
+    pub fn exit_code(&self) -> i32 {
+        if self.is_clean() { 0 } else { 1 }
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Audit report for {}", self.date)?;
+        writeln!(f, "  accounts checked:  {}", self.accounts_checked)?;
+        writeln!(f, "  transfers checked: {}", self.transfers_checked)?;
+
+        if self.is_clean() {
+            write!(f, "  OK: no discrepancies")
+        } else {
+            write!(f, "  FAILED: {} discrepancies", self.discrepancies.len())?;
+            for discrepancy in &self.discrepancies {
+                write!(f, "\n  - {}", discrepancy)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// Recomputes every balance from the ledger and checks both legs of every transfer.
+pub fn reconcile(bank: &Bank) -> AuditReport {
+    let mut discrepancies = vec![];
+
+    // Balances, rebuilt from the ledger entries only
+    let mut recomputed: HashMap<u32, i32> = HashMap::new();
+    let mut legs_by_transaction: HashMap<u64, Vec<(u32, i32)>> = HashMap::new();
+    for entry in bank.entries() {
+        *recomputed.entry(entry.account).or_insert(0) += entry.amount;
+        legs_by_transaction
+            .entry(entry.transaction_id)
+            .or_default()
+            .push((entry.account, entry.amount));
+    }
+
+    for account in bank.accounts() {
+        let history = recomputed.remove(&account.id()).unwrap_or(0);
+        if history != account.balance() {
+            discrepancies.push(Discrepancy::BalanceMismatch {
+                account: account.id(),
+                stored: account.balance(),
+                recomputed: history,
+            });
+        }
+    }
+
+    // Whatever is left in 'recomputed' belongs to accounts the bank doesn't have
+    let mut unknown: Vec<u32> = recomputed.into_keys().collect();
+    unknown.sort();
+    for account in unknown {
+        for entry in bank.entries().iter().filter(|e| e.account == account) {
+            discrepancies.push(Discrepancy::UnknownAccount {
+                transaction: entry.transaction_id,
+                account,
+            });
+        }
+    }
+
+    // Both legs of every transfer
+    let mut transfers_checked = 0;
+    for transaction in bank.transactions() {
+        let legs = legs_by_transaction
+            .remove(&transaction.id)
+            .unwrap_or_default();
+
+        let TransactionKind::Transfer { from, to } = transaction.kind else {
+            continue;
+        };
+        transfers_checked += 1;
+
+        let debit: i32 = legs
+            .iter()
+            .filter(|(account, amount)| *account == from && *amount < 0)
+            .map(|(_, amount)| -amount)
+            .sum();
+        let credit: i32 = legs
+            .iter()
+            .filter(|(account, amount)| *account == to && *amount > 0)
+            .map(|(_, amount)| amount)
+            .sum();
+
+        if legs.len() != 2 || debit != transaction.amount || credit != transaction.credited() {
+            discrepancies.push(Discrepancy::UnbalancedTransfer {
+                transaction: transaction.id,
+                debit,
+                credit,
+                expected_debit: transaction.amount,
+                expected_credit: transaction.credited(),
+            });
+        }
+    }
+
+    // Entries whose transaction was never recorded
+    let mut orphans: Vec<(u64, Vec<(u32, i32)>)> = legs_by_transaction.into_iter().collect();
+    orphans.sort_by_key(|(id, _)| *id);
+    for (transaction, legs) in orphans {
+        for (account, _) in legs {
+            discrepancies.push(Discrepancy::OrphanEntry {
+                transaction,
+                account,
+            });
+        }
+    }
+
+    AuditReport {
+        date: bank.today(),
+        accounts_checked: bank.accounts().len(),
+        transfers_checked,
+        discrepancies,
+    }
+}
@@ -0,0 +1,1624 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::account::{Account, AccountError, AccountState};
+use crate::clock::{Clock, Date};
+use crate::currency::{Conversion, Currency, FxError, FxRates, RoundingMode};
+use crate::events::{DEFAULT_SNAPSHOT_EVERY, Event, EventStore, RecordedEvent, Snapshot};
+use crate::fraud::{Flag, RuleEngine};
+use crate::loans::{BASIS_POINTS, CreditLine, Loan, LoanError};
+use crate::standing_orders::{
+    DEFAULT_STANDING_ORDER_RETRIES, Outcome, Schedule, StandingOrder, StandingOrderError,
+};
+use crate::transaction::{LedgerEntry, Transaction, TransactionKind};
+
+// Default number of days without activity before an open account becomes dormant.
+pub const DEFAULT_DORMANCY_PERIOD_DAYS: u32 = 365;
+// Default penalty for a loan installment that couldn't be collected on time (5% of it)
+pub const DEFAULT_LATE_PENALTY_BP: u32 = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BankError {
+    AccountNotFound(u32),
+    DuplicateAccount(u32),
+    TransactionNotFound(u64),
+    AlreadyReversed(u64),
+    // Money moved by a loan, a credit line or a standing order: undoing it would leave
+    // the product out of step with the account
+    NotReversible { transaction: u64, product: String },
+    // Deposits, withdrawals and transfers move more than 0
+    InvalidAmount(i32),
+    InsufficientFunds { account: u32, needed: i32 },
+    Account(AccountError),
+    // A fraud rule refused the transaction
+    Blocked { rule: String, reason: String },
+    Fx(FxError),
+    Loan(LoanError),
+    StandingOrder(StandingOrderError),
+}
+
+impl fmt::Display for BankError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BankError::AccountNotFound(id) => write!(f, "account #{} not found", id),
+            BankError::DuplicateAccount(id) => write!(f, "account #{} already exists", id),
+            BankError::TransactionNotFound(id) => write!(f, "transaction {} not found", id),
+            BankError::AlreadyReversed(id) => {
+                write!(f, "transaction {} has already been reversed", id)
+            }
+            BankError::NotReversible {
+                transaction,
+                product,
+            } => write!(
+                f,
+                "transaction {} belongs to {} and can't be reversed",
+                transaction, product
+            ),
+            BankError::InvalidAmount(amount) => {
+                write!(
+                    f,
+                    "{} isn't a valid amount (it must be more than 0)",
+                    amount
+                )
+            }
+            BankError::InsufficientFunds { account, needed } => {
+                write!(f, "account #{} needs at least {}", account, needed)
+            }
+            BankError::Account(error) => write!(f, "{}", error),
+            BankError::Blocked { rule, reason } => {
+                write!(f, "transaction blocked by rule '{}': {}", rule, reason)
+            }
+            BankError::Fx(error) => write!(f, "{}", error),
+            BankError::Loan(error) => write!(f, "{}", error),
+            BankError::StandingOrder(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for BankError {}
+
+// Lets us use the '?' operator on an AccountError inside a method returning a BankError
+impl From<AccountError> for BankError {
+    fn from(error: AccountError) -> Self {
+        BankError::Account(error)
+    }
+}
+
+impl From<FxError> for BankError {
+    fn from(error: FxError) -> Self {
+        BankError::Fx(error)
+    }
+}
+
+impl From<LoanError> for BankError {
+    fn from(error: LoanError) -> Self {
+        BankError::Loan(error)
+    }
+}
+
+impl From<StandingOrderError> for BankError {
+    fn from(error: StandingOrderError) -> Self {
+        BankError::StandingOrder(error)
+    }
+}
+
+#[derive(Debug)]
+pub struct Bank {
+    accounts: Vec<Account>,
+    clock: Clock,
+    dormancy_period_days: u32,
+    transactions: Vec<Transaction>,
+    entries: Vec<LedgerEntry>,
+    next_transaction_id: u64,
+    rules: RuleEngine,
+    flags: Vec<Flag>,
+    fx_rates: FxRates,
+    events: EventStore,
+    loans: Vec<Loan>,
+    credit_lines: Vec<CreditLine>,
+    late_penalty_bp: u32,
+    standing_orders: Vec<StandingOrder>,
+    standing_order_retries: u32,
+}
+
+impl Bank {
+    pub fn new(clock: Clock) -> Self {
+        Bank {
+            accounts: vec![],
+            clock,
+            dormancy_period_days: DEFAULT_DORMANCY_PERIOD_DAYS,
+            transactions: vec![],
+            entries: vec![],
+            next_transaction_id: 1,
+            rules: RuleEngine::new(),
+            flags: vec![],
+            fx_rates: FxRates::new(),
+            events: EventStore::new(DEFAULT_SNAPSHOT_EVERY),
+            loans: vec![],
+            credit_lines: vec![],
+            late_penalty_bp: DEFAULT_LATE_PENALTY_BP,
+            standing_orders: vec![],
+            standing_order_retries: DEFAULT_STANDING_ORDER_RETRIES,
+        }
+    }
+
+    // Applies to the standing orders created afterwards
+    pub fn with_standing_order_retries(mut self, retries: u32) -> Self {
+        self.standing_order_retries = retries;
+        self
+    }
+
+    pub fn with_late_penalty(mut self, basis_points: u32) -> Self {
+        self.late_penalty_bp = basis_points;
+        self
+    }
+
+    pub fn with_snapshot_every(mut self, events: usize) -> Self {
+        self.events = EventStore::new(events);
+        self
+    }
+
+    pub fn with_fx_rates(mut self, fx_rates: FxRates) -> Self {
+        self.fx_rates = fx_rates;
+        self
+    }
+
+    pub fn with_rules(mut self, rules: RuleEngine) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn with_dormancy_period(mut self, days: u32) -> Self {
+        self.dormancy_period_days = days;
+        self
+    }
+
+    pub fn today(&self) -> Date {
+        self.clock.today()
+    }
+
+    // '&mut self': we can change this reference / this bank.
+    pub fn open_account(
+        &mut self,
+        id: u32,
+        holder: &str,
+        currency: Currency,
+    ) -> Result<(), BankError> {
+        if self.account(id).is_ok() {
+            return Err(BankError::DuplicateAccount(id));
+        }
+
+        self.record(Event::AccountOpened {
+            account: id,
+            holder: holder.to_string(),
+            currency,
+        })
+    }
+
+    pub fn account(&self, id: u32) -> Result<&Account, BankError> {
+        self.accounts
+            .iter()
+            .find(|acc| acc.id() == id)
+            .ok_or(BankError::AccountNotFound(id))
+    }
+
+    fn account_mut(&mut self, id: u32) -> Result<&mut Account, BankError> {
+        self.accounts
+            .iter_mut()
+            .find(|acc| acc.id() == id)
+            .ok_or(BankError::AccountNotFound(id))
+    }
+
+    pub fn deposit(&mut self, id: u32, amount: i32) -> Result<i32, BankError> {
+        self.execute(TransactionKind::Deposit { account: id }, amount)?;
+        Ok(self.account(id)?.balance())
+    }
+
+    pub fn withdraw(&mut self, id: u32, amount: i32) -> Result<i32, BankError> {
+        self.execute(TransactionKind::Withdrawal { account: id }, amount)?;
+        Ok(self.account(id)?.balance())
+    }
+
+    // Returns the id of the new transaction
+    pub fn transfer(&mut self, from: u32, to: u32, amount: i32) -> Result<u64, BankError> {
+        self.execute(TransactionKind::Transfer { from, to }, amount)
+    }
+
+    // Every movement of money goes through here:
+    // 0. the amount must be positive (a negative deposit would be a withdrawal in disguise,
+    //    one that even a frozen account would accept)
+    // 1. the accounts must accept it (state, funds and overflow checks happen before
+    //    anything changes, so a transfer can't leave money "in the air" half way)
+    //    and a transfer between two currencies gets converted with today's rate
+    // 2. the fraud rules look at it, and may flag or block it
+    // 3. it gets recorded as an event, and applied
+    fn execute(&mut self, kind: TransactionKind, amount: i32) -> Result<u64, BankError> {
+        if amount <= 0 {
+            return Err(BankError::InvalidAmount(amount));
+        }
+
+        let mut conversion = None;
+        match kind {
+            TransactionKind::Deposit { account } => self.check_credit(account, amount)?,
+            TransactionKind::Withdrawal { account } => self.check_funds(account, amount)?,
+            TransactionKind::Transfer { from, to } => {
+                self.check_funds(from, amount)?;
+                let (from_currency, to_currency) =
+                    (self.account(from)?.currency(), self.account(to)?.currency());
+                conversion = self.conversion(amount, from_currency, to_currency)?;
+                let credited = conversion.map(|c| c.converted).unwrap_or(amount);
+                self.check_credit(to, credited)?;
+            }
+        }
+
+        // Blocked transactions use up an id too, so that flags never point to the wrong one
+        let transaction = Transaction {
+            id: self.next_transaction(),
+            kind,
+            amount,
+            at: self.today(),
+            conversion,
+            reverses: None,
+        };
+
+        // When a rule blocks the transaction, the flags raised by the other rules
+        // are about a rejected transaction too
+        let evaluation = self.rules.evaluate(&transaction, &self.transactions);
+        let blocked = evaluation.block.is_some();
+        for (rule, reason) in evaluation.flags {
+            self.flags.push(Flag {
+                transaction: transaction.clone(),
+                rule,
+                reason,
+                blocked,
+            });
+        }
+        if let Some((rule, reason)) = evaluation.block {
+            self.flags.push(Flag {
+                transaction,
+                rule: rule.clone(),
+                reason: reason.clone(),
+                blocked: true,
+            });
+            return Err(BankError::Blocked { rule, reason });
+        }
+
+        let event = match kind {
+            TransactionKind::Deposit { account } => Event::Deposited {
+                transaction: transaction.id,
+                account,
+                amount,
+            },
+            TransactionKind::Withdrawal { account } => Event::Withdrawn {
+                transaction: transaction.id,
+                account,
+                amount,
+            },
+            TransactionKind::Transfer { from, to } => Event::Transferred {
+                transaction: transaction.id,
+                from,
+                to,
+                amount,
+                conversion,
+            },
+        };
+        self.record(event)?;
+
+        Ok(transaction.id)
+    }
+
+    // Undoes a transaction by recording a compensating one (money goes back where it came from).
+    // Fraud rules don't apply, and frozen or dormant accounts are fine, but closed ones aren't,
+    // and the account the money is taken back from must still have it.
+    pub fn reverse_transaction(&mut self, id: u64, reason: &str) -> Result<u64, BankError> {
+        let original = self.transaction(id)?;
+        if self.transactions.iter().any(|tx| tx.reverses == Some(id)) {
+            return Err(BankError::AlreadyReversed(id));
+        }
+        if let Some(product) = self.product_of(id) {
+            return Err(BankError::NotReversible {
+                transaction: id,
+                product,
+            });
+        }
+        for account in original.accounts() {
+            if self.account(account)?.state() == AccountState::Closed {
+                return Err(BankError::Account(AccountError::Closed { id: account }));
+            }
+        }
+        // The money that comes back must fit on the account, the money taken back must be there
+        for leg in original.reversal(0, self.today()).legs() {
+            if leg.amount < 0 {
+                self.check_balance(leg.account, -leg.amount)?;
+            } else {
+                self.account(leg.account)?.balance_after(leg.amount)?;
+            }
+        }
+
+        let transaction = self.next_transaction();
+        self.record(Event::Reversed {
+            transaction,
+            original: id,
+            reason: reason.to_string(),
+        })?;
+
+        Ok(transaction)
+    }
+
+    // "loan 2" when the transaction was made by a loan (or a credit line, or a standing
+    // order), None for a plain deposit, withdrawal or transfer
+    fn product_of(&self, transaction: u64) -> Option<String> {
+        self.events()
+            .iter()
+            .find_map(|recorded| match &recorded.event {
+                Event::LoanOpened {
+                    loan,
+                    transaction: id,
+                    ..
+                }
+                | Event::LoanRepaid {
+                    loan,
+                    transaction: id,
+                    ..
+                } if *id == transaction => Some(format!("loan {}", loan)),
+                Event::CreditDrawn {
+                    line,
+                    transaction: id,
+                    ..
+                }
+                | Event::CreditRepaid {
+                    line,
+                    transaction: id,
+                    ..
+                } if *id == transaction => Some(format!("credit line {}", line)),
+                Event::StandingOrderRun {
+                    order,
+                    outcome: Outcome::Executed { transaction: id },
+                } if *id == transaction => Some(format!("standing order {}", order)),
+                _ => None,
+            })
+    }
+
+    fn next_transaction(&mut self) -> u64 {
+        let id = self.next_transaction_id;
+        self.next_transaction_id += 1;
+        id
+    }
+
+    pub fn transaction(&self, id: u64) -> Result<&Transaction, BankError> {
+        self.transactions
+            .iter()
+            .find(|tx| tx.id == id)
+            .ok_or(BankError::TransactionNotFound(id))
+    }
+
+    // Every change goes through here: apply the event, then keep it (and maybe take a snapshot)
+    fn record(&mut self, event: Event) -> Result<(), BankError> {
+        let at = self.today();
+        self.apply(&event, at)?;
+        self.keep(RecordedEvent {
+            sequence: self.events.next_sequence(),
+            at,
+            event,
+        });
+        Ok(())
+    }
+
+    fn keep(&mut self, recorded: RecordedEvent) {
+        let at = recorded.at;
+        if self.events.append(recorded) {
+            let snapshot = self.snapshot(at);
+            self.events.add_snapshot(snapshot);
+        }
+    }
+
+    fn snapshot(&self, at: Date) -> Snapshot {
+        Snapshot {
+            sequence: self.events.events().len() as u64,
+            at,
+            accounts: self.accounts.clone(),
+            transactions: self.transactions.clone(),
+            entries: self.entries.clone(),
+            next_transaction_id: self.next_transaction_id,
+            loans: self.loans.clone(),
+            credit_lines: self.credit_lines.clone(),
+            standing_orders: self.standing_orders.clone(),
+        }
+    }
+
+    // The only place where the state of the bank changes.
+    // Replaying the same events always gives the same bank.
+    fn apply(&mut self, event: &Event, at: Date) -> Result<(), BankError> {
+        match event {
+            Event::AccountOpened {
+                account,
+                holder,
+                currency,
+            } => {
+                let opened = Account::new(*account, holder.clone(), at).in_currency(*currency);
+                self.accounts.push(opened);
+            }
+            Event::Deposited {
+                transaction,
+                account,
+                amount,
+            } => self.deposit_movement(*transaction, *account, *amount, at)?,
+            Event::Withdrawn {
+                transaction,
+                account,
+                amount,
+            } => self.withdrawal_movement(*transaction, *account, *amount, at)?,
+            Event::Transferred {
+                transaction,
+                from,
+                to,
+                amount,
+                conversion,
+            } => {
+                let transfer = Transaction {
+                    id: *transaction,
+                    kind: TransactionKind::Transfer {
+                        from: *from,
+                        to: *to,
+                    },
+                    amount: *amount,
+                    at,
+                    conversion: *conversion,
+                    reverses: None,
+                };
+                self.account_mut(*from)?.withdraw(*amount, at)?;
+                self.account_mut(*to)?.deposit(transfer.credited(), at)?;
+                self.add_transaction(transfer);
+            }
+            Event::StateChanged {
+                account,
+                to,
+                reason,
+            } => {
+                self.account_mut(*account)?.transition(*to, reason, at)?;
+            }
+            Event::Closed { account, reason } => {
+                self.account_mut(*account)?
+                    .transition(AccountState::Closed, reason, at)?;
+            }
+            Event::Reversed {
+                transaction,
+                original,
+                ..
+            } => {
+                let reversal = self.transaction(*original)?.reversal(*transaction, at);
+                for leg in reversal.legs() {
+                    self.account_mut(leg.account)?.correct(leg.amount, at)?;
+                }
+                self.add_transaction(reversal);
+            }
+            Event::LoanOpened {
+                loan,
+                account,
+                principal,
+                annual_rate_bp,
+                term_months,
+                transaction,
+            } => {
+                self.loans.push(Loan::new(
+                    *loan,
+                    *account,
+                    *principal,
+                    *annual_rate_bp,
+                    *term_months,
+                    at,
+                ));
+                self.deposit_movement(*transaction, *account, *principal, at)?;
+            }
+            Event::LoanRepaid {
+                loan,
+                installment,
+                transaction,
+                amount,
+            } => {
+                let account = self.loan(*loan)?.account;
+                self.withdrawal_movement(*transaction, account, *amount, at)?;
+                let loan = self.loan_mut(*loan)?;
+                match installment {
+                    Some(number) => loan.pay_installment(*number, at),
+                    None => loan.pay_off(at),
+                }
+            }
+            Event::LatePenaltyCharged {
+                loan,
+                installment,
+                amount,
+            } => self.loan_mut(*loan)?.charge_penalty(*installment, *amount),
+            Event::CreditLineOpened {
+                line,
+                account,
+                limit,
+            } => self
+                .credit_lines
+                .push(CreditLine::new(*line, *account, *limit)),
+            Event::CreditDrawn {
+                line,
+                transaction,
+                amount,
+            } => {
+                let account = self.credit_line(*line)?.account;
+                self.deposit_movement(*transaction, account, *amount, at)?;
+                self.credit_line_mut(*line)?.drawn += amount;
+            }
+            Event::CreditRepaid {
+                line,
+                transaction,
+                amount,
+            } => {
+                let account = self.credit_line(*line)?.account;
+                self.withdrawal_movement(*transaction, account, *amount, at)?;
+                self.credit_line_mut(*line)?.drawn -= amount;
+            }
+            Event::StandingOrderCreated {
+                order,
+                from,
+                to,
+                amount,
+                schedule,
+                max_retries,
+            } => self.standing_orders.push(StandingOrder::new(
+                *order,
+                *from,
+                *to,
+                *amount,
+                *schedule,
+                *max_retries,
+                at,
+            )),
+            Event::StandingOrderCancelled { order } => {
+                self.standing_order_mut(*order)?.cancelled_on = Some(at);
+            }
+            Event::StandingOrderRun { order, outcome } => {
+                self.standing_order_mut(*order)?.record(outcome.clone(), at);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn deposit_movement(
+        &mut self,
+        id: u64,
+        account: u32,
+        amount: i32,
+        at: Date,
+    ) -> Result<(), BankError> {
+        self.account_mut(account)?.deposit(amount, at)?;
+        self.add_transaction(Transaction {
+            id,
+            kind: TransactionKind::Deposit { account },
+            amount,
+            at,
+            conversion: None,
+            reverses: None,
+        });
+        Ok(())
+    }
+
+    fn withdrawal_movement(
+        &mut self,
+        id: u64,
+        account: u32,
+        amount: i32,
+        at: Date,
+    ) -> Result<(), BankError> {
+        self.account_mut(account)?.withdraw(amount, at)?;
+        self.add_transaction(Transaction {
+            id,
+            kind: TransactionKind::Withdrawal { account },
+            amount,
+            at,
+            conversion: None,
+            reverses: None,
+        });
+        Ok(())
+    }
+
+    fn add_transaction(&mut self, transaction: Transaction) {
+        self.next_transaction_id = self.next_transaction_id.max(transaction.id + 1);
+        self.entries.extend(transaction.legs());
+        self.transactions.push(transaction);
+    }
+
+    // Rebuilds a bank from scratch, only from its events
+    pub fn from_events(clock: Clock, events: &[RecordedEvent]) -> Result<Self, BankError> {
+        let mut bank = Bank::new(clock);
+        for recorded in events {
+            bank.apply(&recorded.event, recorded.at)?;
+            bank.keep(recorded.clone());
+        }
+
+        Ok(bank)
+    }
+
+    // The bank as it was at the end of 'date': starts from the latest snapshot taken
+    // before that day (if any), then replays the events that follow it.
+    pub fn as_of(&self, date: Date) -> Result<Self, BankError> {
+        let mut bank = Bank::new(Clock::new(date)).with_fx_rates(self.fx_rates.clone());
+        let mut after = 0;
+
+        if let Some(snapshot) = self.events.latest_snapshot_until(date) {
+            bank.accounts = snapshot.accounts.clone();
+            bank.transactions = snapshot.transactions.clone();
+            bank.entries = snapshot.entries.clone();
+            bank.next_transaction_id = snapshot.next_transaction_id;
+            bank.loans = snapshot.loans.clone();
+            bank.credit_lines = snapshot.credit_lines.clone();
+            bank.standing_orders = snapshot.standing_orders.clone();
+            after = snapshot.sequence;
+        }
+
+        let events = self.events.events().iter();
+        for recorded in events
+            .skip_while(|e| e.sequence <= after)
+            .take_while(|e| e.at <= date)
+        {
+            bank.apply(&recorded.event, recorded.at)?;
+        }
+
+        Ok(bank)
+    }
+
+    // "What was the balance on date X?"
+    pub fn balance_on(&self, id: u32, date: Date) -> Result<i32, BankError> {
+        Ok(self.as_of(date)?.account(id)?.balance())
+    }
+
+    pub fn events(&self) -> &[RecordedEvent] {
+        self.events.events()
+    }
+
+    pub fn snapshots(&self) -> &[Snapshot] {
+        self.events.snapshots()
+    }
+
+    // None when both sides use the same currency
+    fn conversion(
+        &self,
+        amount: i32,
+        from: Currency,
+        to: Currency,
+    ) -> Result<Option<Conversion>, BankError> {
+        if from == to {
+            return Ok(None);
+        }
+
+        Ok(Some(self.fx_rates.convert(
+            amount,
+            from,
+            to,
+            self.fx_rates.rounding(),
+        )?))
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    pub fn accounts(&self) -> &[Account] {
+        &self.accounts
+    }
+
+    pub fn flags(&self) -> &[Flag] {
+        &self.flags
+    }
+
+    pub fn flags_for_account(&self, id: u32) -> Vec<&Flag> {
+        self.flags
+            .iter()
+            .filter(|flag| flag.transaction.involves(id))
+            .collect()
+    }
+
+    pub fn freeze(&mut self, id: u32, reason: &str) -> Result<(), BankError> {
+        self.transition(id, AccountState::Frozen, reason)
+    }
+
+    // Brings a frozen or dormant account back to 'Open'
+    pub fn reopen(&mut self, id: u32, reason: &str) -> Result<(), BankError> {
+        self.transition(id, AccountState::Open, reason)
+    }
+
+    pub fn close(&mut self, id: u32, reason: &str) -> Result<(), BankError> {
+        self.transition(id, AccountState::Closed, reason)
+    }
+
+    fn transition(&mut self, id: u32, to: AccountState, reason: &str) -> Result<(), BankError> {
+        let account = self.account(id)?;
+        if !account.state().can_transition_to(to) {
+            return Err(BankError::Account(AccountError::IllegalTransition {
+                id,
+                from: account.state(),
+                to,
+            }));
+        }
+
+        let reason = reason.to_string();
+        let event = match to {
+            AccountState::Closed => Event::Closed {
+                account: id,
+                reason,
+            },
+            _ => Event::StateChanged {
+                account: id,
+                to,
+                reason,
+            },
+        };
+        self.record(event)
+    }
+
+    // Moves the clock forward one day at a time, so that every day gets its loan
+    // installments collected and its standing orders run.
+    // Returns the accounts that became dormant in the meantime.
+    pub fn advance_days(&mut self, days: u32) -> Vec<u32> {
+        let mut dormant = vec![];
+        for _ in 0..days {
+            self.clock.advance_days(1);
+            self.collect_loan_installments();
+            self.run_standing_orders();
+            dormant.extend(self.mark_dormant_accounts());
+        }
+        dormant
+    }
+
+    // Returns the ids of the accounts that just became dormant.
+    pub fn mark_dormant_accounts(&mut self) -> Vec<u32> {
+        let today = self.today();
+        let period = self.dormancy_period_days;
+        let reason = format!("no activity for {} days", period);
+
+        let inactive: Vec<u32> = self
+            .accounts
+            .iter()
+            .filter(|acc| acc.state() == AccountState::Open)
+            .filter(|acc| acc.is_inactive_since(today, period))
+            .map(|acc| acc.id())
+            .collect();
+
+        inactive
+            .into_iter()
+            .filter(|id| {
+                self.record(Event::StateChanged {
+                    account: *id,
+                    to: AccountState::Dormant,
+                    reason: reason.clone(),
+                })
+                .is_ok()
+            })
+            .collect()
+    }
+
+    // Loans and credit lines
+    // (their money flows are regular deposits and withdrawals on the linked account,
+    // so 'total_balance' and the audit see them, but fraud rules don't apply to them)
+
+    pub fn open_loan(
+        &mut self,
+        account: u32,
+        principal: i32,
+        annual_rate_bp: u32,
+        term_months: u32,
+    ) -> Result<u32, BankError> {
+        if principal <= 0 || term_months == 0 {
+            return Err(BankError::Loan(LoanError::InvalidTerms(format!(
+                "principal {} over {} months",
+                principal, term_months
+            ))));
+        }
+        self.check_credit(account, principal)?;
+
+        let loan = self.loans.len() as u32 + 1;
+        let transaction = self.next_transaction();
+        self.record(Event::LoanOpened {
+            loan,
+            account,
+            principal,
+            annual_rate_bp,
+            term_months,
+            transaction,
+        })?;
+
+        Ok(loan)
+    }
+
+    pub fn loans(&self) -> &[Loan] {
+        &self.loans
+    }
+
+    pub fn loan(&self, id: u32) -> Result<&Loan, BankError> {
+        self.loans
+            .iter()
+            .find(|loan| loan.id == id)
+            .ok_or(BankError::Loan(LoanError::LoanNotFound(id)))
+    }
+
+    fn loan_mut(&mut self, id: u32) -> Result<&mut Loan, BankError> {
+        self.loans
+            .iter_mut()
+            .find(|loan| loan.id == id)
+            .ok_or(BankError::Loan(LoanError::LoanNotFound(id)))
+    }
+
+    // Pays everything that's left today. Returns the amount taken from the linked account.
+    pub fn pay_off_loan(&mut self, id: u32) -> Result<i32, BankError> {
+        let loan = self.loan(id)?;
+        if loan.is_paid_off() {
+            return Err(BankError::Loan(LoanError::AlreadyPaidOff(id)));
+        }
+        let (account, amount) = (loan.account, loan.payoff_amount());
+        self.check_funds(account, amount)?;
+
+        let transaction = self.next_transaction();
+        self.record(Event::LoanRepaid {
+            loan: id,
+            installment: None,
+            transaction,
+            amount,
+        })?;
+
+        Ok(amount)
+    }
+
+    // Anything that brings money to an account (deposits, transfers, loans, credit)
+    // must be accepted by it, and keep its balance within an i32
+    fn check_credit(&self, id: u32, amount: i32) -> Result<(), BankError> {
+        let account = self.account(id)?;
+        account.check_deposit()?;
+        account.balance_after(amount)?;
+        Ok(())
+    }
+
+    // Nothing that takes money from an account (withdrawals, transfers, repayments)
+    // ever pushes it below zero
+    fn check_funds(&self, id: u32, amount: i32) -> Result<(), BankError> {
+        self.account(id)?.check_withdraw()?;
+        self.check_balance(id, amount)
+    }
+
+    // Only the money, whatever the state of the account (for reversals)
+    fn check_balance(&self, id: u32, amount: i32) -> Result<(), BankError> {
+        if self.account(id)?.balance() < amount {
+            return Err(BankError::InsufficientFunds {
+                account: id,
+                needed: amount,
+            });
+        }
+        Ok(())
+    }
+
+    // Collects the installments that are due. When there isn't enough money on the linked
+    // account, a late penalty is charged (once per installment) and we try again tomorrow.
+    fn collect_loan_installments(&mut self) {
+        let today = self.today();
+        let due: Vec<(u32, u32, u32, i32, i32)> = self
+            .loans
+            .iter()
+            .filter(|loan| !loan.is_paid_off())
+            .filter_map(|loan| {
+                loan.next_installment()
+                    .filter(|installment| installment.due <= today)
+                    .map(|i| (loan.id, loan.account, i.number, i.payment, i.penalty))
+            })
+            .collect();
+
+        for (loan, account, installment, payment, penalty) in due {
+            let event = if self.check_funds(account, payment + penalty).is_ok() {
+                Event::LoanRepaid {
+                    loan,
+                    installment: Some(installment),
+                    transaction: self.next_transaction(),
+                    amount: payment + penalty,
+                }
+            } else if penalty == 0 {
+                Event::LatePenaltyCharged {
+                    loan,
+                    installment,
+                    amount: RoundingMode::HalfUp
+                        .divide(payment as i64 * self.late_penalty_bp as i64, BASIS_POINTS)
+                        as i32,
+                }
+            } else {
+                continue;
+            };
+
+            // Can't fail: the loan and the account exist, and the funds were checked
+            let _ = self.record(event);
+        }
+    }
+
+    pub fn open_credit_line(&mut self, account: u32, limit: i32) -> Result<u32, BankError> {
+        if limit <= 0 {
+            return Err(BankError::Loan(LoanError::InvalidTerms(format!(
+                "credit limit of {}",
+                limit
+            ))));
+        }
+        self.account(account)?;
+
+        let line = self.credit_lines.len() as u32 + 1;
+        self.record(Event::CreditLineOpened {
+            line,
+            account,
+            limit,
+        })?;
+
+        Ok(line)
+    }
+
+    pub fn credit_line(&self, id: u32) -> Result<&CreditLine, BankError> {
+        self.credit_lines
+            .iter()
+            .find(|line| line.id == id)
+            .ok_or(BankError::Loan(LoanError::CreditLineNotFound(id)))
+    }
+
+    fn credit_line_mut(&mut self, id: u32) -> Result<&mut CreditLine, BankError> {
+        self.credit_lines
+            .iter_mut()
+            .find(|line| line.id == id)
+            .ok_or(BankError::Loan(LoanError::CreditLineNotFound(id)))
+    }
+
+    // Moves money from the credit line to its account
+    pub fn draw_credit(&mut self, line: u32, amount: i32) -> Result<u64, BankError> {
+        let credit_line = self.credit_line(line)?;
+        credit_line.check_draw(amount)?;
+        self.check_credit(credit_line.account, amount)?;
+
+        let transaction = self.next_transaction();
+        self.record(Event::CreditDrawn {
+            line,
+            transaction,
+            amount,
+        })?;
+
+        Ok(transaction)
+    }
+
+    // Moves money from the account back to the credit line
+    pub fn repay_credit(&mut self, line: u32, amount: i32) -> Result<u64, BankError> {
+        let credit_line = self.credit_line(line)?;
+        credit_line.check_repay(amount)?;
+        self.check_funds(credit_line.account, amount)?;
+
+        let transaction = self.next_transaction();
+        self.record(Event::CreditRepaid {
+            line,
+            transaction,
+            amount,
+        })?;
+
+        Ok(transaction)
+    }
+
+    // What customers owe the bank (loans and credit lines), in the reporting currency.
+    // 'total_balance' is what the bank owes its customers: deposits, including the money
+    // that was lent and is now sitting on the accounts.
+    pub fn total_lending(&self, reporting: Currency) -> Result<i32, BankError> {
+        let loans = self
+            .loans
+            .iter()
+            .map(|loan| (loan.account, loan.payoff_amount()));
+        let lines = self
+            .credit_lines
+            .iter()
+            .map(|line| (line.account, line.drawn));
+
+        let mut total: i32 = 0;
+        for (account, owed) in loans.chain(lines) {
+            let currency = self.account(account)?.currency();
+            let converted =
+                self.fx_rates
+                    .convert(owed, currency, reporting, self.fx_rates.rounding())?;
+            total = total
+                .checked_add(converted.converted)
+                .ok_or(BankError::Fx(FxError::Overflow))?;
+        }
+
+        Ok(total)
+    }
+
+    // Standing orders
+    // (each run is a regular transfer, so fraud rules and conversions apply to it)
+
+    // The first run is on the first scheduled day after today
+    pub fn create_standing_order(
+        &mut self,
+        from: u32,
+        to: u32,
+        amount: i32,
+        schedule: Schedule,
+    ) -> Result<u32, BankError> {
+        if amount <= 0 {
+            return Err(BankError::StandingOrder(StandingOrderError::InvalidAmount(
+                amount,
+            )));
+        }
+        self.account(from)?.check_deposit()?;
+        self.account(to)?.check_deposit()?;
+
+        let order = self.standing_orders.len() as u32 + 1;
+        self.record(Event::StandingOrderCreated {
+            order,
+            from,
+            to,
+            amount,
+            schedule,
+            max_retries: self.standing_order_retries,
+        })?;
+
+        Ok(order)
+    }
+
+    pub fn cancel_standing_order(&mut self, id: u32) -> Result<(), BankError> {
+        if !self.standing_order(id)?.is_active() {
+            return Err(BankError::StandingOrder(
+                StandingOrderError::AlreadyCancelled(id),
+            ));
+        }
+        self.record(Event::StandingOrderCancelled { order: id })
+    }
+
+    pub fn standing_orders(&self) -> &[StandingOrder] {
+        &self.standing_orders
+    }
+
+    pub fn standing_order(&self, id: u32) -> Result<&StandingOrder, BankError> {
+        self.standing_orders
+            .iter()
+            .find(|order| order.id == id)
+            .ok_or(BankError::StandingOrder(StandingOrderError::NotFound(id)))
+    }
+
+    fn standing_order_mut(&mut self, id: u32) -> Result<&mut StandingOrder, BankError> {
+        self.standing_orders
+            .iter_mut()
+            .find(|order| order.id == id)
+            .ok_or(BankError::StandingOrder(StandingOrderError::NotFound(id)))
+    }
+
+    // Runs the orders that are due. Not enough money means another try tomorrow
+    // (up to the order's 'max_retries'), any other refusal skips the run.
+    fn run_standing_orders(&mut self) {
+        let today = self.today();
+        let due: Vec<(u32, u32, u32, i32, u32, u32)> = self
+            .standing_orders
+            .iter()
+            .filter(|order| order.is_due(today))
+            .map(|o| (o.id, o.from, o.to, o.amount, o.retries, o.max_retries))
+            .collect();
+
+        for (order, from, to, amount, retries, max_retries) in due {
+            let outcome = match self.transfer(from, to, amount) {
+                Ok(transaction) => Outcome::Executed { transaction },
+                Err(error @ BankError::InsufficientFunds { .. }) if retries < max_retries => {
+                    Outcome::Retrying {
+                        attempt: retries + 1,
+                        reason: error.to_string(),
+                    }
+                }
+                Err(error) => Outcome::Failed {
+                    reason: error.to_string(),
+                },
+            };
+
+            // Can't fail: the order exists
+            let _ = self.record(Event::StandingOrderRun { order, outcome });
+        }
+    }
+
+    // Every balance gets converted into the reporting currency before being added up
+    pub fn total_balance(&self, reporting: Currency) -> Result<i32, BankError> {
+        let mut total: i32 = 0;
+
+        for acc in &self.accounts {
+            let converted = self.fx_rates.convert(
+                acc.balance(),
+                acc.currency(),
+                reporting,
+                self.fx_rates.rounding(),
+            )?;
+            total = total
+                .checked_add(converted.converted)
+                .ok_or(BankError::Fx(FxError::Overflow))?;
+        }
+
+        Ok(total)
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        self.accounts
+            .iter()
+            .map(|acc| acc.summary())
+            .collect::<Vec<String>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{Discrepancy, reconcile};
+    use crate::currency::RATE_SCALE;
+
+    fn day(day: u32) -> Date {
+        Date::new(2024, 1, day).unwrap()
+    }
+
+    // #1 and #2 are held in EUR, #1 has 100 on it
+    fn bank() -> Bank {
+        accounts(Bank::new(Clock::new(day(1))))
+    }
+
+    // The same accounts, on a bank built with other settings
+    fn accounts(mut bank: Bank) -> Bank {
+        bank.open_account(1, "me", Currency::Eur).unwrap();
+        bank.open_account(2, "you", Currency::Eur).unwrap();
+        bank.deposit(1, 100).unwrap();
+        bank
+    }
+
+    fn balance(bank: &Bank, id: u32) -> i32 {
+        bank.account(id).unwrap().balance()
+    }
+
+    #[test]
+    fn frozen_accounts_take_deposits_but_no_withdrawals() {
+        let mut bank = bank();
+        bank.freeze(1, "test").unwrap();
+
+        assert_eq!(bank.deposit(1, 10), Ok(110));
+        assert_eq!(
+            bank.withdraw(1, 10),
+            Err(BankError::Account(AccountError::Frozen { id: 1 }))
+        );
+        assert_eq!(
+            bank.transfer(1, 2, 10),
+            Err(BankError::Account(AccountError::Frozen { id: 1 }))
+        );
+        bank.reopen(1, "test").unwrap();
+        assert_eq!(bank.withdraw(1, 10), Ok(100));
+    }
+
+    #[test]
+    fn closed_accounts_are_read_only() {
+        let mut bank = bank();
+        bank.close(2, "test").unwrap();
+
+        let closed = BankError::Account(AccountError::Closed { id: 2 });
+        assert_eq!(bank.deposit(2, 10), Err(closed.clone()));
+        assert_eq!(bank.transfer(1, 2, 10), Err(closed));
+        assert_eq!(
+            bank.reopen(2, "test"),
+            Err(BankError::Account(AccountError::IllegalTransition {
+                id: 2,
+                from: AccountState::Closed,
+                to: AccountState::Open,
+            }))
+        );
+    }
+
+    #[test]
+    fn inactive_accounts_become_dormant_and_wake_up_on_deposit() {
+        let mut bank = bank().with_dormancy_period(10);
+        bank.deposit(2, 10).unwrap();
+        bank.advance_days(5);
+        bank.deposit(2, 10).unwrap();
+
+        // #1 hasn't moved for 10 days, #2 only for 5
+        assert_eq!(bank.advance_days(5), vec![1]);
+        assert_eq!(bank.account(1).unwrap().state(), AccountState::Dormant);
+        assert_eq!(
+            bank.withdraw(1, 10),
+            Err(BankError::Account(AccountError::Dormant { id: 1 }))
+        );
+
+        assert_eq!(bank.deposit(1, 10), Ok(110));
+        assert_eq!(bank.account(1).unwrap().state(), AccountState::Open);
+        // A reopened account doesn't go back to sleep the next day
+        assert_eq!(bank.advance_days(1), vec![]);
+    }
+
+    #[test]
+    fn amounts_must_be_positive_whatever_the_state() {
+        let mut bank = bank();
+        bank.freeze(1, "test").unwrap();
+
+        assert_eq!(bank.deposit(1, -50), Err(BankError::InvalidAmount(-50)));
+        assert_eq!(bank.deposit(2, 0), Err(BankError::InvalidAmount(0)));
+        assert_eq!(balance(&bank, 1), 100);
+    }
+
+    #[test]
+    fn balances_never_overflow() {
+        let mut bank = bank();
+        let overflow = BankError::Account(AccountError::Overflow { id: 2 });
+
+        bank.deposit(2, i32::MAX).unwrap();
+        assert_eq!(bank.deposit(2, 1), Err(overflow.clone()));
+        assert_eq!(bank.transfer(1, 2, 1), Err(overflow.clone()));
+        assert_eq!(bank.open_loan(2, 1, 0, 12), Err(overflow.clone()));
+        let line = bank.open_credit_line(2, 100).unwrap();
+        assert_eq!(bank.draw_credit(line, 1), Err(overflow));
+
+        // Nothing moved
+        assert_eq!(balance(&bank, 1), 100);
+        assert_eq!(balance(&bank, 2), i32::MAX);
+        assert_eq!(bank.transactions().len(), 2);
+    }
+
+    #[test]
+    fn reversal_never_leaves_a_negative_balance() {
+        let mut bank = bank();
+        bank.deposit(2, 100).unwrap();
+        let deposit = bank.transactions().last().unwrap().id;
+        bank.withdraw(2, 100).unwrap();
+
+        assert_eq!(
+            bank.reverse_transaction(deposit, "mistake"),
+            Err(BankError::InsufficientFunds {
+                account: 2,
+                needed: 100
+            })
+        );
+        assert_eq!(balance(&bank, 2), 0);
+        assert!(bank.transactions().iter().all(|tx| tx.reverses.is_none()));
+    }
+
+    #[test]
+    fn reversal_moves_the_money_back_once() {
+        let mut bank = bank();
+        let transfer = bank.transfer(1, 2, 40).unwrap();
+        // Frozen accounts can still be corrected
+        bank.freeze(2, "test").unwrap();
+
+        let reversal = bank.reverse_transaction(transfer, "mistake").unwrap();
+        assert_eq!((balance(&bank, 1), balance(&bank, 2)), (100, 0));
+        assert_eq!(bank.transaction(reversal).unwrap().reverses, Some(transfer));
+        assert_eq!(
+            bank.reverse_transaction(transfer, "again"),
+            Err(BankError::AlreadyReversed(transfer))
+        );
+    }
+
+    #[test]
+    fn replaying_the_events_gives_the_same_bank() {
+        let mut bank = accounts(Bank::new(Clock::new(day(1))).with_snapshot_every(2));
+        bank.transfer(1, 2, 30).unwrap();
+        bank.advance_days(1);
+        bank.withdraw(2, 10).unwrap();
+        bank.freeze(1, "test").unwrap();
+
+        let rebuilt = Bank::from_events(Clock::new(bank.today()), bank.events()).unwrap();
+        for id in [1, 2] {
+            let (account, again) = (bank.account(id).unwrap(), rebuilt.account(id).unwrap());
+            assert_eq!(account.balance(), again.balance());
+            assert_eq!(account.state(), again.state());
+        }
+        assert_eq!(rebuilt.transactions().len(), bank.transactions().len());
+        assert_eq!(rebuilt.events().len(), bank.events().len());
+    }
+
+    #[test]
+    fn balances_in_the_past_come_from_snapshots_and_events() {
+        let mut bank = accounts(Bank::new(Clock::new(day(1))).with_snapshot_every(3));
+        bank.advance_days(1);
+        bank.transfer(1, 2, 30).unwrap();
+        bank.advance_days(1);
+        bank.withdraw(1, 20).unwrap();
+
+        assert!(!bank.snapshots().is_empty());
+        assert_eq!(bank.balance_on(1, day(1)), Ok(100));
+        assert_eq!(bank.balance_on(1, day(2)), Ok(70));
+        assert_eq!(bank.balance_on(1, day(3)), Ok(50));
+        assert_eq!(bank.balance_on(2, day(2)), Ok(30));
+    }
+
+    #[test]
+    fn loan_installments_are_collected_when_due() {
+        let mut bank = bank();
+        let loan = bank.open_loan(2, 1200, 600, 12).unwrap();
+        assert_eq!(balance(&bank, 2), 1200);
+
+        let schedule = &bank.loan(loan).unwrap().schedule;
+        let principal: i32 = schedule.iter().map(|i| i.principal).sum();
+        assert_eq!(principal, 1200);
+        let first = schedule[0].payment;
+
+        bank.advance_days(31);
+        assert_eq!(balance(&bank, 2), 1200 - first);
+        assert_eq!(
+            bank.loan(loan).unwrap().next_installment().unwrap().number,
+            2
+        );
+    }
+
+    #[test]
+    fn missed_installment_gets_one_penalty() {
+        let mut bank = bank().with_late_penalty(1000);
+        let loan = bank.open_loan(2, 1200, 0, 12).unwrap();
+        bank.withdraw(2, 1200).unwrap();
+
+        bank.advance_days(33);
+        let installment = bank.loan(loan).unwrap().next_installment().unwrap().clone();
+        assert_eq!((installment.number, installment.penalty), (1, 10));
+
+        // Collected the next day, with its penalty (and only once)
+        bank.deposit(2, 110).unwrap();
+        bank.advance_days(1);
+        assert_eq!(balance(&bank, 2), 0);
+        let installment = bank.loan(loan).unwrap().next_installment().unwrap().clone();
+        assert_eq!((installment.number, installment.penalty), (2, 0));
+    }
+
+    #[test]
+    fn paying_off_a_loan_takes_the_principal_left() {
+        let mut bank = bank();
+        let loan = bank.open_loan(1, 1000, 1200, 10).unwrap();
+        assert_eq!(bank.pay_off_loan(loan), Ok(1000));
+        assert_eq!(balance(&bank, 1), 100);
+        assert_eq!(
+            bank.pay_off_loan(loan),
+            Err(BankError::Loan(LoanError::AlreadyPaidOff(loan)))
+        );
+    }
+
+    #[test]
+    fn credit_lines_stay_within_their_limit() {
+        let mut bank = bank();
+        let line = bank.open_credit_line(2, 500).unwrap();
+
+        bank.draw_credit(line, 300).unwrap();
+        assert_eq!(
+            bank.draw_credit(line, 300),
+            Err(BankError::Loan(LoanError::LimitExceeded {
+                line,
+                available: 200
+            }))
+        );
+        assert_eq!(
+            bank.repay_credit(line, 400),
+            Err(BankError::Loan(LoanError::Overpayment { line, drawn: 300 }))
+        );
+        assert_eq!(
+            bank.draw_credit(line, -300),
+            Err(BankError::Loan(LoanError::InvalidAmount {
+                line,
+                amount: -300
+            }))
+        );
+        bank.repay_credit(line, 100).unwrap();
+        assert_eq!(bank.credit_line(line).unwrap().drawn, 200);
+        assert_eq!(balance(&bank, 2), 200);
+        assert_eq!(bank.total_lending(Currency::Eur), Ok(200));
+    }
+
+    #[test]
+    fn loan_and_credit_transactions_cant_be_reversed() {
+        let mut bank = bank();
+        let line = bank.open_credit_line(2, 500).unwrap();
+        let draw = bank.draw_credit(line, 300).unwrap();
+        bank.open_loan(1, 1000, 500, 12).unwrap();
+        let principal = bank.transactions().last().unwrap().id;
+
+        assert_eq!(
+            bank.reverse_transaction(draw, "mistake"),
+            Err(BankError::NotReversible {
+                transaction: draw,
+                product: format!("credit line {}", line),
+            })
+        );
+        assert!(matches!(
+            bank.reverse_transaction(principal, "mistake"),
+            Err(BankError::NotReversible { .. })
+        ));
+        assert_eq!(bank.credit_line(line).unwrap().drawn, 300);
+        assert_eq!((balance(&bank, 1), balance(&bank, 2)), (1100, 300));
+    }
+
+    fn rules(toml: &str) -> RuleEngine {
+        RuleEngine::from_toml(toml).unwrap()
+    }
+
+    #[test]
+    fn velocity_limit_blocks_the_transaction_over_the_limit() {
+        let rules =
+            rules("[velocity]\nmax_transactions = 2\nwindow_days = 1\naction = \"block\"\n");
+        let mut bank = accounts(Bank::new(Clock::new(day(1))).with_rules(rules));
+        bank.deposit(1, 10).unwrap();
+
+        assert!(matches!(
+            bank.deposit(1, 10),
+            Err(BankError::Blocked { rule, .. }) if rule == "velocity"
+        ));
+        assert_eq!(balance(&bank, 1), 110);
+        // The window includes the day before: two days later, it's fine again
+        bank.advance_days(2);
+        assert_eq!(bank.deposit(1, 10), Ok(120));
+    }
+
+    #[test]
+    fn every_flag_of_a_blocked_transaction_is_blocked() {
+        let rules = rules(
+            "[velocity]\nmax_transactions = 1\nwindow_days = 1\naction = \"block\"\n\
+             [round_trip]\nwindow_days = 1\naction = \"flag\"\n",
+        );
+        let mut bank = accounts(Bank::new(Clock::new(day(1))).with_rules(rules));
+        bank.advance_days(2);
+        bank.transfer(1, 2, 10).unwrap();
+
+        assert!(bank.transfer(2, 1, 10).is_err());
+        let flags = bank.flags_for_account(2);
+        assert_eq!(flags.len(), 2);
+        assert!(flags.iter().all(|flag| flag.blocked));
+    }
+
+    #[test]
+    fn round_trip_is_flagged_but_goes_through() {
+        let rules = rules("[round_trip]\nwindow_days = 1\naction = \"flag\"\n");
+        let mut bank = accounts(Bank::new(Clock::new(day(1))).with_rules(rules));
+        bank.transfer(1, 2, 10).unwrap();
+        bank.advance_days(1);
+
+        bank.transfer(2, 1, 10).unwrap();
+        assert_eq!(bank.flags().len(), 1);
+        assert_eq!(bank.flags()[0].rule, "round_trip");
+        assert!(!bank.flags()[0].blocked);
+    }
+
+    #[test]
+    fn large_amounts_are_compared_in_the_account_currency() {
+        let rules = rules("[large_amount]\nmultiplier = 2\nmin_history = 2\naction = \"flag\"\n");
+        let mut fx_rates = FxRates::new();
+        fx_rates.set(Currency::Eur, Currency::Usd, 100 * RATE_SCALE);
+        let bank = Bank::new(Clock::new(day(1)))
+            .with_rules(rules)
+            .with_fx_rates(fx_rates);
+        let mut bank = accounts(bank);
+        bank.open_account(3, "them", Currency::Usd).unwrap();
+        bank.deposit(3, 1000).unwrap();
+        bank.deposit(3, 1000).unwrap();
+
+        // 5 EUR arrive as 500 USD: below twice the 1000 USD #3 usually moves
+        bank.transfer(1, 3, 5).unwrap();
+        assert!(bank.flags().is_empty());
+        bank.transfer(1, 3, 30).unwrap();
+        assert_eq!(bank.flags().len(), 1);
+    }
+
+    #[test]
+    fn reconcile_finds_nothing_wrong_with_a_busy_bank() {
+        let mut bank = bank();
+        let transfer = bank.transfer(1, 2, 30).unwrap();
+        bank.withdraw(2, 10).unwrap();
+        bank.reverse_transaction(transfer, "mistake").unwrap_err();
+        bank.deposit(2, 10).unwrap();
+        bank.reverse_transaction(transfer, "mistake").unwrap();
+        bank.open_loan(1, 1000, 600, 12).unwrap();
+        bank.advance_days(40);
+
+        let report = reconcile(&bank);
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!(report.exit_code(), 0);
+        assert_eq!((report.accounts_checked, report.transfers_checked), (2, 2));
+    }
+
+    #[test]
+    fn reconcile_reports_a_missing_leg_and_a_stray_entry() {
+        let mut bank = bank();
+        let transfer = bank.transfer(1, 2, 30).unwrap();
+        // The credit leg of the transfer gets lost, an entry points to nowhere
+        bank.entries.pop();
+        bank.entries.push(LedgerEntry {
+            transaction_id: 99,
+            account: 7,
+            amount: 5,
+        });
+
+        let report = reconcile(&bank);
+        assert_eq!(report.exit_code(), 1);
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                Discrepancy::BalanceMismatch {
+                    account: 2,
+                    stored: 30,
+                    recomputed: 0,
+                },
+                Discrepancy::UnknownAccount {
+                    transaction: 99,
+                    account: 7,
+                },
+                Discrepancy::UnbalancedTransfer {
+                    transaction: transfer,
+                    debit: 30,
+                    credit: 0,
+                    expected_debit: 30,
+                    expected_credit: 30,
+                },
+                Discrepancy::OrphanEntry {
+                    transaction: 99,
+                    account: 7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn transfers_between_currencies_are_converted_and_reversed_exactly() {
+        let fx_rates = FxRates::parse("EUR USD 1.085").unwrap();
+        let mut bank = accounts(Bank::new(Clock::new(day(1))).with_fx_rates(fx_rates));
+        bank.open_account(3, "them", Currency::Usd).unwrap();
+
+        let transfer = bank.transfer(1, 3, 33).unwrap();
+        // 33 * 1.085 = 35.805
+        assert_eq!(balance(&bank, 3), 36);
+        assert_eq!(bank.total_balance(Currency::Eur), Ok(67 + 33));
+
+        bank.reverse_transaction(transfer, "mistake").unwrap();
+        assert_eq!((balance(&bank, 1), balance(&bank, 3)), (100, 0));
+        assert!(reconcile(&bank).is_clean());
+    }
+
+    #[test]
+    fn standing_orders_retry_then_give_up() {
+        let mut bank = accounts(Bank::new(Clock::new(day(1))).with_standing_order_retries(1));
+        let order = bank
+            .create_standing_order(1, 2, 60, Schedule::Daily)
+            .unwrap();
+
+        bank.advance_days(3);
+        let history: Vec<&Outcome> = bank
+            .standing_order(order)
+            .unwrap()
+            .history
+            .iter()
+            .map(|run| &run.outcome)
+            .collect();
+        assert!(matches!(
+            history[..],
+            [
+                Outcome::Executed { .. },
+                Outcome::Retrying { attempt: 1, .. },
+                Outcome::Failed { .. },
+            ]
+        ));
+        assert_eq!((balance(&bank, 1), balance(&bank, 2)), (40, 60));
+
+        let Outcome::Executed { transaction } = history[0] else {
+            unreachable!()
+        };
+        assert!(matches!(
+            bank.reverse_transaction(*transaction, "mistake"),
+            Err(BankError::NotReversible { .. })
+        ));
+    }
+
+    #[test]
+    fn cancelled_standing_orders_stop_running() {
+        let mut bank = bank();
+        let order = bank
+            .create_standing_order(1, 2, 10, Schedule::Daily)
+            .unwrap();
+        bank.advance_days(2);
+        bank.cancel_standing_order(order).unwrap();
+        bank.advance_days(2);
+
+        assert_eq!(balance(&bank, 2), 20);
+        assert_eq!(
+            bank.cancel_standing_order(order),
+            Err(BankError::StandingOrder(
+                StandingOrderError::AlreadyCancelled(order)
+            ))
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+
+// A calendar date (no time of day), good enough for a bank that works "per day".
+// Deriving PartialOrd/Ord lets us compare dates with '<', '>=', ... because the
+// fields are declared from the most significant (year) to the least significant (day).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        if month == 0 || month > 12 || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        Some(Date { year, month, day })
+    }
+
+    // Number of days since 1970-01-01 (Howard Hinnant's "days_from_civil" algorithm).
+    pub fn to_days(self) -> i64 {
+        let year = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        } as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year =
+            (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    // Inverse of 'to_days()' ("civil_from_days").
+    pub fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (year_of_era + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+
+        Date { year, month, day }
+    }
+
+    pub fn add_days(self, days: i64) -> Self {
+        Date::from_days(self.to_days() + days)
+    }
+
+    // Same day, 'months' later. When that day doesn't exist (Jan 31 + 1 month),
+    // we use the last day of the month instead (Feb 28 or 29).
+    pub fn add_months(self, months: u32) -> Self {
+        let index = self.year as i64 * 12 + (self.month as i64 - 1) + months as i64;
+        let year = index.div_euclid(12) as i32;
+        let month = index.rem_euclid(12) as u32 + 1;
+        let day = self.day.min(days_in_month(year, month));
+
+        Date { year, month, day }
+    }
+
+    // 0 = Monday ... 6 = Sunday (1970-01-01 was a Thursday)
+    pub fn weekday(self) -> u32 {
+        (self.to_days() + 3).rem_euclid(7) as u32
+    }
+
+    // Signed number of days from 'self' to 'other' (positive when 'other' is later).
+    pub fn days_until(self, other: Date) -> i64 {
+        other.to_days() - self.to_days()
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+pub fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+// The Bank never asks the system for "today": the clock is injected,
+// so the caller (or a test) decides when time moves forward.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    today: Date,
+}
+
+impl Clock {
+    pub fn new(today: Date) -> Self {
+        Clock { today }
+    }
+
+    pub fn today(&self) -> Date {
+        self.today
+    }
+
+    pub fn advance_days(&mut self, days: u32) -> Date {
+        self.today = self.today.add_days(days as i64);
+        self.today
+    }
+}
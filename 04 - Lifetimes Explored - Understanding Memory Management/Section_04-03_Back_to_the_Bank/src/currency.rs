@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+// Rates are stored as integers ("micro units"): 1.085 is stored as 1_085_000.
+// No floats means no surprise like 0.1 + 0.2 != 0.3 when dealing with money.
+pub const RATE_SCALE: i64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Currency {
+    Eur,
+    Usd,
+}
+
+impl Currency {
+    pub fn parse(code: &str) -> Option<Self> {
+        match code.trim().to_uppercase().as_str() {
+            "EUR" => Some(Currency::Eur),
+            "USD" => Some(Currency::Usd),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = match self {
+            Currency::Eur => "EUR",
+            Currency::Usd => "USD",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+// What to do with the part of a converted amount that doesn't fit in an integer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    // 2.5 -> 3, 3.5 -> 4
+    HalfUp,
+    // "Banker's rounding": 2.5 -> 2, 3.5 -> 4
+    HalfEven,
+    // Towards zero: 2.9 -> 2
+    Down,
+    // Away from zero: 2.1 -> 3
+    Up,
+}
+
+impl RoundingMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "half-up" => Some(RoundingMode::HalfUp),
+            "half-even" => Some(RoundingMode::HalfEven),
+            "down" => Some(RoundingMode::Down),
+            "up" => Some(RoundingMode::Up),
+            _ => None,
+        }
+    }
+
+    // Divides 'value' by 'divisor' (> 0), rounding the result with this mode
+    pub fn divide(self, value: i64, divisor: i64) -> i64 {
+        self.divide_wide(value as i128, divisor as i128) as i64
+    }
+
+    // The same, for intermediate results that don't fit in an i64 (compound interest)
+    pub fn divide_wide(self, value: i128, divisor: i128) -> i128 {
+        let quotient = value / divisor;
+        let remainder = (value % divisor).abs();
+        if remainder == 0 {
+            return quotient;
+        }
+
+        let away_from_zero = quotient + value.signum();
+        match self {
+            RoundingMode::Down => quotient,
+            RoundingMode::Up => away_from_zero,
+            RoundingMode::HalfUp if remainder * 2 >= divisor => away_from_zero,
+            RoundingMode::HalfEven
+                if remainder * 2 > divisor || (remainder * 2 == divisor && quotient % 2 != 0) =>
+            {
+                away_from_zero
+            }
+            RoundingMode::HalfUp | RoundingMode::HalfEven => quotient,
+        }
+    }
+}
+
+// The result of a conversion, kept with the transaction so it can be explained later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conversion {
+    pub from: Currency,
+    pub to: Currency,
+    // In micro units (see RATE_SCALE)
+    pub rate: i64,
+    pub rounding: RoundingMode,
+    pub converted: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FxError {
+    Io(String),
+    Syntax { line: usize, message: String },
+    // A rate of 0 or less (1 EUR can't be worth -0.5 USD)
+    InvalidRate { line: usize, rate: String },
+    MissingRate { from: Currency, to: Currency },
+    Overflow,
+}
+
+impl fmt::Display for FxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FxError::Io(message) => write!(f, "can't read rates file: {}", message),
+            FxError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            FxError::InvalidRate { line, rate } => {
+                write!(f, "line {}: rate {} must be greater than 0", line, rate)
+            }
+            FxError::MissingRate { from, to } => write!(f, "no rate from {} to {}", from, to),
+            FxError::Overflow => write!(f, "converted amount doesn't fit in a balance"),
+        }
+    }
+}
+
+impl Error for FxError {}
+
+#[derive(Debug, Clone)]
+pub struct FxRates {
+    rates: HashMap<(Currency, Currency), i64>,
+    rounding: RoundingMode,
+}
+
+impl FxRates {
+    pub fn new() -> Self {
+        FxRates {
+            rates: HashMap::new(),
+            rounding: RoundingMode::HalfEven,
+        }
+    }
+
+    pub fn rounding(&self) -> RoundingMode {
+        self.rounding
+    }
+
+    // 'rate' is in micro units: one 'from' is worth rate / RATE_SCALE 'to'
+    pub fn set(&mut self, from: Currency, to: Currency, rate: i64) {
+        self.rates.insert((from, to), rate);
+    }
+
+    // A rate of the same currency is always 1.
+    // When only the opposite rate is known (USD -> EUR), we use its inverse.
+    pub fn rate(&self, from: Currency, to: Currency) -> Result<i64, FxError> {
+        if from == to {
+            return Ok(RATE_SCALE);
+        }
+
+        if let Some(rate) = self.rates.get(&(from, to)) {
+            return Ok(*rate);
+        }
+
+        match self.rates.get(&(to, from)) {
+            Some(inverse) if *inverse > 0 => {
+                Ok(RoundingMode::HalfEven.divide(RATE_SCALE * RATE_SCALE, *inverse))
+            }
+            _ => Err(FxError::MissingRate { from, to }),
+        }
+    }
+
+    pub fn convert(
+        &self,
+        amount: i32,
+        from: Currency,
+        to: Currency,
+        rounding: RoundingMode,
+    ) -> Result<Conversion, FxError> {
+        let rate = self.rate(from, to)?;
+        let converted = rounding.divide(amount as i64 * rate, RATE_SCALE);
+
+        Ok(Conversion {
+            from,
+            to,
+            rate,
+            rounding,
+            converted: i32::try_from(converted).map_err(|_| FxError::Overflow)?,
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, FxError> {
+        let text = fs::read_to_string(path).map_err(|error| FxError::Io(error.to_string()))?;
+        FxRates::parse(&text)
+    }
+
+    // One rate per line: "EUR USD 1.085" (1 EUR = 1.085 USD), '#' starts a comment.
+    // An optional "ROUNDING half-up" line picks the rounding mode (half-even by default).
+    pub fn parse(text: &str) -> Result<Self, FxError> {
+        let mut rates = FxRates::new();
+
+        for (index, raw_line) in text.lines().enumerate() {
+            let syntax = |message: String| FxError::Syntax {
+                line: index + 1,
+                message,
+            };
+
+            let line = raw_line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if let ["ROUNDING", mode] = parts[..] {
+                rates.rounding = RoundingMode::parse(mode)
+                    .ok_or_else(|| syntax(format!("unknown rounding mode '{}'", mode)))?;
+                continue;
+            }
+
+            let [from, to, rate] = parts[..] else {
+                return Err(syntax(String::from("expected 'FROM TO RATE'")));
+            };
+
+            let from = Currency::parse(from)
+                .ok_or_else(|| syntax(format!("unknown currency '{}'", from)))?;
+            let to =
+                Currency::parse(to).ok_or_else(|| syntax(format!("unknown currency '{}'", to)))?;
+            let value =
+                parse_rate(rate).ok_or_else(|| syntax(format!("invalid rate '{}'", rate)))?;
+            if value <= 0 {
+                return Err(FxError::InvalidRate {
+                    line: index + 1,
+                    rate: rate.to_string(),
+                });
+            }
+
+            rates.set(from, to, value);
+        }
+
+        Ok(rates)
+    }
+}
+
+// "1.085" -> 1_085_000, "-0.5" -> -500_000 (at most 6 decimals, no floats involved).
+// The sign is kept, so that the caller can tell a negative rate from a malformed one.
+fn parse_rate(text: &str) -> Option<i64> {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, text),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || fraction.len() > 6 || !all_digits(fraction) {
+        return None;
+    }
+
+    let whole: i64 = whole.parse().ok()?;
+    let fraction: i64 = format!("{:0<6}", fraction).parse().ok()?;
+    let rate = whole.checked_mul(RATE_SCALE)?.checked_add(fraction)?;
+
+    Some(sign * rate)
+}
+
+// 1_085_000 -> "1.085000"
+pub fn format_rate(rate: i64) -> String {
+    format!("{}.{:06}", rate / RATE_SCALE, rate % RATE_SCALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounding_modes() {
+        let cases = [
+            (RoundingMode::HalfUp, [3, 4, -3]),
+            (RoundingMode::HalfEven, [2, 4, -2]),
+            (RoundingMode::Down, [2, 3, -2]),
+            (RoundingMode::Up, [3, 4, -3]),
+        ];
+        for (mode, expected) in cases {
+            // 2.5, 3.5, -2.5
+            let results = [
+                mode.divide(25, 10),
+                mode.divide(35, 10),
+                mode.divide(-25, 10),
+            ];
+            assert_eq!(results, expected, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn missing_rates_use_the_inverse() {
+        let rates = FxRates::parse("EUR USD 1.25").unwrap();
+        assert_eq!(rates.rate(Currency::Usd, Currency::Eur), Ok(800_000));
+        assert_eq!(rates.rate(Currency::Eur, Currency::Eur), Ok(RATE_SCALE));
+
+        let conversion = rates
+            .convert(10, Currency::Usd, Currency::Eur, RoundingMode::HalfEven)
+            .unwrap();
+        assert_eq!(conversion.converted, 8);
+        assert_eq!(
+            FxRates::new().rate(Currency::Eur, Currency::Usd),
+            Err(FxError::MissingRate {
+                from: Currency::Eur,
+                to: Currency::Usd
+            })
+        );
+    }
+
+    #[test]
+    fn conversions_that_dont_fit_are_refused() {
+        let rates = FxRates::parse("EUR USD 2").unwrap();
+        assert_eq!(
+            rates.convert(i32::MAX, Currency::Eur, Currency::Usd, RoundingMode::Down),
+            Err(FxError::Overflow)
+        );
+    }
+
+    #[test]
+    fn rates_file() {
+        let rates = FxRates::parse("# rates\nROUNDING half-up\nEUR USD 1.085 # today\n").unwrap();
+        assert_eq!(rates.rounding(), RoundingMode::HalfUp);
+        assert_eq!(rates.rate(Currency::Eur, Currency::Usd), Ok(1_085_000));
+
+        let errors = [
+            (
+                "USD EUR -0.5",
+                FxError::InvalidRate {
+                    line: 1,
+                    rate: String::from("-0.5"),
+                },
+            ),
+            (
+                "\nEUR USD 0",
+                FxError::InvalidRate {
+                    line: 2,
+                    rate: String::from("0"),
+                },
+            ),
+            (
+                "EUR USD 1.0000001",
+                FxError::Syntax {
+                    line: 1,
+                    message: String::from("invalid rate '1.0000001'"),
+                },
+            ),
+            (
+                "EUR GBP 1",
+                FxError::Syntax {
+                    line: 1,
+                    message: String::from("unknown currency 'GBP'"),
+                },
+            ),
+        ];
+        for (text, error) in errors {
+            assert_eq!(FxRates::parse(text).map(|_| ()), Err(error), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use crate::account::{Account, AccountState};
+use crate::clock::Date;
+use crate::currency::{Conversion, Currency};
+use crate::loans::{CreditLine, Loan};
+use crate::standing_orders::{Outcome, Schedule, StandingOrder};
+use crate::transaction::{LedgerEntry, Transaction};
+
+// Take a snapshot of the bank every N events, unless told otherwise
+pub const DEFAULT_SNAPSHOT_EVERY: usize = 100;
+
+// Something that happened to the bank. The state of the bank (accounts, balances, ledger)
+// is nothing more than the result of applying these events one after the other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    AccountOpened {
+        account: u32,
+        holder: String,
+        currency: Currency,
+    },
+    Deposited {
+        transaction: u64,
+        account: u32,
+        amount: i32,
+    },
+    Withdrawn {
+        transaction: u64,
+        account: u32,
+        amount: i32,
+    },
+    Transferred {
+        transaction: u64,
+        from: u32,
+        to: u32,
+        amount: i32,
+        conversion: Option<Conversion>,
+    },
+    // Freezing, reopening, becoming dormant
+    StateChanged {
+        account: u32,
+        to: AccountState,
+        reason: String,
+    },
+    Closed {
+        account: u32,
+        reason: String,
+    },
+    // Compensating event: undoes 'original' by moving the money back,
+    // the original transaction stays in the history.
+    Reversed {
+        transaction: u64,
+        original: u64,
+        reason: String,
+    },
+    // The principal is paid into the linked account
+    LoanOpened {
+        loan: u32,
+        account: u32,
+        principal: i32,
+        annual_rate_bp: u32,
+        term_months: u32,
+        transaction: u64,
+    },
+    // 'installment' is None for an early payoff (every remaining installment at once)
+    LoanRepaid {
+        loan: u32,
+        installment: Option<u32>,
+        transaction: u64,
+        amount: i32,
+    },
+    LatePenaltyCharged {
+        loan: u32,
+        installment: u32,
+        amount: i32,
+    },
+    CreditLineOpened {
+        line: u32,
+        account: u32,
+        limit: i32,
+    },
+    CreditDrawn {
+        line: u32,
+        transaction: u64,
+        amount: i32,
+    },
+    CreditRepaid {
+        line: u32,
+        transaction: u64,
+        amount: i32,
+    },
+    StandingOrderCreated {
+        order: u32,
+        from: u32,
+        to: u32,
+        amount: i32,
+        schedule: Schedule,
+        max_retries: u32,
+    },
+    StandingOrderCancelled {
+        order: u32,
+    },
+    // One attempt at running the order (an executed one follows its 'Transferred' event)
+    StandingOrderRun {
+        order: u32,
+        outcome: Outcome,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordedEvent {
+    // Position in the stream, starting at 1
+    pub sequence: u64,
+    pub at: Date,
+    pub event: Event,
+}
+
+// A copy of the whole state right after event number 'sequence',
+// so that replaying doesn't have to start from the very first event.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub sequence: u64,
+    pub at: Date,
+    pub accounts: Vec<Account>,
+    pub transactions: Vec<Transaction>,
+    pub entries: Vec<LedgerEntry>,
+    pub next_transaction_id: u64,
+    pub loans: Vec<Loan>,
+    pub credit_lines: Vec<CreditLine>,
+    pub standing_orders: Vec<StandingOrder>,
+}
+
+#[derive(Debug)]
+pub struct EventStore {
+    events: Vec<RecordedEvent>,
+    snapshots: Vec<Snapshot>,
+    snapshot_every: usize,
+}
+
+impl EventStore {
+    pub fn new(snapshot_every: usize) -> Self {
+        EventStore {
+            events: vec![],
+            snapshots: vec![],
+            // 0 would mean "a snapshot after every 0 events"...
+            snapshot_every: snapshot_every.max(1),
+        }
+    }
+
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    pub fn next_sequence(&self) -> u64 {
+        self.events.len() as u64 + 1
+    }
+
+    // Returns true when it's time to take a snapshot
+    pub fn append(&mut self, event: RecordedEvent) -> bool {
+        self.events.push(event);
+        self.events.len().is_multiple_of(self.snapshot_every)
+    }
+
+    pub fn add_snapshot(&mut self, snapshot: Snapshot) {
+        self.snapshots.push(snapshot);
+    }
+
+    // The most recent snapshot that only contains events that happened on or before 'date'.
+    // Events are appended in chronological order, and a snapshot is dated with its last
+    // event, so every event inside such a snapshot is on or before 'date' too.
+    pub fn latest_snapshot_until(&self, date: Date) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.at <= date)
+    }
+}
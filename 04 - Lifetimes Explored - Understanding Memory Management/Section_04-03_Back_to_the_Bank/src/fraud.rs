@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+use crate::transaction::{Transaction, TransactionKind};
+
+// What a rule thinks about a transaction before it is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Flag(String),
+    Block(String),
+}
+
+// What a rule does when it finds something suspicious (configurable per rule).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    Flag,
+    Block,
+}
+
+impl RuleAction {
+    fn verdict(self, reason: String) -> Verdict {
+        match self {
+            RuleAction::Flag => Verdict::Flag(reason),
+            RuleAction::Block => Verdict::Block(reason),
+        }
+    }
+}
+
+// Anything implementing this trait can be plugged into the RuleEngine.
+// 'history' contains every transaction already applied by the bank, oldest first.
+pub trait Rule: fmt::Debug {
+    fn name(&self) -> &str;
+    fn check(&self, transaction: &Transaction, history: &[Transaction]) -> Verdict;
+}
+
+// Every rule counts its window the same way, the day of the transaction included:
+// with 'window_days = 1', a transaction looks back at the same day and the day before.
+fn within_window(past: &Transaction, transaction: &Transaction, window_days: u32) -> bool {
+    past.at.days_until(transaction.at) <= window_days as i64
+}
+
+// What a transaction moves on one account, in that account's currency
+// (the credit side of a converted transfer gets the converted amount).
+fn amount_on(transaction: &Transaction, account: u32) -> Option<i64> {
+    transaction
+        .legs()
+        .iter()
+        .find(|leg| leg.account == account)
+        .map(|leg| (leg.amount as i64).abs())
+}
+
+// Too many transactions on the same account within a few days.
+#[derive(Debug)]
+pub struct VelocityLimit {
+    pub max_transactions: usize,
+    pub window_days: u32,
+    pub action: RuleAction,
+}
+
+impl Rule for VelocityLimit {
+    fn name(&self) -> &str {
+        "velocity"
+    }
+
+    fn check(&self, transaction: &Transaction, history: &[Transaction]) -> Verdict {
+        for account in transaction.accounts() {
+            // '+ 1' because the transaction we're looking at counts too
+            let count = history
+                .iter()
+                .filter(|past| past.involves(account))
+                .filter(|past| within_window(past, transaction, self.window_days))
+                .count()
+                + 1;
+
+            if count > self.max_transactions {
+                return self.action.verdict(format!(
+                    "{} transactions on account #{} within {} day(s) (max {})",
+                    count, account, self.window_days, self.max_transactions
+                ));
+            }
+        }
+
+        Verdict::Allow
+    }
+}
+
+// An amount way above what the account usually moves.
+#[derive(Debug)]
+pub struct LargeAmount {
+    // The transaction is suspicious when amount > multiplier * average amount
+    pub multiplier: u32,
+    // Below this number of past transactions, we don't know the account well enough
+    pub min_history: usize,
+    pub action: RuleAction,
+}
+
+impl Rule for LargeAmount {
+    fn name(&self) -> &str {
+        "large_amount"
+    }
+
+    // Amounts are compared in the currency of the account, never across currencies
+    fn check(&self, transaction: &Transaction, history: &[Transaction]) -> Verdict {
+        for account in transaction.accounts() {
+            let amounts: Vec<i64> = history
+                .iter()
+                .filter_map(|past| amount_on(past, account))
+                .collect();
+
+            if amounts.len() < self.min_history {
+                continue;
+            }
+
+            let average = amounts.iter().sum::<i64>() / amounts.len() as i64;
+            let amount = amount_on(transaction, account).unwrap_or(0);
+            if amount > average * self.multiplier as i64 {
+                return self.action.verdict(format!(
+                    "amount {} is more than {}x the average ({}) of account #{}",
+                    amount, self.multiplier, average, account
+                ));
+            }
+        }
+
+        Verdict::Allow
+    }
+}
+
+// Money going A -> B shortly after it went B -> A.
+#[derive(Debug)]
+pub struct RoundTrip {
+    pub window_days: u32,
+    pub action: RuleAction,
+}
+
+impl Rule for RoundTrip {
+    fn name(&self) -> &str {
+        "round_trip"
+    }
+
+    fn check(&self, transaction: &Transaction, history: &[Transaction]) -> Verdict {
+        let TransactionKind::Transfer { from, to } = transaction.kind else {
+            return Verdict::Allow;
+        };
+
+        let previous = history.iter().rev().find(|past| {
+            past.kind == TransactionKind::Transfer { from: to, to: from }
+                && within_window(past, transaction, self.window_days)
+        });
+
+        match previous {
+            Some(past) => self.action.verdict(format!(
+                "transfer #{} -> #{} reverses transaction {} from {}",
+                from, to, past.id, past.at
+            )),
+            None => Verdict::Allow,
+        }
+    }
+}
+
+// Outcome of running every rule on a transaction.
+#[derive(Debug, Default)]
+pub struct Evaluation {
+    // (rule name, reason)
+    pub flags: Vec<(String, String)>,
+    pub block: Option<(String, String)>,
+}
+
+// A transaction a rule complained about, kept by the bank so it can be reviewed later.
+#[derive(Debug, Clone)]
+pub struct Flag {
+    pub transaction: Transaction,
+    pub rule: String,
+    pub reason: String,
+    // true when the transaction was rejected, false when it went through anyway
+    pub blocked: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct RuleEngine {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl RuleEngine {
+    pub fn new() -> Self {
+        RuleEngine { rules: vec![] }
+    }
+
+    pub fn add_rule(&mut self, rule: Box<dyn Rule>) {
+        self.rules.push(rule);
+    }
+
+    // Every rule runs (so all flags get collected), the first 'Block' wins.
+    pub fn evaluate(&self, transaction: &Transaction, history: &[Transaction]) -> Evaluation {
+        let mut evaluation = Evaluation::default();
+
+        for rule in &self.rules {
+            match rule.check(transaction, history) {
+                Verdict::Allow => {}
+                Verdict::Flag(reason) => {
+                    evaluation.flags.push((rule.name().to_string(), reason));
+                }
+                Verdict::Block(reason) => {
+                    if evaluation.block.is_none() {
+                        evaluation.block = Some((rule.name().to_string(), reason));
+                    }
+                }
+            }
+        }
+
+        evaluation
+    }
+
+    pub fn from_toml_file(path: &str) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|error| ConfigError::Io(error.to_string()))?;
+        RuleEngine::from_toml(&text)
+    }
+
+    // Expected layout (every section is optional, a missing section means "rule disabled"):
+    //
+    //   [velocity]
+    //   max_transactions = 5
+    //   window_days = 1
+    //   action = "block"
+    //
+    //   [large_amount]
+    //   multiplier = 10
+    //   min_history = 3
+    //   action = "flag"
+    //
+    //   [round_trip]
+    //   window_days = 2
+    //   action = "flag"
+    //
+    // Each section also accepts 'enabled = false'.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let sections = parse_toml(text)?;
+        let mut engine = RuleEngine::new();
+
+        for section in &sections {
+            if !section.boolean("enabled", true)? {
+                continue;
+            }
+
+            let action = section.action()?;
+            let rule: Box<dyn Rule> = match section.name.as_str() {
+                "velocity" => Box::new(VelocityLimit {
+                    max_transactions: section.integer("max_transactions")? as usize,
+                    window_days: section.integer("window_days")? as u32,
+                    action,
+                }),
+                "large_amount" => Box::new(LargeAmount {
+                    multiplier: section.integer("multiplier")? as u32,
+                    min_history: section.integer("min_history")? as usize,
+                    action,
+                }),
+                "round_trip" => Box::new(RoundTrip {
+                    window_days: section.integer("window_days")? as u32,
+                    action,
+                }),
+                other => return Err(ConfigError::UnknownRule(other.to_string())),
+            };
+            engine.add_rule(rule);
+        }
+
+        Ok(engine)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Io(String),
+    Syntax { line: usize, message: String },
+    UnknownRule(String),
+    MissingKey { section: String, key: String },
+    InvalidValue { section: String, key: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(message) => write!(f, "can't read rules file: {}", message),
+            ConfigError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            ConfigError::UnknownRule(name) => write!(f, "unknown rule [{}]", name),
+            ConfigError::MissingKey { section, key } => {
+                write!(f, "[{}] is missing '{}'", section, key)
+            }
+            ConfigError::InvalidValue { section, key } => {
+                write!(f, "[{}] has an invalid value for '{}'", section, key)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+// A (very) small subset of TOML: '[section]' headers, 'key = value' lines,
+// with integers, booleans and "strings" as values, and '#' comments.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Integer(i64),
+    Boolean(bool),
+    Text(String),
+}
+
+#[derive(Debug, Default)]
+struct Section {
+    name: String,
+    values: HashMap<String, Value>,
+}
+
+impl Section {
+    fn get(&self, key: &str) -> Result<&Value, ConfigError> {
+        self.values.get(key).ok_or(ConfigError::MissingKey {
+            section: self.name.clone(),
+            key: key.to_string(),
+        })
+    }
+
+    fn invalid(&self, key: &str) -> ConfigError {
+        ConfigError::InvalidValue {
+            section: self.name.clone(),
+            key: key.to_string(),
+        }
+    }
+
+    fn integer(&self, key: &str) -> Result<i64, ConfigError> {
+        match self.get(key)? {
+            Value::Integer(value) if *value >= 0 => Ok(*value),
+            _ => Err(self.invalid(key)),
+        }
+    }
+
+    fn boolean(&self, key: &str, default: bool) -> Result<bool, ConfigError> {
+        match self.values.get(key) {
+            None => Ok(default),
+            Some(Value::Boolean(value)) => Ok(*value),
+            Some(_) => Err(self.invalid(key)),
+        }
+    }
+
+    // 'action' is optional and defaults to "flag"
+    fn action(&self) -> Result<RuleAction, ConfigError> {
+        match self.values.get("action") {
+            None => Ok(RuleAction::Flag),
+            Some(Value::Text(text)) if text == "flag" => Ok(RuleAction::Flag),
+            Some(Value::Text(text)) if text == "block" => Ok(RuleAction::Block),
+            Some(_) => Err(self.invalid("action")),
+        }
+    }
+}
+
+// Sections are returned in the order they appear in the file.
+fn parse_toml(text: &str) -> Result<Vec<Section>, ConfigError> {
+    let mut sections: Vec<Section> = vec![];
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let syntax = |message: &str| ConfigError::Syntax {
+            line: line_number,
+            message: message.to_string(),
+        };
+
+        // Strip comments (we don't support '#' inside strings)
+        let line = raw_line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            sections.push(Section {
+                name: name.trim().to_string(),
+                values: HashMap::new(),
+            });
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| syntax("expected 'key = value'"))?;
+        let value = value.trim();
+        let value = if let Some(text) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Value::Text(text.to_string())
+        } else if value == "true" || value == "false" {
+            Value::Boolean(value == "true")
+        } else {
+            Value::Integer(
+                value
+                    .replace('_', "")
+                    .parse()
+                    .map_err(|_| syntax("expected an integer, a boolean or a \"string\""))?,
+            )
+        };
+
+        let section = sections
+            .last_mut()
+            .ok_or_else(|| syntax("key outside of a [section]"))?;
+        section.values.insert(key.trim().to_string(), value);
+    }
+
+    Ok(sections)
+}
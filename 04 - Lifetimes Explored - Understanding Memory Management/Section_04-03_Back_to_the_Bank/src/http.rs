@@ -0,0 +1,786 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::account::AccountError;
+use crate::bank::{Bank, BankError};
+use crate::currency::{Currency, FxError};
+use crate::json::Json;
+use crate::loans::LoanError;
+use crate::standing_orders::StandingOrderError;
+use crate::transaction::TransactionKind;
+
+// Requests bigger than this are refused (nobody needs 1 MB to make a deposit)
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+// How many Idempotency-Keys are remembered: past that, the oldest one is forgotten
+// (a retry coming that late is handled as a new request)
+const MAX_IDEMPOTENCY_KEYS: usize = 10_000;
+
+// How long a client has to send its request (and to read the response): connections are
+// handled one at a time, so an idle client would otherwise block the server forever
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn new(method: &str, path: &str, body: &str) -> Self {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: vec![],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // Header names are case-insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Reads "METHOD /path HTTP/1.1", the headers, then 'Content-Length' bytes of body
+    pub fn read_from(reader: &mut impl BufRead) -> Result<Self, String> {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|error| error.to_string())?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+            return Err(String::from("invalid request line"));
+        };
+        let mut request = Request::new(method, path, "");
+
+        loop {
+            let mut line = String::new();
+            reader
+                .read_line(&mut line)
+                .map_err(|error| error.to_string())?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("invalid header '{}'", line))?;
+            request = request.with_header(name.trim(), value.trim());
+        }
+
+        let length: usize = match request.header("Content-Length") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| String::from("invalid Content-Length"))?,
+            None => 0,
+        };
+        if length > MAX_BODY_SIZE {
+            return Err(String::from("request body too large"));
+        }
+
+        let mut body = vec![0; length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|error| error.to_string())?;
+        request.body = String::from_utf8(body).map_err(|_| String::from("body isn't UTF-8"))?;
+
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Json,
+}
+
+impl Response {
+    pub fn new(status: u16, body: Json) -> Self {
+        Response { status, body }
+    }
+
+    pub fn error(status: u16, code: &str, message: &str) -> Self {
+        Response::new(
+            status,
+            Json::object(vec![
+                ("error", Json::text(code)),
+                ("message", Json::text(message)),
+            ]),
+        )
+    }
+
+    pub fn to_http(&self) -> String {
+        let body = self.body.to_string();
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason_phrase(self.status),
+            body.len(),
+            body
+        )
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    }
+}
+
+// Domain errors -> HTTP status codes
+fn bank_error(error: &BankError) -> Response {
+    let (status, code) = match error {
+        BankError::AccountNotFound(..) => (404, "account_not_found"),
+        BankError::TransactionNotFound(..) => (404, "transaction_not_found"),
+        BankError::Loan(LoanError::LoanNotFound(..) | LoanError::CreditLineNotFound(..)) => {
+            (404, "not_found")
+        }
+        BankError::DuplicateAccount(..) => (409, "duplicate_account"),
+        BankError::AlreadyReversed(..) => (409, "already_reversed"),
+        BankError::NotReversible { .. } => (409, "not_reversible"),
+        BankError::Account(AccountError::Overflow { .. }) => (422, "overflow"),
+        BankError::Account(..) => (409, "account_state"),
+        BankError::Loan(LoanError::AlreadyPaidOff(..)) => (409, "already_paid_off"),
+        BankError::Blocked { .. } => (403, "blocked"),
+        BankError::InvalidAmount(..) => (422, "invalid_amount"),
+        BankError::InsufficientFunds { .. } => (422, "insufficient_funds"),
+        BankError::Fx(FxError::MissingRate { .. } | FxError::Overflow) => (422, "conversion"),
+        BankError::Fx(..) => (500, "fx_rates"),
+        BankError::Loan(..) => (422, "loan"),
+        BankError::StandingOrder(StandingOrderError::NotFound(..)) => (404, "not_found"),
+        BankError::StandingOrder(StandingOrderError::AlreadyCancelled(..)) => {
+            (409, "already_cancelled")
+        }
+        BankError::StandingOrder(..) => (422, "standing_order"),
+    };
+
+    Response::error(status, code, &error.to_string())
+}
+
+// The Bank, behind HTTP-like requests and JSON responses:
+//
+//   GET  /accounts                        list every account
+//   POST /accounts                        {"id": 4, "holder": "me", "currency": "EUR"}
+//   GET  /accounts/{id}                   one account
+//   POST /accounts/{id}/deposits          {"amount": 100}
+//   POST /accounts/{id}/withdrawals       {"amount": 100}
+//   GET  /accounts/{id}/statement         every ledger line of the account
+//   POST /transfers                       {"from": 1, "to": 2, "amount": 100}
+//
+// A POST with an 'Idempotency-Key' header is only applied once: retrying it with the same
+// key gets the first response back, instead of moving the money twice
+// (reusing the key for a different request is a conflict).
+#[derive(Debug)]
+pub struct Api {
+    bank: Bank,
+    // Idempotency-Key -> (the request it was used for, the response it got)
+    idempotency: HashMap<String, (String, Response)>,
+    // The same keys, oldest first
+    idempotency_keys: VecDeque<String>,
+}
+
+impl Api {
+    pub fn new(bank: Bank) -> Self {
+        Api {
+            bank,
+            idempotency: HashMap::new(),
+            idempotency_keys: VecDeque::new(),
+        }
+    }
+
+    pub fn bank(&self) -> &Bank {
+        &self.bank
+    }
+
+    pub fn handle(&mut self, request: &Request) -> Response {
+        let key = match request.header("Idempotency-Key") {
+            Some(key) if request.method == "POST" => key.to_string(),
+            _ => return self.route(request),
+        };
+
+        let fingerprint = format!("{} {} {}", request.method, request.path, request.body);
+        if let Some((previous, response)) = self.idempotency.get(&key) {
+            if *previous != fingerprint {
+                return Response::error(
+                    409,
+                    "idempotency_key_reused",
+                    "this Idempotency-Key was already used for a different request",
+                );
+            }
+            return response.clone();
+        }
+
+        let response = self.route(request);
+        if self.idempotency_keys.len() == MAX_IDEMPOTENCY_KEYS
+            && let Some(oldest) = self.idempotency_keys.pop_front()
+        {
+            self.idempotency.remove(&oldest);
+        }
+        self.idempotency_keys.push_back(key.clone());
+        self.idempotency
+            .insert(key, (fingerprint, response.clone()));
+        response
+    }
+
+    fn route(&mut self, request: &Request) -> Response {
+        let path = request.path.split('?').next().unwrap_or("");
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        let result = match (request.method.as_str(), &segments[..]) {
+            ("GET", ["accounts"]) => Ok(self.list_accounts()),
+            ("POST", ["accounts"]) => self.open_account(request),
+            ("GET", ["accounts", id]) => account_id(id).and_then(|id| self.show_account(id)),
+            ("POST", ["accounts", id, "deposits"]) => {
+                account_id(id).and_then(|id| self.deposit(id, request))
+            }
+            ("POST", ["accounts", id, "withdrawals"]) => {
+                account_id(id).and_then(|id| self.withdraw(id, request))
+            }
+            ("GET", ["accounts", id, "statement"]) => {
+                account_id(id).and_then(|id| self.statement(id))
+            }
+            ("POST", ["transfers"]) => self.transfer(request),
+            (
+                _,
+                ["accounts"]
+                | ["accounts", _]
+                | ["accounts", _, "deposits" | "withdrawals" | "statement"]
+                | ["transfers"],
+            ) => Err(Response::error(
+                405,
+                "method_not_allowed",
+                "method not allowed",
+            )),
+            _ => Err(Response::error(404, "not_found", "no such endpoint")),
+        };
+
+        result.unwrap_or_else(|response| response)
+    }
+
+    fn account_json(&self, id: u32) -> Result<Json, Response> {
+        let account = self.bank.account(id).map_err(|e| bank_error(&e))?;
+        Ok(Json::object(vec![
+            ("id", Json::Number(account.id() as i64)),
+            ("holder", Json::text(account.holder())),
+            ("currency", Json::text(&account.currency().to_string())),
+            ("balance", Json::Number(account.balance() as i64)),
+            ("state", Json::text(&account.state().to_string())),
+        ]))
+    }
+
+    fn list_accounts(&self) -> Response {
+        let accounts = self
+            .bank
+            .accounts()
+            .iter()
+            .filter_map(|account| self.account_json(account.id()).ok())
+            .collect();
+
+        Response::new(200, Json::Array(accounts))
+    }
+
+    fn show_account(&self, id: u32) -> Result<Response, Response> {
+        Ok(Response::new(200, self.account_json(id)?))
+    }
+
+    fn open_account(&mut self, request: &Request) -> Result<Response, Response> {
+        let body = parse_body(request)?;
+        let id = number(&body, "id")?;
+        let holder = text(&body, "holder")?;
+        let currency = match body.get("currency") {
+            None => Currency::Eur,
+            Some(value) => value
+                .as_str()
+                .and_then(Currency::parse)
+                .ok_or_else(|| bad_request("'currency' must be \"EUR\" or \"USD\""))?,
+        };
+
+        self.bank
+            .open_account(id, &holder, currency)
+            .map_err(|e| bank_error(&e))?;
+        Ok(Response::new(201, self.account_json(id)?))
+    }
+
+    fn deposit(&mut self, id: u32, request: &Request) -> Result<Response, Response> {
+        let amount = amount(&parse_body(request)?)?;
+        self.bank.deposit(id, amount).map_err(|e| bank_error(&e))?;
+        Ok(Response::new(201, self.account_json(id)?))
+    }
+
+    fn withdraw(&mut self, id: u32, request: &Request) -> Result<Response, Response> {
+        let amount = amount(&parse_body(request)?)?;
+        self.bank.withdraw(id, amount).map_err(|e| bank_error(&e))?;
+        Ok(Response::new(201, self.account_json(id)?))
+    }
+
+    fn transfer(&mut self, request: &Request) -> Result<Response, Response> {
+        let body = parse_body(request)?;
+        let from = number(&body, "from")?;
+        let to = number(&body, "to")?;
+        let amount = amount(&body)?;
+
+        let transaction = self
+            .bank
+            .transfer(from, to, amount)
+            .map_err(|e| bank_error(&e))?;
+        let credited = self
+            .bank
+            .transaction(transaction)
+            .map_err(|e| bank_error(&e))?
+            .credited();
+
+        Ok(Response::new(
+            201,
+            Json::object(vec![
+                ("transaction", Json::Number(transaction as i64)),
+                ("from", self.account_json(from)?),
+                ("to", self.account_json(to)?),
+                ("debited", Json::Number(amount as i64)),
+                ("credited", Json::Number(credited as i64)),
+            ]),
+        ))
+    }
+
+    fn statement(&self, id: u32) -> Result<Response, Response> {
+        let account = self.account_json(id)?;
+
+        let mut lines = vec![];
+        for entry in self.bank.entries().iter().filter(|e| e.account == id) {
+            let transaction = self
+                .bank
+                .transaction(entry.transaction_id)
+                .map_err(|e| bank_error(&e))?;
+            let kind = match transaction.kind {
+                TransactionKind::Deposit { .. } => "deposit",
+                TransactionKind::Withdrawal { .. } => "withdrawal",
+                TransactionKind::Transfer { from, .. } if from == id => "transfer_out",
+                TransactionKind::Transfer { .. } => "transfer_in",
+            };
+
+            lines.push(Json::object(vec![
+                ("transaction", Json::Number(transaction.id as i64)),
+                ("date", Json::text(&transaction.at.to_string())),
+                ("kind", Json::text(kind)),
+                ("amount", Json::Number(entry.amount as i64)),
+            ]));
+        }
+
+        Ok(Response::new(
+            200,
+            Json::object(vec![("account", account), ("lines", Json::Array(lines))]),
+        ))
+    }
+}
+
+fn bad_request(message: &str) -> Response {
+    Response::error(400, "bad_request", message)
+}
+
+fn account_id(segment: &str) -> Result<u32, Response> {
+    segment
+        .parse()
+        .map_err(|_| Response::error(404, "account_not_found", "invalid account id"))
+}
+
+fn parse_body(request: &Request) -> Result<Json, Response> {
+    Json::parse(&request.body).map_err(|error| bad_request(&format!("invalid JSON: {}", error)))
+}
+
+fn number<T: TryFrom<i64>>(body: &Json, key: &str) -> Result<T, Response> {
+    body.get(key)
+        .and_then(Json::as_i64)
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| bad_request(&format!("missing or invalid '{}'", key)))
+}
+
+fn text(body: &Json, key: &str) -> Result<String, Response> {
+    body.get(key)
+        .and_then(Json::as_str)
+        .map(|value| value.to_string())
+        .ok_or_else(|| bad_request(&format!("missing or invalid '{}'", key)))
+}
+
+fn amount(body: &Json) -> Result<i32, Response> {
+    let amount: i32 = number(body, "amount")?;
+    if amount <= 0 {
+        return Err(Response::error(
+            422,
+            "invalid_amount",
+            "'amount' must be greater than 0",
+        ));
+    }
+    Ok(amount)
+}
+
+// Talks to the Api directly, without going through a socket
+// (handy to drive the API from code, exactly like a real HTTP client would).
+pub struct Client<'a> {
+    api: &'a mut Api,
+}
+
+impl<'a> Client<'a> {
+    pub fn new(api: &'a mut Api) -> Self {
+        Client { api }
+    }
+
+    pub fn get(&mut self, path: &str) -> Response {
+        self.api.handle(&Request::new("GET", path, ""))
+    }
+
+    pub fn post(&mut self, path: &str, body: &str, idempotency_key: Option<&str>) -> Response {
+        let mut request = Request::new("POST", path, body);
+        if let Some(key) = idempotency_key {
+            request = request.with_header("Idempotency-Key", key);
+        }
+        self.api.handle(&request)
+    }
+}
+
+// Only ever listens on localhost. Connections are handled one after the other,
+// so the bank never sees two requests at the same time.
+pub fn serve(api: &mut Api, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Listening on http://{}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        // A failed accept only concerns that one connection: the server keeps going
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                println!("Accept error: {}", error);
+                continue;
+            }
+        };
+        if let Err(error) = handle_connection(api, stream) {
+            println!("Connection error: {}", error);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection(api: &mut Api, mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match Request::read_from(&mut reader) {
+        Ok(request) => api.handle(&request),
+        Err(message) => bad_request(&message),
+    };
+
+    stream.write_all(response.to_http().as_bytes())?;
+    stream.flush()
+}
+
+// Every test drives the API through 'Client', the same way the demo (or any other code) does
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::AccountState;
+    use crate::clock::{Clock, Date};
+    use crate::fraud::{RuleAction, RuleEngine, VelocityLimit};
+
+    // #1 has 100 EUR, #2 is frozen, #3 is held in USD (and there are no exchange rates)
+    fn bank() -> Bank {
+        let mut bank = Bank::new(Clock::new(Date::new(2024, 1, 1).unwrap()));
+        bank.open_account(1, "me", Currency::Eur).unwrap();
+        bank.open_account(2, "you", Currency::Eur).unwrap();
+        bank.open_account(3, "them", Currency::Usd).unwrap();
+        bank.deposit(1, 100).unwrap();
+        bank.freeze(2, "test").unwrap();
+        bank
+    }
+
+    fn error_code(response: &Response) -> &str {
+        response
+            .body
+            .get("error")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+    }
+
+    fn balance(api: &Api, id: u32) -> i32 {
+        api.bank().account(id).unwrap().balance()
+    }
+
+    #[test]
+    fn bank_errors_through_the_client() {
+        let mut api = Api::new(bank());
+        let mut client = Client::new(&mut api);
+
+        let cases = [
+            (
+                "/accounts/9/deposits",
+                r#"{"amount": 5}"#,
+                404,
+                "account_not_found",
+            ),
+            (
+                "/accounts",
+                r#"{"id": 1, "holder": "again"}"#,
+                409,
+                "duplicate_account",
+            ),
+            (
+                "/accounts/2/withdrawals",
+                r#"{"amount": 5}"#,
+                409,
+                "account_state",
+            ),
+            (
+                "/accounts/1/withdrawals",
+                r#"{"amount": 1000}"#,
+                422,
+                "insufficient_funds",
+            ),
+            (
+                "/transfers",
+                r#"{"from": 1, "to": 3, "amount": 5}"#,
+                422,
+                "conversion",
+            ),
+            (
+                "/accounts/1/deposits",
+                r#"{"amount": 0}"#,
+                422,
+                "invalid_amount",
+            ),
+        ];
+        for (path, body, status, code) in cases {
+            let response = client.post(path, body, None);
+            assert_eq!(
+                (response.status, error_code(&response)),
+                (status, code),
+                "POST {} {}",
+                path,
+                body
+            );
+        }
+
+        let response = client.get("/accounts/9");
+        assert_eq!(
+            (response.status, error_code(&response)),
+            (404, "account_not_found")
+        );
+        assert_eq!(balance(&api, 1), 100);
+    }
+
+    // Used to panic on "attempt to add with overflow", taking the whole server down
+    #[test]
+    fn deposit_that_overflows_the_balance_is_refused() {
+        let mut api = Api::new(bank());
+        let mut client = Client::new(&mut api);
+
+        let response = client.post("/accounts/3/deposits", r#"{"amount": 2147483647}"#, None);
+        assert_eq!(response.status, 201);
+        let response = client.post("/accounts/3/deposits", r#"{"amount": 1}"#, None);
+        assert_eq!((response.status, error_code(&response)), (422, "overflow"));
+
+        // Still serving
+        assert_eq!(client.get("/accounts/3").status, 200);
+        assert_eq!(balance(&api, 3), i32::MAX);
+    }
+
+    #[test]
+    fn blocked_transaction_is_forbidden() {
+        let mut rules = RuleEngine::new();
+        rules.add_rule(Box::new(VelocityLimit {
+            max_transactions: 1,
+            window_days: 1,
+            action: RuleAction::Block,
+        }));
+        let mut api = Api::new(bank().with_rules(rules));
+        let mut client = Client::new(&mut api);
+
+        let response = client.post("/accounts/1/deposits", r#"{"amount": 5}"#, None);
+        assert_eq!((response.status, error_code(&response)), (403, "blocked"));
+        assert_eq!(balance(&api, 1), 100);
+    }
+
+    // The errors no route can run into today still get a status of their own
+    #[test]
+    fn every_bank_error_has_a_status() {
+        let cases = [
+            (BankError::AccountNotFound(1), 404),
+            (BankError::DuplicateAccount(1), 409),
+            (BankError::TransactionNotFound(1), 404),
+            (BankError::AlreadyReversed(1), 409),
+            (
+                BankError::NotReversible {
+                    transaction: 1,
+                    product: String::from("loan 1"),
+                },
+                409,
+            ),
+            (BankError::InvalidAmount(-1), 422),
+            (
+                BankError::InsufficientFunds {
+                    account: 1,
+                    needed: 10,
+                },
+                422,
+            ),
+            (
+                BankError::Account(AccountError::IllegalTransition {
+                    id: 1,
+                    from: AccountState::Closed,
+                    to: AccountState::Open,
+                }),
+                409,
+            ),
+            (
+                BankError::Blocked {
+                    rule: String::from("velocity"),
+                    reason: String::from("too many"),
+                },
+                403,
+            ),
+            (
+                BankError::Fx(FxError::MissingRate {
+                    from: Currency::Eur,
+                    to: Currency::Usd,
+                }),
+                422,
+            ),
+            (BankError::Account(AccountError::Overflow { id: 1 }), 422),
+            (BankError::Fx(FxError::Overflow), 422),
+            (BankError::Fx(FxError::Io(String::from("gone"))), 500),
+            (BankError::Loan(LoanError::LoanNotFound(1)), 404),
+            (BankError::Loan(LoanError::CreditLineNotFound(1)), 404),
+            (BankError::Loan(LoanError::AlreadyPaidOff(1)), 409),
+            (
+                BankError::Loan(LoanError::LimitExceeded {
+                    line: 1,
+                    available: 0,
+                }),
+                422,
+            ),
+            (
+                BankError::StandingOrder(StandingOrderError::NotFound(1)),
+                404,
+            ),
+            (
+                BankError::StandingOrder(StandingOrderError::AlreadyCancelled(1)),
+                409,
+            ),
+            (
+                BankError::StandingOrder(StandingOrderError::InvalidAmount(0)),
+                422,
+            ),
+        ];
+        for (error, status) in cases {
+            assert_eq!(bank_error(&error).status, status, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn replayed_idempotency_key_gets_the_same_response() {
+        let mut api = Api::new(bank());
+        let mut client = Client::new(&mut api);
+
+        let first = client.post("/accounts/1/deposits", r#"{"amount": 40}"#, Some("key-1"));
+        let again = client.post("/accounts/1/deposits", r#"{"amount": 40}"#, Some("key-1"));
+        assert_eq!(first.status, 201);
+        assert_eq!(first, again);
+
+        // The setup made one transaction, the two requests only one more
+        assert_eq!(api.bank().transactions().len(), 2);
+        assert_eq!(balance(&api, 1), 140);
+    }
+
+    #[test]
+    fn idempotency_key_reused_for_another_body_is_a_conflict() {
+        let mut api = Api::new(bank());
+        let mut client = Client::new(&mut api);
+
+        client.post("/accounts/1/deposits", r#"{"amount": 40}"#, Some("key-1"));
+        let response = client.post("/accounts/1/deposits", r#"{"amount": 99}"#, Some("key-1"));
+        assert_eq!(
+            (response.status, error_code(&response)),
+            (409, "idempotency_key_reused")
+        );
+        assert_eq!(balance(&api, 1), 140);
+    }
+
+    #[test]
+    fn oldest_idempotency_keys_are_forgotten() {
+        let mut api = Api::new(bank());
+        let mut client = Client::new(&mut api);
+
+        for index in 0..=MAX_IDEMPOTENCY_KEYS {
+            client.post("/nowhere", "", Some(&format!("key-{}", index)));
+        }
+        // 'key-0' is gone: using it again is a new request, not a conflict
+        let response = client.post("/accounts/1/deposits", r#"{"amount": 1}"#, Some("key-0"));
+        assert_eq!(response.status, 201);
+        // ('key-1' made room for it, 'key-2' is still known)
+        let response = client.post("/nowhere", "{}", Some("key-2"));
+        assert_eq!(response.status, 409);
+    }
+
+    #[test]
+    fn malformed_json_is_a_bad_request() {
+        let mut api = Api::new(bank());
+        let mut client = Client::new(&mut api);
+
+        let nested = "[".repeat(100_000);
+        for body in [r#"{"amount": 5"#, "amount=5", r#"{"amount": 5.5}"#, &nested] {
+            let response = client.post("/accounts/1/deposits", body, None);
+            assert_eq!(
+                (response.status, error_code(&response)),
+                (400, "bad_request"),
+                "{}",
+                &body[..body.len().min(20)]
+            );
+        }
+        assert_eq!(balance(&api, 1), 100);
+    }
+
+    #[test]
+    fn surrogate_pairs_are_one_character() {
+        let mut api = Api::new(bank());
+        let mut client = Client::new(&mut api);
+
+        let response = client.post(
+            "/accounts",
+            r#"{"id": 4, "holder": "\ud83d\ude00 \u00e9"}"#,
+            None,
+        );
+        assert_eq!(response.status, 201);
+        assert_eq!(api.bank().account(4).unwrap().holder(), "\u{1F600} \u{e9}");
+
+        let mut client = Client::new(&mut api);
+        let response = client.post("/accounts", r#"{"id": 5, "holder": "\ud83d"}"#, None);
+        assert_eq!(response.status, 400);
+    }
+
+    #[test]
+    fn unknown_routes() {
+        let mut api = Api::new(bank());
+        let mut client = Client::new(&mut api);
+
+        assert_eq!(client.get("/loans").status, 404);
+        assert_eq!(client.get("/transfers").status, 405);
+        assert_eq!(client.post("/accounts/1", "{}", None).status, 405);
+    }
+}
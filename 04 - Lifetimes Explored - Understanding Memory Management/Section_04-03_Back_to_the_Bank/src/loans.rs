@@ -0,0 +1,332 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::clock::Date;
+use crate::currency::RoundingMode;
+
+// Rates are expressed in basis points: 1% = 100 bp, 6.5% = 650 bp
+pub const BASIS_POINTS: i64 = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoanError {
+    LoanNotFound(u32),
+    CreditLineNotFound(u32),
+    AlreadyPaidOff(u32),
+    // Principal, term or limit of 0 (or less)
+    InvalidTerms(String),
+    // Drawing or paying back 0 (or less) on a credit line
+    InvalidAmount { line: u32, amount: i32 },
+    LimitExceeded { line: u32, available: i32 },
+    // Paying back more than what was drawn on a credit line
+    Overpayment { line: u32, drawn: i32 },
+}
+
+impl fmt::Display for LoanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoanError::LoanNotFound(id) => write!(f, "loan {} not found", id),
+            LoanError::CreditLineNotFound(id) => write!(f, "credit line {} not found", id),
+            LoanError::AlreadyPaidOff(id) => write!(f, "loan {} is already paid off", id),
+            LoanError::InvalidTerms(message) => write!(f, "invalid terms: {}", message),
+            LoanError::InvalidAmount { line, amount } => {
+                write!(f, "credit line {} can't move {}", line, amount)
+            }
+            LoanError::LimitExceeded { line, available } => {
+                write!(f, "credit line {} only has {} available", line, available)
+            }
+            LoanError::Overpayment { line, drawn } => {
+                write!(f, "credit line {} only has {} to pay back", line, drawn)
+            }
+        }
+    }
+}
+
+impl Error for LoanError {}
+
+// One line of an amortization schedule
+#[derive(Debug, Clone)]
+pub struct Installment {
+    pub number: u32,
+    pub due: Date,
+    pub payment: i32,
+    pub interest: i32,
+    pub principal: i32,
+    // Principal still owed once this installment is paid
+    pub remaining: i32,
+    // Charged when the installment couldn't be collected on its due date
+    pub penalty: i32,
+    pub paid_on: Option<Date>,
+}
+
+// Fixed payments every month ("annuity"): the share of interest goes down,
+// the share of principal goes up, and the last payment absorbs the rounding.
+// Everything is computed with integers, like conversions (see 'currency'):
+//   payment = principal * r * (1 + r)^n / ((1 + r)^n - 1), with r the monthly rate
+pub fn amortization_schedule(
+    principal: i32,
+    annual_rate_bp: u32,
+    term_months: u32,
+    start: Date,
+) -> Vec<Installment> {
+    let payment = if annual_rate_bp == 0 {
+        RoundingMode::Up.divide(principal as i64, term_months as i64) as i32
+    } else {
+        annuity_payment(principal, annual_rate_bp, term_months)
+    };
+
+    let mut remaining = principal;
+    let mut schedule = vec![];
+    for number in 1..=term_months {
+        let interest = RoundingMode::HalfUp
+            .divide(remaining as i64 * annual_rate_bp as i64, BASIS_POINTS * 12)
+            as i32;
+        let principal_part = if number == term_months {
+            remaining
+        } else {
+            (payment - interest).min(remaining)
+        };
+        remaining -= principal_part;
+
+        schedule.push(Installment {
+            number,
+            due: start.add_months(number),
+            payment: principal_part + interest,
+            interest,
+            principal: principal_part,
+            remaining,
+            penalty: 0,
+            paid_on: None,
+        });
+    }
+
+    schedule
+}
+
+// Precision of (1 + r)^n: 12 decimal places
+const GROWTH_SCALE: i128 = 1_000_000_000_000;
+
+fn annuity_payment(principal: i32, annual_rate_bp: u32, term_months: u32) -> i32 {
+    let (principal, rate) = (principal as i128, annual_rate_bp as i128);
+    let months_per_year = BASIS_POINTS as i128 * 12;
+
+    // (1 + r)^n, rounded at every step. None once it's too big to fit: the payment
+    // is then just the interest of a month (what it tends to as n grows)
+    let mut growth = Some(GROWTH_SCALE);
+    for _ in 0..term_months {
+        growth = growth
+            .and_then(|growth| growth.checked_mul(months_per_year + rate))
+            .map(|growth| RoundingMode::HalfUp.divide_wide(growth, months_per_year));
+    }
+
+    let payment = growth
+        .and_then(|growth| {
+            let numerator = (principal * rate).checked_mul(growth)?;
+            let denominator = months_per_year * (growth - GROWTH_SCALE);
+            Some(RoundingMode::HalfUp.divide_wide(numerator, denominator))
+        })
+        .unwrap_or_else(|| RoundingMode::HalfUp.divide_wide(principal * rate, months_per_year));
+    payment.min(i32::MAX as i128) as i32
+}
+
+#[derive(Debug, Clone)]
+pub struct Loan {
+    pub id: u32,
+    // Receives the principal, and pays the installments
+    pub account: u32,
+    pub principal: i32,
+    pub annual_rate_bp: u32,
+    pub term_months: u32,
+    pub opened_on: Date,
+    pub schedule: Vec<Installment>,
+    pub paid_off_on: Option<Date>,
+}
+
+impl Loan {
+    pub fn new(
+        id: u32,
+        account: u32,
+        principal: i32,
+        annual_rate_bp: u32,
+        term_months: u32,
+        opened_on: Date,
+    ) -> Self {
+        Loan {
+            id,
+            account,
+            principal,
+            annual_rate_bp,
+            term_months,
+            opened_on,
+            schedule: amortization_schedule(principal, annual_rate_bp, term_months, opened_on),
+            paid_off_on: None,
+        }
+    }
+
+    pub fn is_paid_off(&self) -> bool {
+        self.paid_off_on.is_some()
+    }
+
+    // The oldest installment that hasn't been paid yet
+    pub fn next_installment(&self) -> Option<&Installment> {
+        self.schedule.iter().find(|i| i.paid_on.is_none())
+    }
+
+    pub fn outstanding_principal(&self) -> i32 {
+        self.next_installment()
+            .map(|i| i.remaining + i.principal)
+            .unwrap_or(0)
+    }
+
+    pub fn penalties_due(&self) -> i32 {
+        self.schedule
+            .iter()
+            .filter(|i| i.paid_on.is_none())
+            .map(|i| i.penalty)
+            .sum()
+    }
+
+    // Paying early: only the principal left (no future interest), plus unpaid penalties
+    pub fn payoff_amount(&self) -> i32 {
+        self.outstanding_principal() + self.penalties_due()
+    }
+
+    pub fn pay_installment(&mut self, number: u32, on: Date) {
+        if let Some(installment) = self.schedule.iter_mut().find(|i| i.number == number) {
+            installment.paid_on = Some(on);
+        }
+        if self.next_installment().is_none() {
+            self.paid_off_on = Some(on);
+        }
+    }
+
+    pub fn charge_penalty(&mut self, number: u32, amount: i32) {
+        if let Some(installment) = self.schedule.iter_mut().find(|i| i.number == number) {
+            installment.penalty += amount;
+        }
+    }
+
+    // Settles every remaining installment at once
+    pub fn pay_off(&mut self, on: Date) {
+        for installment in self.schedule.iter_mut().filter(|i| i.paid_on.is_none()) {
+            installment.paid_on = Some(on);
+        }
+        self.paid_off_on = Some(on);
+    }
+}
+
+// A revolving credit line: draw up to the limit, pay back, draw again.
+#[derive(Debug, Clone)]
+pub struct CreditLine {
+    pub id: u32,
+    pub account: u32,
+    pub limit: i32,
+    pub drawn: i32,
+    // Minimum monthly payment: a share of what was drawn, but never less than the floor
+    pub minimum_payment_bp: u32,
+    pub minimum_payment_floor: i32,
+}
+
+impl CreditLine {
+    pub fn new(id: u32, account: u32, limit: i32) -> Self {
+        CreditLine {
+            id,
+            account,
+            limit,
+            drawn: 0,
+            minimum_payment_bp: 300,
+            minimum_payment_floor: 25,
+        }
+    }
+
+    pub fn available(&self) -> i32 {
+        self.limit - self.drawn
+    }
+
+    // A negative draw would lower 'drawn', and give the line room above its limit
+    pub fn check_draw(&self, amount: i32) -> Result<(), LoanError> {
+        self.check_amount(amount)?;
+        if amount > self.available() {
+            return Err(LoanError::LimitExceeded {
+                line: self.id,
+                available: self.available(),
+            });
+        }
+        Ok(())
+    }
+
+    // A negative repayment would credit the account, and push 'drawn' above the limit
+    pub fn check_repay(&self, amount: i32) -> Result<(), LoanError> {
+        self.check_amount(amount)?;
+        if amount > self.drawn {
+            return Err(LoanError::Overpayment {
+                line: self.id,
+                drawn: self.drawn,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_amount(&self, amount: i32) -> Result<(), LoanError> {
+        if amount <= 0 {
+            return Err(LoanError::InvalidAmount {
+                line: self.id,
+                amount,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn minimum_payment(&self) -> i32 {
+        let share = RoundingMode::Up.divide(
+            self.drawn as i64 * self.minimum_payment_bp as i64,
+            BASIS_POINTS,
+        ) as i32;
+
+        share.max(self.minimum_payment_floor).min(self.drawn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(principal: i32, annual_rate_bp: u32, term_months: u32) -> Vec<Installment> {
+        amortization_schedule(
+            principal,
+            annual_rate_bp,
+            term_months,
+            Date::new(2024, 1, 1).unwrap(),
+        )
+    }
+
+    #[test]
+    fn annuity_payments_match_the_textbook() {
+        // 100 000 at 5% over 30 years: 536.82 a month
+        assert_eq!(annuity_payment(100_000, 500, 360), 537);
+        assert_eq!(annuity_payment(1200, 600, 12), 103);
+        // An absurd rate (1000% a month): only the interest of a month, and no overflow
+        assert_eq!(annuity_payment(1200, 1_200_000, 360), 12_000);
+    }
+
+    #[test]
+    fn installments_add_up_to_the_principal() {
+        for (principal, rate, term) in [(1200, 600, 12), (100_000, 500, 360), (1000, 0, 3)] {
+            let schedule = schedule(principal, rate, term);
+            let paid: i32 = schedule.iter().map(|i| i.principal).sum();
+            assert_eq!(paid, principal);
+            assert_eq!(schedule.last().unwrap().remaining, 0);
+            for installment in &schedule {
+                assert_eq!(
+                    installment.payment,
+                    installment.principal + installment.interest
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn without_interest_the_last_payment_is_the_smallest() {
+        let payments: Vec<i32> = schedule(1000, 0, 3).iter().map(|i| i.payment).collect();
+        assert_eq!(payments, vec![334, 334, 332]);
+    }
+}
@@ -1,78 +1,351 @@
-#[derive(Debug)]
-struct Account {
-    id: u32,
-    balance: i32,
-    holder: String,
-}
+mod account;
+mod audit;
+mod bank;
+mod clock;
+mod currency;
+mod events;
+mod fraud;
+mod http;
+mod json;
+mod loans;
+mod standing_orders;
+mod transaction;
+
+use std::env;
+use std::process;
+
+use bank::Bank;
+use clock::{Clock, Date};
+use currency::{Currency, FxRates, format_rate};
+use fraud::RuleEngine;
+use http::{Api, Client};
+use standing_orders::Schedule;
+
+// 'cargo run' runs the demo, 'cargo run -- serve' keeps the bank running behind
+// http://127.0.0.1:8080.
+// 'cargo run -- audit' is a demo too: the bank only lives in memory (there's no ledger
+// file to load), so it reconciles the bank built below, then exits with the code
+// a nightly audit job would use (non-zero when anything is wrong).
+fn main() {
+    let command = env::args().nth(1);
+    let audit_mode = command.as_deref() == Some("audit");
+    let serve_mode = command.as_deref() == Some("serve");
 
-impl Account {
-    fn new(id: u32, holder: String) -> Self {
-        Account {
-            id,
-            holder,
-            balance: 0,
+    let opened_on = Date::new(2024, 1, 1).expect("valid date");
+    // Rules are read from a TOML file next to Cargo.toml (run with 'cargo run' from this folder)
+    let rules = match RuleEngine::from_toml_file("fraud_rules.toml") {
+        Ok(rules) => rules,
+        Err(error) => {
+            println!("Fraud rules disabled: {}", error);
+            RuleEngine::new()
         }
-    }
+    };
+    let fx_rates = FxRates::from_file("fx_rates.txt").unwrap_or_else(|error| {
+        println!("No exchange rates: {}", error);
+        FxRates::new()
+    });
+    println!(
+        "Negative rate: {:?}",
+        FxRates::parse("EUR USD 1.085\nUSD EUR -0.5").map(|_| ())
+    );
+    let mut bank = Bank::new(Clock::new(opened_on))
+        .with_dormancy_period(180)
+        .with_rules(rules)
+        .with_fx_rates(fx_rates)
+        .with_snapshot_every(5)
+        .with_late_penalty(500)
+        .with_standing_order_retries(2);
+    bank.open_account(1, "me", Currency::Eur).unwrap();
+    bank.open_account(2, "you", Currency::Eur).unwrap();
+    bank.open_account(3, "them", Currency::Usd).unwrap();
 
-    fn deposit(&mut self, amount: i32) -> i32 {
-        self.balance += amount;
-        self.balance
-    }
+    // Money always goes through the bank, so that it ends up in the ledger
+    // (calling 'Account::deposit' directly would show up as a discrepancy in the audit)
+    bank.deposit(1, 1000)
+        .expect("open account accepts deposits");
+    bank.withdraw(1, 250)
+        .expect("open account accepts withdrawals");
 
-    fn withdraw(&mut self, amount: i32) -> i32 {
-        self.balance -= amount;
-        self.balance
-    }
+    println!("{}", bank.account(1).unwrap().summary());
+    // No account ever goes below zero
+    println!("Withdrawing 5000: {:?}", bank.withdraw(1, 5000));
 
-    fn summary(&self) -> String {
-        format!(
-            "{} (#{}) has a balance of {}.",
-            self.holder, self.id, self.balance
-        )
+    // EUR -> USD: the rate used is kept with the transaction
+    bank.transfer(1, 3, 99).unwrap();
+    if let Some(conversion) = bank.transactions().last().and_then(|tx| tx.conversion) {
+        println!(
+            "Converted 99 {} into {} {} (rate {}, {:?})",
+            conversion.from,
+            conversion.converted,
+            conversion.to,
+            format_rate(conversion.rate),
+            conversion.rounding
+        );
     }
-}
 
-#[derive(Debug)]
-struct Bank {
-    accounts: Vec<Account>,
-}
+    // Frozen: deposits are fine, withdrawals are rejected
+    bank.freeze(2, "suspicious login").unwrap();
+    println!("Deposit on frozen account: {:?}", bank.deposit(2, 100));
+    println!("Withdraw on frozen account: {:?}", bank.withdraw(2, 50));
+    println!(
+        "Negative deposit on frozen account: {:?}",
+        bank.deposit(2, -50)
+    );
+    bank.reopen(2, "identity confirmed").unwrap();
 
-impl Bank {
-    fn new() -> Self {
-        Bank { accounts: vec![] }
+    // Fraud rules: a few small transfers, a big one (flagged), a quick way back (flagged)
+    // and one transaction too many on the same day (blocked).
+    // Two days later, so that the velocity window (today and yesterday) starts empty.
+    bank.advance_days(2);
+    for _ in 0..3 {
+        bank.transfer(2, 1, 10).unwrap();
     }
+    println!("Large transfer: {:?}", bank.transfer(1, 2, 400));
+    println!("Fifth transaction today: {:?}", bank.deposit(1, 5));
+    println!("Sixth transaction today: {:?}", bank.deposit(1, 5));
+    for flag in bank.flags_for_account(1) {
+        let status = if flag.blocked { "BLOCKED" } else { "flagged" };
+        println!(
+            "  [{}] transaction {} by '{}': {}",
+            status, flag.transaction.id, flag.rule, flag.reason
+        );
+    }
+    println!(
+        "{} flag(s), {} transaction(s) in the ledger",
+        bank.flags().len(),
+        bank.transactions().len()
+    );
 
-    // '&mut self': we can change this reference / this bank.
-    fn add_account(&mut self, account: Account) {
-        self.accounts.push(account);
+    // Oops, that large transfer was a mistake: undo it with a compensating transaction
+    let large = bank
+        .transactions()
+        .iter()
+        .find(|tx| tx.amount == 400)
+        .map(|tx| tx.id);
+    if let Some(id) = large {
+        match bank.reverse_transaction(id, "wrong amount") {
+            Ok(reversal) => println!("Transaction {} reversed by transaction {}", id, reversal),
+            Err(error) => println!("Error: {}", error),
+        }
+        println!(
+            "Reversing it twice: {:?}",
+            bank.reverse_transaction(id, "again")
+        );
     }
 
-    fn total_balance(&self) -> i32 {
-        self.accounts.iter().map(|acc| acc.balance).sum()
+    // Standing orders: rent from #2 on the 1st of every month (retried twice when
+    // there isn't enough money), pocket money to #3 every Friday
+    let rent = bank
+        .create_standing_order(2, 1, 150, Schedule::parse("monthly 1").unwrap())
+        .unwrap();
+    let pocket_money = bank
+        .create_standing_order(1, 3, 20, Schedule::parse("weekly fri").unwrap())
+        .unwrap();
+    println!("Daily order: {:?}", Schedule::parse("daily"));
+    println!("Invalid schedule: {:?}", Schedule::parse("monthly 32"));
+    bank.advance_days(70);
+    bank.cancel_standing_order(pocket_money).unwrap();
+    println!(
+        "Cancelling twice: {:?}",
+        bank.cancel_standing_order(pocket_money)
+    );
+    for order in bank.standing_orders() {
+        println!(
+            "Standing order {}: {} from #{} to #{} {} (active: {}, next run {}, {} retries so far)",
+            order.id,
+            order.amount,
+            order.from,
+            order.to,
+            order.schedule,
+            order.is_active(),
+            order.next_run,
+            order.retries
+        );
+    }
+    for execution in &bank.standing_order(rent).unwrap().history {
+        println!(
+            "  due {}, run on {}: {:?}",
+            execution.due, execution.at, execution.outcome
+        );
     }
 
-    fn summary(&self) -> Vec<String> {
-        self.accounts
-            .iter()
-            .map(|acc| acc.summary())
-            .collect::<Vec<String>>()
+    // A loan of 1200 at 6% over 12 months, paid into (and repaid from) account #1,
+    // and a credit line on account #2
+    let loan = bank.open_loan(1, 1200, 600, 12).unwrap();
+    for loan in bank.loans() {
+        println!(
+            "Loan {} on #{}: {} at {}.{:02}% over {} months, from {}",
+            loan.id,
+            loan.account,
+            loan.principal,
+            loan.annual_rate_bp / 100,
+            loan.annual_rate_bp % 100,
+            loan.term_months,
+            loan.opened_on
+        );
     }
-}
+    for installment in bank.loan(loan).unwrap().schedule.iter().take(3) {
+        println!(
+            "  #{:<2} due {}: pay {} = {} interest + {} principal, {} left",
+            installment.number,
+            installment.due,
+            installment.payment,
+            installment.interest,
+            installment.principal,
+            installment.remaining
+        );
+    }
+    let line = bank.open_credit_line(2, 500).unwrap();
+    bank.draw_credit(line, 300).unwrap();
+    println!("Drawing 300 more: {:?}", bank.draw_credit(line, 300));
+    println!("Drawing -300: {:?}", bank.draw_credit(line, -300));
+    let minimum = bank.credit_line(line).unwrap().minimum_payment();
+    bank.repay_credit(line, minimum).unwrap();
+    println!("Paying back 400: {:?}", bank.repay_credit(line, 400));
+    println!(
+        "Credit line: {} drawn, {} available, minimum payment {}",
+        bank.credit_line(line).unwrap().drawn,
+        bank.credit_line(line).unwrap().available(),
+        bank.credit_line(line).unwrap().minimum_payment()
+    );
 
-fn main() {
-    let mut bank = Bank::new();
-    let mut account = Account::new(1, String::from("me"));
+    // Nothing happens for 200 days (but the rent, as long as #2 can pay it):
+    // #3 becomes dormant
+    let dormant = bank.advance_days(200);
+    println!("Dormant accounts on {}: {:?}", bank.today(), dormant);
+    // Reopened by hand, #3 stays open the next day
+    bank.reopen(3, "customer called").unwrap();
+    bank.advance_days(1);
+    println!(
+        "#3 a day after being reopened: {}",
+        bank.account(3).unwrap().state()
+    );
 
-    account.deposit(500);
-    account.withdraw(250);
+    // 6 installments were collected in the meantime, then the loan is paid off early
+    let paid = bank
+        .loan(loan)
+        .unwrap()
+        .schedule
+        .iter()
+        .filter(|i| i.paid_on.is_some())
+        .count();
+    println!(
+        "{} installments paid, paying off early: {:?}",
+        paid,
+        bank.pay_off_loan(loan)
+    );
+    println!("Paying off twice: {:?}", bank.pay_off_loan(loan));
 
-    println!("{}", account.summary());
+    // Closed: read-only, and there's no way back
+    bank.close(1, "customer request").unwrap();
+    println!("Deposit on closed account: {:?}", bank.deposit(1, 10));
+    match bank.reopen(1, "changed their mind") {
+        Ok(..) => println!("Account reopened"),
+        Err(error) => println!("Error: {}", error),
+    }
+
+    // Every transition is recorded along with its reason
+    let account = bank.account(2).unwrap();
+    println!(
+        "History of #{} (last activity on {}):",
+        account.id(),
+        account.last_activity()
+    );
+    for transition in account.transitions() {
+        println!(
+            "  {}: {} -> {} ({})",
+            transition.at, transition.from, transition.to, transition.reason
+        );
+    }
 
-    bank.add_account(account);
+    // Everything above was recorded as events: the bank can be rebuilt from them,
+    // and we can go back in time
+    let rebuilt = Bank::from_events(Clock::new(bank.today()), bank.events()).unwrap();
+    println!(
+        "{} events, {} snapshots, rebuilt bank has the same summary: {}",
+        bank.events().len(),
+        bank.snapshots().len(),
+        rebuilt.summary() == bank.summary()
+    );
+    let first_day = Date::new(2024, 1, 1).expect("valid date");
+    println!(
+        "Balance of #1 on {}: {:?}, today: {}",
+        first_day,
+        bank.balance_on(1, first_day),
+        bank.account(1).unwrap().balance()
+    );
 
     println!("{:#?}", bank);
 
-    println!("Bank Total balance: {}", bank.total_balance());
+    for currency in [Currency::Eur, Currency::Usd] {
+        match bank.total_balance(currency) {
+            Ok(total) => println!("Bank Total balance: {} {}", total, currency),
+            Err(error) => println!("Bank Total balance in {}: {}", currency, error),
+        }
+    }
+    println!(
+        "Bank Total lending: {:?}",
+        bank.total_lending(Currency::Eur)
+    );
 
     println!("Bank Summary: {:#?}", bank.summary());
+
+    let report = audit::reconcile(&bank);
+    println!("{}", report);
+    if audit_mode {
+        println!(
+            "(audit of the demo bank above, exit code {})",
+            report.exit_code()
+        );
+        process::exit(report.exit_code());
+    }
+
+    // The same bank, through the JSON API (no socket involved here)
+    let mut api = Api::new(bank);
+    let mut client = Client::new(&mut api);
+    let body = r#"{"id": 4, "holder": "web", "currency": "USD"}"#;
+    let opened = client.post("/accounts", body, None);
+    println!("POST /accounts -> {} {}", opened.status, opened.body);
+    let opened = client.post("/accounts", body, None);
+    println!("POST /accounts again -> {} {}", opened.status, opened.body);
+
+    // A retry with the same Idempotency-Key doesn't deposit twice
+    for _ in 0..2 {
+        let response = client.post("/accounts/4/deposits", r#"{"amount": 40}"#, Some("dep-1"));
+        println!(
+            "POST /accounts/4/deposits -> {} {}",
+            response.status, response.body
+        );
+    }
+    let response = client.post("/accounts/4/deposits", r#"{"amount": 99}"#, Some("dep-1"));
+    println!(
+        "Same key, other amount -> {} {}",
+        response.status, response.body
+    );
+
+    let response = client.post("/transfers", r#"{"from": 4, "to": 3, "amount": 15}"#, None);
+    println!("POST /transfers -> {} {}", response.status, response.body);
+    for path in ["/accounts/1/withdrawals", "/accounts/2/withdrawals"] {
+        let response = client.post(path, r#"{"amount": 1}"#, None);
+        println!("POST {} -> {} {}", path, response.status, response.body);
+    }
+    let response = client.post("/accounts/4/withdrawals", r#"{"amount": 1000}"#, None);
+    println!(
+        "More than the balance -> {} {}",
+        response.status, response.body
+    );
+    let response = client.post("/accounts/4/withdrawals", r#"{"amount": "a lot"}"#, None);
+    println!("Invalid amount -> {} {}", response.status, response.body);
+    for path in ["/accounts/9", "/accounts/4/statement", "/accounts"] {
+        let response = client.get(path);
+        println!("GET {} -> {} {}", path, response.status, response.body);
+    }
+    println!(
+        "Audit after the API calls: {}",
+        audit::reconcile(api.bank())
+    );
+
+    if serve_mode && let Err(error) = http::serve(&mut api, 8080) {
+        println!("Server error: {}", error);
+    }
 }
@@ -0,0 +1,244 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::clock::{Date, days_in_month};
+
+// How many times a standing order is tried again (once a day) when the account
+// doesn't have enough money, unless told otherwise
+pub const DEFAULT_STANDING_ORDER_RETRIES: u32 = 3;
+
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StandingOrderError {
+    NotFound(u32),
+    AlreadyCancelled(u32),
+    InvalidSchedule(String),
+    InvalidAmount(i32),
+}
+
+impl fmt::Display for StandingOrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StandingOrderError::NotFound(id) => write!(f, "standing order {} not found", id),
+            StandingOrderError::AlreadyCancelled(id) => {
+                write!(f, "standing order {} is already cancelled", id)
+            }
+            StandingOrderError::InvalidSchedule(text) => write!(f, "invalid schedule '{}'", text),
+            StandingOrderError::InvalidAmount(amount) => {
+                write!(f, "a standing order can't transfer {}", amount)
+            }
+        }
+    }
+}
+
+impl Error for StandingOrderError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    Daily,
+    // 0 = Monday ... 6 = Sunday
+    Weekly { weekday: u32 },
+    // On the 31st of a 30 day month, the order runs on the 30th
+    Monthly { day: u32 },
+}
+
+impl Schedule {
+    // "daily", "weekly friday" (or "weekly fri"), "monthly 1"
+    pub fn parse(text: &str) -> Result<Self, StandingOrderError> {
+        let invalid = || StandingOrderError::InvalidSchedule(text.to_string());
+        let lowercase = text.trim().to_lowercase();
+        let parts: Vec<&str> = lowercase.split_whitespace().collect();
+
+        match parts[..] {
+            ["daily"] => Ok(Schedule::Daily),
+            ["weekly", day] if day.len() >= 3 => WEEKDAYS
+                .iter()
+                .position(|name| name.starts_with(day))
+                .map(|weekday| Schedule::Weekly {
+                    weekday: weekday as u32,
+                })
+                .ok_or_else(invalid),
+            ["monthly", day] => match day.parse() {
+                Ok(day @ 1..=31) => Ok(Schedule::Monthly { day }),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+
+    // The first day strictly after 'date' on which the order runs
+    pub fn next_after(self, date: Date) -> Date {
+        match self {
+            Schedule::Daily => date.add_days(1),
+            Schedule::Weekly { weekday } => {
+                let ahead = (weekday + 7 - date.weekday()) % 7;
+                date.add_days(if ahead == 0 { 7 } else { ahead as i64 })
+            }
+            Schedule::Monthly { day } => {
+                let in_month = |year, month| Date {
+                    year,
+                    month,
+                    day: day.min(days_in_month(year, month)),
+                };
+                let this_month = in_month(date.year, date.month);
+                if this_month > date {
+                    return this_month;
+                }
+                let next = Date { day: 1, ..date }.add_months(1);
+                in_month(next.year, next.month)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::Daily => write!(f, "every day"),
+            Schedule::Weekly { weekday } => {
+                write!(f, "every {}", WEEKDAYS[*weekday as usize % 7])
+            }
+            Schedule::Monthly { day } => write!(f, "on day {} of every month", day),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Executed { transaction: u64 },
+    // Not enough money: we try again tomorrow
+    Retrying { attempt: u32, reason: String },
+    // Out of retries, or refused for another reason: this run is skipped
+    Failed { reason: String },
+}
+
+// One attempt at running a standing order
+#[derive(Debug, Clone)]
+pub struct Execution {
+    // The day the run was scheduled for
+    pub due: Date,
+    pub at: Date,
+    pub outcome: Outcome,
+}
+
+// "Transfer 100 from #1 to #2 on the 1st of every month"
+#[derive(Debug, Clone)]
+pub struct StandingOrder {
+    pub id: u32,
+    pub from: u32,
+    pub to: u32,
+    pub amount: i32,
+    pub schedule: Schedule,
+    pub max_retries: u32,
+    pub next_run: Date,
+    // Failed attempts for the current run
+    pub retries: u32,
+    pub cancelled_on: Option<Date>,
+    pub history: Vec<Execution>,
+}
+
+impl StandingOrder {
+    pub fn new(
+        id: u32,
+        from: u32,
+        to: u32,
+        amount: i32,
+        schedule: Schedule,
+        max_retries: u32,
+        created_on: Date,
+    ) -> Self {
+        StandingOrder {
+            id,
+            from,
+            to,
+            amount,
+            schedule,
+            max_retries,
+            next_run: schedule.next_after(created_on),
+            retries: 0,
+            cancelled_on: None,
+            history: vec![],
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.cancelled_on.is_none()
+    }
+
+    pub fn is_due(&self, today: Date) -> bool {
+        self.is_active() && self.next_run <= today
+    }
+
+    // Keeps the outcome, and moves on to the next run unless we're going to retry
+    pub fn record(&mut self, outcome: Outcome, at: Date) {
+        let retrying = matches!(outcome, Outcome::Retrying { .. });
+        self.history.push(Execution {
+            due: self.next_run,
+            at,
+            outcome,
+        });
+
+        if retrying {
+            self.retries += 1;
+        } else {
+            self.retries = 0;
+            self.next_run = self.schedule.next_after(self.next_run);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> Date {
+        Date::new(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn schedules_are_parsed() {
+        assert_eq!(Schedule::parse("daily"), Ok(Schedule::Daily));
+        assert_eq!(
+            Schedule::parse("Weekly fri"),
+            Ok(Schedule::Weekly { weekday: 4 })
+        );
+        assert_eq!(
+            Schedule::parse("monthly 31"),
+            Ok(Schedule::Monthly { day: 31 })
+        );
+        for text in ["monthly 32", "weekly fr", "yearly"] {
+            assert_eq!(
+                Schedule::parse(text),
+                Err(StandingOrderError::InvalidSchedule(text.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn monthly_orders_run_on_the_last_day_of_short_months() {
+        let schedule = Schedule::Monthly { day: 31 };
+        assert_eq!(schedule.next_after(date(2024, 1, 31)), date(2024, 2, 29));
+        assert_eq!(schedule.next_after(date(2024, 2, 29)), date(2024, 3, 31));
+        assert_eq!(
+            Schedule::Monthly { day: 15 }.next_after(date(2024, 12, 20)),
+            date(2025, 1, 15)
+        );
+    }
+
+    #[test]
+    fn weekly_orders_run_a_week_apart() {
+        // 2024-01-01 is a Monday
+        let friday = Schedule::Weekly { weekday: 4 };
+        assert_eq!(friday.next_after(date(2024, 1, 1)), date(2024, 1, 5));
+        assert_eq!(friday.next_after(date(2024, 1, 5)), date(2024, 1, 12));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::clock::Date;
+use crate::currency::{Conversion, RATE_SCALE, RoundingMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    Deposit { account: u32 },
+    Withdrawal { account: u32 },
+    Transfer { from: u32, to: u32 },
+}
+
+// One line of the bank's ledger.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub id: u64,
+    pub kind: TransactionKind,
+    // Always in the currency of the account the money comes from
+    pub amount: i32,
+    pub at: Date,
+    // Set on transfers between accounts held in different currencies
+    pub conversion: Option<Conversion>,
+    // Set when this transaction undoes another one
+    pub reverses: Option<u64>,
+}
+
+// One side of a transaction on a single account (double-entry style):
+// a positive amount is a credit (money coming in), a negative amount is a debit.
+// A transfer has two legs that must cancel each other out
+// (once the credit leg is converted back to the debit currency).
+#[derive(Debug, Clone)]
+pub struct LedgerEntry {
+    pub transaction_id: u64,
+    pub account: u32,
+    pub amount: i32,
+}
+
+impl Transaction {
+    pub fn involves(&self, account: u32) -> bool {
+        match self.kind {
+            TransactionKind::Deposit { account: id }
+            | TransactionKind::Withdrawal { account: id } => id == account,
+            TransactionKind::Transfer { from, to } => from == account || to == account,
+        }
+    }
+
+    // The accounts money is taken from or given to
+    pub fn accounts(&self) -> Vec<u32> {
+        match self.kind {
+            TransactionKind::Deposit { account } | TransactionKind::Withdrawal { account } => {
+                vec![account]
+            }
+            TransactionKind::Transfer { from, to } => vec![from, to],
+        }
+    }
+
+    // What the receiving account gets (differs from 'amount' after a conversion)
+    pub fn credited(&self) -> i32 {
+        self.conversion
+            .map(|conversion| conversion.converted)
+            .unwrap_or(self.amount)
+    }
+
+    // The compensating transaction: same money, opposite direction.
+    // A converted transfer goes back with the inverse rate, and gives back exactly
+    // what was taken in the first place.
+    pub fn reversal(&self, id: u64, at: Date) -> Transaction {
+        let (kind, amount) = match self.kind {
+            TransactionKind::Deposit { account } => {
+                (TransactionKind::Withdrawal { account }, self.amount)
+            }
+            TransactionKind::Withdrawal { account } => {
+                (TransactionKind::Deposit { account }, self.amount)
+            }
+            TransactionKind::Transfer { from, to } => (
+                TransactionKind::Transfer { from: to, to: from },
+                self.credited(),
+            ),
+        };
+
+        let conversion = self.conversion.map(|conversion| Conversion {
+            from: conversion.to,
+            to: conversion.from,
+            rate: RoundingMode::HalfEven.divide(RATE_SCALE * RATE_SCALE, conversion.rate),
+            rounding: conversion.rounding,
+            converted: self.amount,
+        });
+
+        Transaction {
+            id,
+            kind,
+            amount,
+            at,
+            conversion,
+            reverses: Some(self.id),
+        }
+    }
+
+    pub fn legs(&self) -> Vec<LedgerEntry> {
+        let leg = |account: u32, amount: i32| LedgerEntry {
+            transaction_id: self.id,
+            account,
+            amount,
+        };
+
+        match self.kind {
+            TransactionKind::Deposit { account } => vec![leg(account, self.amount)],
+            TransactionKind::Withdrawal { account } => vec![leg(account, -self.amount)],
+            TransactionKind::Transfer { from, to } => {
+                vec![leg(from, -self.amount), leg(to, self.credited())]
+            }
+        }
+    }
+}
@@ -0,0 +1,648 @@
+// Manages a catalog kept in a file, from the command line:
+//   cargo run --bin catalog -- add book "The Hobbit" --by "J.R.R. Tolkien" --year 1937
+//   cargo run --bin catalog -- list --sort year
+// Every file in 'src/bin' is compiled into its own binary, named after the file.
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::process;
+
+use media_catalog::content::catalog::{Catalog, CatalogError, MediaId};
+use media_catalog::content::media::{
+    Audiobook, Book, Length, Media, MediaKind, Metadata, Movie, Podcast,
+};
+use media_catalog::content::page::Cursor;
+use media_catalog::content::query::{Query, SortBy};
+use media_catalog::content::tags::TagError;
+use media_catalog::storage::{ImportFormat, StorageError};
+
+// Used when no '--file' is given
+const DEFAULT_FILE: &str = "catalog.json";
+
+const USAGE: &str = "\
+usage: catalog [--file PATH] <command> [arguments]
+
+commands:
+  add <kind> <title> [--by NAME] [--episode N] [--year YEAR] [--genre GENRE]...
+                     [--pages N] [--minutes N] [--language CODE] [--rating 0-5]
+  list [--sort FIELD] [--descending]
+  list --limit N [--cursor CURSOR] [--sort FIELD]
+                     one page at a time: the next page starts at the cursor it prints
+  show <id>
+  search [text] [--kind KIND]... [--by NAME] [--genre GENRE] [--years FROM-TO]
+                [--typos N] [--sort FIELD] [--descending]
+  remove <id>
+  import <path>      adds the items of a .json, .csv, .bib (BibTeX), .xml (MARCXML)
+                     or .opml (podcast subscriptions) file
+  export <path>      writes the catalog to a .json or .csv file
+  stats [--json]     counts per kind, genre and decade, top creators, ratings, runtime
+  tag <id> <tag>...
+  untag <id> <tag>...
+  tags [--all TAG]... [--any TAG]...
+                     every tag, or the items that have all the --all tags or any --any tag
+  rename-tag <old> <new> [--merge]
+                     --merge when items are already tagged <new>
+  shelf [name]       every shelf, or the items on one, in its order
+  shelf <name> create|delete
+  shelf <name> add <id> [--at N] | remove <id> | move <id> <N>
+                     positions start at 1; added items go last without --at
+  help
+
+kinds: book, movie, audiobook, podcast
+sort fields: id, kind, title, creator, year, length, rating
+The catalog is kept in 'catalog.json' unless --file says otherwise.";
+
+// Options that don't take a value
+const SWITCHES: [&str; 3] = ["descending", "json", "merge"];
+
+#[derive(Debug)]
+enum CliError {
+    // The command line itself is wrong: the usage is shown along with the message
+    Usage(String),
+    Catalog(CatalogError),
+    Storage(StorageError),
+    Tags(TagError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}", message),
+            CliError::Catalog(error) => write!(f, "{}", error),
+            CliError::Storage(error) => write!(f, "{}", error),
+            CliError::Tags(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+impl From<CatalogError> for CliError {
+    fn from(error: CatalogError) -> Self {
+        CliError::Catalog(error)
+    }
+}
+
+impl From<StorageError> for CliError {
+    fn from(error: StorageError) -> Self {
+        CliError::Storage(error)
+    }
+}
+
+impl From<TagError> for CliError {
+    fn from(error: TagError) -> Self {
+        CliError::Tags(error)
+    }
+}
+
+fn usage(message: String) -> CliError {
+    CliError::Usage(message)
+}
+
+// The words of the command line, split into arguments and '--name value' options
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, Vec<String>>,
+}
+
+impl Args {
+    fn parse(words: Vec<String>) -> Result<Self, CliError> {
+        let mut args = Args {
+            positional: vec![],
+            options: HashMap::new(),
+        };
+
+        let mut words = words.into_iter();
+        while let Some(word) = words.next() {
+            let Some(name) = word.strip_prefix("--") else {
+                args.positional.push(word);
+                continue;
+            };
+
+            let value = if SWITCHES.contains(&name) {
+                String::new()
+            } else {
+                words
+                    .next()
+                    .ok_or_else(|| usage(format!("--{} needs a value", name)))?
+            };
+            args.options
+                .entry(name.to_string())
+                .or_default()
+                .push(value);
+        }
+
+        Ok(args)
+    }
+
+    // Typos in option names are reported instead of being silently ignored
+    fn allow(&self, command: &str, names: &[&str]) -> Result<(), CliError> {
+        match self
+            .options
+            .keys()
+            .find(|name| !names.contains(&name.as_str()))
+        {
+            Some(name) => Err(usage(format!("'{}' has no --{} option", command, name))),
+            None => Ok(()),
+        }
+    }
+
+    // The argument at 'index', 'what' describing it in the error message
+    fn argument(&self, index: usize, what: &str) -> Result<&str, CliError> {
+        self.positional
+            .get(index)
+            .map(|argument| argument.as_str())
+            .ok_or_else(|| usage(format!("missing {}", what)))
+    }
+
+    // The last value given for the option
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .get(name)
+            .and_then(|values| values.last())
+            .map(|value| value.as_str())
+    }
+
+    fn values(&self, name: &str) -> Vec<&str> {
+        self.options
+            .get(name)
+            .map(|values| values.iter().map(|value| value.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        match self.option(name) {
+            None => Ok(None),
+            Some(text) => text
+                .parse()
+                .map(Some)
+                .map_err(|_| usage(format!("--{}: '{}' isn't a valid number", name, text))),
+        }
+    }
+}
+
+fn main() {
+    let words: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(error) = run(words) {
+        eprintln!("error: {}", error);
+        if let CliError::Usage(_) = error {
+            eprintln!("\n{}", USAGE);
+        }
+        process::exit(1);
+    }
+}
+
+fn run(words: Vec<String>) -> Result<(), CliError> {
+    let mut args = Args::parse(words)?;
+    let file = args
+        .options
+        .remove("file")
+        .and_then(|mut files| files.pop())
+        .unwrap_or_else(|| DEFAULT_FILE.to_string());
+
+    if args.positional.is_empty() {
+        return Err(usage(String::from("missing command")));
+    }
+    let command = args.positional.remove(0);
+
+    match command.as_str() {
+        "add" => add(&file, &args),
+        "list" => list(&file, &args),
+        "show" => show(&file, &args),
+        "search" => search(&file, &args),
+        "remove" => remove(&file, &args),
+        "import" => import(&file, &args),
+        "export" => export(&file, &args),
+        "stats" => stats(&file, &args),
+        "tag" => tag(&file, &args, true),
+        "untag" => tag(&file, &args, false),
+        "tags" => tags(&file, &args),
+        "rename-tag" => rename_tag(&file, &args),
+        "shelf" => shelf(&file, &args),
+        "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        command => Err(usage(format!("unknown command '{}'", command))),
+    }
+}
+
+// A catalog that hasn't been saved yet is simply empty
+fn open(file: &str) -> Result<Catalog, CliError> {
+    if Path::new(file).exists() {
+        Ok(Catalog::load(file)?)
+    } else {
+        Ok(Catalog::new())
+    }
+}
+
+fn add(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow(
+        "add",
+        &[
+            "by", "episode", "year", "genre", "pages", "minutes", "language", "rating",
+        ],
+    )?;
+    let kind = args.argument(0, "kind (book, movie, audiobook or podcast)")?;
+    let title = args.argument(1, "title")?.to_string();
+
+    let mut meta = Metadata::new().with_genres(&args.values("genre"));
+    meta.year = args.number("year")?;
+    meta.rating = args.number::<u8>("rating")?.map(|rating| rating.min(5));
+    meta.language = args.option("language").map(|language| language.to_string());
+    if let Some(pages) = args.number("pages")? {
+        meta = meta.with_pages(pages);
+    }
+    if let Some(minutes) = args.number("minutes")? {
+        meta = meta.with_minutes(minutes);
+    }
+
+    let creator = || {
+        args.option("by")
+            .map(|name| name.to_string())
+            .ok_or_else(|| usage(format!("a {} needs --by", kind)))
+    };
+    let media = match parse_kind(kind)? {
+        MediaKind::Book => Media::Book(Book {
+            title,
+            author: creator()?,
+            meta,
+        }),
+        MediaKind::Movie => Media::Movie(Movie {
+            title,
+            director: creator()?,
+            meta,
+        }),
+        MediaKind::Audiobook => Media::Audiobook(Audiobook { title, meta }),
+        MediaKind::Podcast => Media::Podcast(Podcast {
+            show: creator()?,
+            episode: args
+                .number("episode")?
+                .ok_or_else(|| usage(String::from("a podcast needs --episode")))?,
+            title,
+            meta,
+        }),
+        kind @ (MediaKind::Placeholder | MediaKind::Custom(_)) => {
+            let kind = kind.to_string().to_lowercase();
+            return Err(usage(format!("can't add a {}", kind)));
+        }
+    };
+
+    let mut catalog = open(file)?;
+    let description = media.description();
+    let id = catalog.add(media);
+    catalog.save(file)?;
+    println!("Added {}: {}", id, description);
+    Ok(())
+}
+
+fn list(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("list", &["sort", "descending", "limit", "cursor"])?;
+    let catalog = open(file)?;
+
+    if let Some(limit) = args.number("limit")? {
+        if args.flag("descending") {
+            return Err(usage(String::from("pages are only in ascending order")));
+        }
+        let sort = args
+            .option("sort")
+            .map(parse_sort)
+            .unwrap_or(Ok(SortBy::Id))?;
+        let cursor = args.option("cursor").map(Cursor::parse).transpose();
+        let page = cursor
+            .and_then(|cursor| catalog.page(cursor.as_ref(), limit, sort))
+            .map_err(|error| usage(error.to_string()))?;
+        print_table(page.items);
+        if let Some(next) = page.next {
+            println!("next page: --cursor {}", next);
+        }
+        return Ok(());
+    }
+    if args.option("cursor").is_some() {
+        return Err(usage(String::from("--cursor goes with --limit")));
+    }
+    let query = sorted(catalog.query(), args)?;
+    print_table(query.entries().collect());
+    Ok(())
+}
+
+fn show(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("show", &[])?;
+    let id = parse_id(args.argument(0, "id")?)?;
+    let catalog = open(file)?;
+    let media = catalog.get(id)?;
+
+    println!("{} {}", id, media.description());
+    println!("  kind:     {}", media.kind());
+    println!("  title:    {}", media.title());
+    if let Some(creator) = media.creator() {
+        println!("  by:       {}", creator);
+    }
+    if let Media::Podcast(Podcast { episode, .. }) = media {
+        println!("  episode:  {}", episode);
+    }
+    if let Some(meta) = media.meta() {
+        if let Some(year) = meta.year {
+            println!("  year:     {}", year);
+        }
+        if !meta.genres.is_empty() {
+            println!("  genres:   {}", meta.genres.join(", "));
+        }
+        match meta.length {
+            Some(Length::Pages(pages)) => println!("  length:   {} pages", pages),
+            Some(Length::Minutes(minutes)) => println!("  length:   {} minutes", minutes),
+            None => {}
+        }
+        if let Some(language) = &meta.language {
+            println!("  language: {}", language);
+        }
+        if let Some(rating) = meta.rating {
+            println!("  rating:   {}/5", rating);
+        }
+    }
+    Ok(())
+}
+
+fn search(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow(
+        "search",
+        &[
+            "kind",
+            "by",
+            "genre",
+            "years",
+            "typos",
+            "sort",
+            "descending",
+        ],
+    )?;
+    let catalog = open(file)?;
+    let mut query = catalog.query();
+
+    if let Some(text) = args.positional.first() {
+        query = match args.number("typos")? {
+            Some(typos) => query.fuzzy_title(text, typos),
+            None => query.title(text),
+        };
+    }
+    for kind in args.values("kind") {
+        query = query.kind(parse_kind(kind)?);
+    }
+    if let Some(creator) = args.option("by") {
+        query = query.creator(creator);
+    }
+    if let Some(genre) = args.option("genre") {
+        query = query.genre(genre);
+    }
+    if let Some(years) = args.option("years") {
+        let invalid = || usage(format!("--years: '{}' should look like 1990-2020", years));
+        let (from, to) = years.split_once('-').ok_or_else(invalid)?;
+        let from = from.trim().parse().map_err(|_| invalid())?;
+        let to = to.trim().parse().map_err(|_| invalid())?;
+        query = query.years(from..=to);
+    }
+
+    let results: Vec<(MediaId, &Media)> = sorted(query, args)?.entries().collect();
+    if results.is_empty() {
+        println!("Nothing found");
+    } else {
+        print_table(results);
+    }
+    Ok(())
+}
+
+fn remove(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("remove", &[])?;
+    let id = parse_id(args.argument(0, "id")?)?;
+    let mut catalog = open(file)?;
+    let media = catalog.remove(id)?;
+    catalog.save(file)?;
+    println!("Removed {}: {}", id, media.description());
+    Ok(())
+}
+
+// The imported items get new ids: theirs may already be used in this catalog
+fn import(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("import", &[])?;
+    let path = args.argument(0, "file to import")?;
+    let mut catalog = open(file)?;
+
+    // Files from other tools: some of their records may not be media we know about
+    if ImportFormat::from_path(path).is_ok() {
+        let report = catalog.import(path)?;
+        catalog.save(file)?;
+        println!("Imported {} items from {}", report.imported.len(), path);
+        for skipped in &report.skipped {
+            println!(
+                "  skipped record {} ({}): {}",
+                skipped.record, skipped.label, skipped.reason
+            );
+        }
+        return Ok(());
+    }
+
+    let imported = Catalog::load(path)?;
+    let count = imported.len();
+    for media in imported.iter() {
+        catalog.add(media.clone());
+    }
+    catalog.save(file)?;
+    println!("Imported {} items from {}", count, path);
+    Ok(())
+}
+
+fn export(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("export", &[])?;
+    let path = args.argument(0, "file to export to")?;
+    let catalog = open(file)?;
+    catalog.save(path)?;
+    println!("Exported {} items to {}", catalog.len(), path);
+    Ok(())
+}
+
+fn stats(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("stats", &["json"])?;
+    let stats = open(file)?.stats();
+    if args.flag("json") {
+        println!("{}", stats.to_json());
+    } else {
+        println!("{}", stats);
+    }
+    Ok(())
+}
+
+// Tags or untags one item
+fn tag(file: &str, args: &Args, add: bool) -> Result<(), CliError> {
+    let command = if add { "tag" } else { "untag" };
+    args.allow(command, &[])?;
+    let id = parse_id(args.argument(0, "id")?)?;
+    args.argument(1, "tag")?;
+    let mut catalog = open(file)?;
+
+    for tag in &args.positional[1..] {
+        let changed = if add {
+            catalog.tag(id, tag)?
+        } else {
+            catalog.untag(id, tag)?
+        };
+        if !changed {
+            let state = if add { "already" } else { "not" };
+            println!("{} is {} tagged '{}'", id, state, tag);
+        }
+    }
+    catalog.save(file)?;
+    println!("Tags of {}: {}", id, catalog.tags_of(id).join(", "));
+    Ok(())
+}
+
+fn tags(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("tags", &["all", "any"])?;
+    let catalog = open(file)?;
+    let items = match (args.values("all"), args.values("any")) {
+        (all, any) if !all.is_empty() && !any.is_empty() => {
+            return Err(usage(String::from("either --all or --any, not both")));
+        }
+        (all, _) if !all.is_empty() => catalog.tagged_all(&all),
+        (_, any) if !any.is_empty() => catalog.tagged_any(&any),
+        _ => {
+            for (tag, count) in catalog.tags() {
+                println!("{} ({})", tag, count);
+            }
+            return Ok(());
+        }
+    };
+
+    if items.is_empty() {
+        println!("Nothing found");
+    } else {
+        print_table(items);
+    }
+    Ok(())
+}
+
+fn rename_tag(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("rename-tag", &["merge"])?;
+    let old = args.argument(0, "tag to rename")?;
+    let new = args.argument(1, "new name")?;
+    let mut catalog = open(file)?;
+    let count = if args.flag("merge") {
+        catalog.merge_tags(old, new)?
+    } else {
+        catalog.rename_tag(old, new)?
+    };
+    catalog.save(file)?;
+    println!("{} item(s) tagged '{}'", count, new);
+    Ok(())
+}
+
+// Positions on the command line start at 1, on shelves at 0
+fn parse_position(text: &str) -> Result<usize, CliError> {
+    match text.parse::<usize>() {
+        Ok(position) if position > 0 => Ok(position - 1),
+        _ => Err(usage(format!("'{}' isn't a valid position", text))),
+    }
+}
+
+fn shelf(file: &str, args: &Args) -> Result<(), CliError> {
+    args.allow("shelf", &["at"])?;
+    let mut catalog = open(file)?;
+    let Some(name) = args.positional.first() else {
+        for (shelf, count) in catalog.shelves() {
+            println!("{} ({})", shelf, count);
+        }
+        return Ok(());
+    };
+    let Some(action) = args.positional.get(1) else {
+        print_table(catalog.shelf(name)?);
+        return Ok(());
+    };
+
+    let id = || parse_id(args.argument(2, "id")?);
+    match action.as_str() {
+        "create" => catalog.create_shelf(name)?,
+        "delete" => {
+            let items = catalog.delete_shelf(name)?;
+            println!("{} item(s) were on '{}'", items.len(), name);
+        }
+        "add" => {
+            let position = args.option("at").map(parse_position).transpose()?;
+            catalog.add_to_shelf(name, id()?, position)?;
+        }
+        "remove" => catalog.remove_from_shelf(name, id()?)?,
+        "move" => {
+            let position = parse_position(args.argument(3, "position")?)?;
+            catalog.move_on_shelf(name, id()?, position)?;
+        }
+        action => return Err(usage(format!("unknown shelf action '{}'", action))),
+    }
+    catalog.save(file)?;
+    if action != "delete" {
+        print_table(catalog.shelf(name)?);
+    }
+    Ok(())
+}
+
+fn sorted<'a>(query: Query<'a>, args: &Args) -> Result<Query<'a>, CliError> {
+    let mut query = match args.option("sort") {
+        Some(field) => query.sort_by(parse_sort(field)?),
+        None => query,
+    };
+    if args.flag("descending") {
+        query = query.descending();
+    }
+    Ok(query)
+}
+
+//  ID  DESCRIPTION
+//  --  -----------
+//  #1  Book: The Hobbit by J.R.R. Tolkien (1937)
+fn print_table(entries: Vec<(MediaId, &Media)>) {
+    let ids: Vec<String> = entries.iter().map(|(id, _)| id.to_string()).collect();
+    let width = ids.iter().map(|id| id.len()).max().unwrap_or(0).max(2);
+
+    println!("{:<width$}  DESCRIPTION", "ID");
+    println!("{:<width$}  {}", "-".repeat(width), "-".repeat(11));
+    for (id, (_, media)) in ids.iter().zip(&entries) {
+        println!("{:<width$}  {}", id, media.description());
+    }
+    println!("{} item(s)", entries.len());
+}
+
+// "3" and "#3" both work
+fn parse_id(text: &str) -> Result<MediaId, CliError> {
+    text.trim_start_matches('#')
+        .parse()
+        .map(MediaId)
+        .map_err(|_| usage(format!("'{}' isn't a valid id", text)))
+}
+
+fn parse_kind(name: &str) -> Result<MediaKind, CliError> {
+    match name.to_lowercase().as_str() {
+        "book" => Ok(MediaKind::Book),
+        "movie" => Ok(MediaKind::Movie),
+        "audiobook" => Ok(MediaKind::Audiobook),
+        "podcast" => Ok(MediaKind::Podcast),
+        _ => Err(usage(format!("unknown kind '{}'", name))),
+    }
+}
+
+fn parse_sort(name: &str) -> Result<SortBy, CliError> {
+    match name.to_lowercase().as_str() {
+        "id" => Ok(SortBy::Id),
+        "kind" => Ok(SortBy::Kind),
+        "title" => Ok(SortBy::Title),
+        "creator" => Ok(SortBy::Creator),
+        "year" => Ok(SortBy::Year),
+        "length" => Ok(SortBy::Length),
+        "rating" => Ok(SortBy::Rating),
+        _ => Err(usage(format!("can't sort by '{}'", name))),
+    }
+}
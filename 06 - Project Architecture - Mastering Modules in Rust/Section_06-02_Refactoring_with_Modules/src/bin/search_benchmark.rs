@@ -0,0 +1,155 @@
+// Compares 'Catalog::search' with and without the full-text index, on a generated catalog:
+//   cargo run --release --bin search_benchmark -- 200000
+// (without --release, everything is much slower, but the comparison still holds)
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use media_catalog::content::catalog::Catalog;
+use media_catalog::content::media::{Audiobook, Book, Media, Metadata, Movie};
+use media_catalog::content::search::Hit;
+
+const DEFAULT_ITEMS: usize = 200_000;
+
+const WORDS: [&str; 24] = [
+    "lord", "rings", "night", "shadow", "river", "city", "king", "stories", "winter", "garden",
+    "dragon", "silent", "ocean", "house", "glass", "empire", "journey", "secret", "storm",
+    "mirror", "forest", "machine", "letters", "return",
+];
+const NAMES: [&str; 8] = [
+    "Tolkien",
+    "Le Guin",
+    "Herbert",
+    "Austen",
+    "Scott",
+    "Kubrick",
+    "Morrison",
+    "Pratchett",
+];
+
+const SEARCHES: [&str; 6] = [
+    "dragon",
+    "winter story",
+    "\"lord of the rings\"",
+    "storm OR ocean",
+    "king -tolkien",
+    "(shadow OR mirror) AND herbert",
+];
+
+// How many times every search is run, to get a steadier time
+const ROUNDS: u32 = 5;
+
+// Always the same "random" numbers, so that two runs search the same catalog
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, limit: usize) -> usize {
+        // A linear congruential generator (Knuth's MMIX constants)
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        ((self.0 >> 33) % limit as u64) as usize
+    }
+}
+
+fn generate(count: usize) -> Catalog {
+    let mut random = Random(42);
+    let mut catalog = Catalog::new();
+
+    for i in 0..count {
+        let words = 2 + random.below(4);
+        let mut title: Vec<&str> = (0..words)
+            .map(|_| WORDS[random.below(WORDS.len())])
+            .collect();
+        // A few real titles, for the phrase search
+        if i % 1000 == 0 {
+            title = vec!["The", "Lord", "of", "the", "Rings"];
+        }
+
+        let title = title.join(" ");
+        let creator = NAMES[random.below(NAMES.len())].to_string();
+        let meta = Metadata::new().with_year(1900 + random.below(125) as u16);
+        catalog.add(if i % 3 == 0 {
+            Media::Movie(Movie {
+                title,
+                director: creator,
+                meta,
+            })
+        } else {
+            Media::Book(Book {
+                title,
+                author: creator,
+                meta,
+            })
+        });
+    }
+
+    catalog
+}
+
+// The results of every search, and how long they took on average
+fn run_searches(catalog: &Catalog) -> Vec<(Vec<Hit>, Duration)> {
+    SEARCHES
+        .iter()
+        .map(|text| {
+            let start = Instant::now();
+            let mut hits = vec![];
+            for _ in 0..ROUNDS {
+                hits = catalog.search(text).expect("a valid search");
+            }
+            (hits, start.elapsed() / ROUNDS)
+        })
+        .collect()
+}
+
+fn main() {
+    let count = env::args()
+        .nth(1)
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(DEFAULT_ITEMS);
+
+    let start = Instant::now();
+    let mut catalog = generate(count);
+    println!("Generated {} items in {:?}", catalog.len(), start.elapsed());
+
+    let linear = run_searches(&catalog);
+
+    let start = Instant::now();
+    catalog.enable_index();
+    println!("Indexed them in {:?}", start.elapsed());
+
+    let indexed = run_searches(&catalog);
+
+    println!();
+    println!(
+        "{:<32} {:>8} {:>14} {:>14} {:>8}",
+        "search", "results", "linear scan", "index", "speedup"
+    );
+    for (text, ((linear_hits, linear_time), (indexed_hits, indexed_time))) in
+        SEARCHES.iter().zip(linear.iter().zip(&indexed))
+    {
+        // Both ways must find the same items, in the same order
+        assert_eq!(linear_hits, indexed_hits, "different results for {}", text);
+        println!(
+            "{:<32} {:>8} {:>14} {:>14} {:>7.0}x",
+            text,
+            indexed_hits.len(),
+            format!("{:.2?}", linear_time),
+            format!("{:.2?}", indexed_time),
+            linear_time.as_secs_f64() / indexed_time.as_secs_f64().max(1e-9)
+        );
+    }
+
+    // Keeping the index up to date costs a little on every change
+    let start = Instant::now();
+    let id = catalog.add(Media::Audiobook(Audiobook {
+        title: String::from("Dragon Winter"),
+        meta: Metadata::new(),
+    }));
+    let _ = catalog.remove(id);
+    println!(
+        "\nAdding and removing an item with the index: {:?}",
+        start.elapsed()
+    );
+}
@@ -1,29 +1,278 @@
+use std::error::Error;
+use std::fmt;
+
 // 'super' is a reference to the parent module (aka 'content')
+use super::lending::Lending;
 use super::media::Media;
+use super::progress::ProgressLog;
+use super::query::Query;
+use super::recommend::UserRatings;
+use super::search::SearchIndex;
+use super::series::SeriesList;
+use super::tags::Tags;
 
 // When you see the crate keyword at the beginning of a path,
 // it means you are specifying an absolute path starting from the root of your own crate.
 //use crate::content::media::Media;
 
-#[derive(Debug)]
+// Given by the catalog when an item is added, and never reused:
+// unlike an index, it keeps pointing to the same item when others are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MediaId(pub u64);
+
+impl fmt::Display for MediaId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatalogError {
+    UnknownId(MediaId),
+    DuplicateId(MediaId),
+    // A book can't be merged with a movie
+    KindMismatch(MediaId, MediaId),
+    // Two items that are both lent can't become one
+    BothCheckedOut(MediaId, MediaId),
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CatalogError::UnknownId(id) => write!(f, "no item with id {}", id),
+            CatalogError::DuplicateId(id) => write!(f, "id {} is already used", id),
+            CatalogError::KindMismatch(a, b) => {
+                write!(f, "{} and {} aren't the same kind of media", a, b)
+            }
+            CatalogError::BothCheckedOut(a, b) => {
+                write!(f, "{} and {} are both checked out", a, b)
+            }
+        }
+    }
+}
+
+impl Error for CatalogError {}
+
+#[derive(Debug, PartialEq)]
 pub struct Catalog {
-    items: Vec<Media>,
+    // In the order they were added
+    items: Vec<(MediaId, Media)>,
+    next_id: u64,
+    // Loans and holds, see 'lending'
+    lending: Lending,
+    // Series and collections, see 'series'
+    series: SeriesList,
+    // Who got how far in what, see 'progress'
+    progress: ProgressLog,
+    // What each user thinks of the items, see 'recommend'
+    ratings: UserRatings,
+    // Tags and shelves, see 'tags'
+    tags: Tags,
+    // Only there once 'enable_index' is called: it's kept up to date from then on
+    index: Option<SearchIndex>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Catalog::new()
+    }
 }
 
 impl Catalog {
     pub fn new() -> Self {
         // Catalog { items: Vec::new() }
-        Catalog { items: vec![] }
+        Catalog {
+            items: vec![],
+            next_id: 1,
+            lending: Lending::new(),
+            series: SeriesList::new(),
+            progress: ProgressLog::new(),
+            ratings: UserRatings::new(),
+            tags: Tags::new(),
+            index: None,
+        }
+    }
+
+    pub fn add(&mut self, media: Media) -> MediaId {
+        let id = MediaId(self.next_id);
+        self.next_id += 1;
+        if let Some(index) = &mut self.index {
+            index.add(id, &media);
+        }
+        self.items.push((id, media));
+        id
+    }
+
+    // Adds an item with a given id (when loading a saved catalog, for instance)
+    pub fn insert(&mut self, id: MediaId, media: Media) -> Result<(), CatalogError> {
+        if self.position(id).is_some() {
+            return Err(CatalogError::DuplicateId(id));
+        }
+
+        self.next_id = self.next_id.max(id.0 + 1);
+        if let Some(index) = &mut self.index {
+            index.add(id, &media);
+        }
+        self.items.push((id, media));
+        Ok(())
+    }
+
+    pub fn get(&self, id: MediaId) -> Result<&Media, CatalogError> {
+        let index = self.position(id).ok_or(CatalogError::UnknownId(id))?;
+        Ok(&self.items[index].1)
+    }
+
+    // catalog.update(id, |media| ...) changes the item in place
+    pub fn update<F>(&mut self, id: MediaId, f: F) -> Result<(), CatalogError>
+    where
+        F: FnOnce(&mut Media),
+    {
+        let index = self.position(id).ok_or(CatalogError::UnknownId(id))?;
+        f(&mut self.items[index].1);
+        self.reindex(index);
+        Ok(())
+    }
+
+    // Puts 'media' in place of the item, and gives the old one back
+    pub fn replace(&mut self, id: MediaId, media: Media) -> Result<Media, CatalogError> {
+        let index = self.position(id).ok_or(CatalogError::UnknownId(id))?;
+        let old = std::mem::replace(&mut self.items[index].1, media);
+        self.reindex(index);
+        Ok(old)
+    }
+
+    // Everything about 'from' (loan, holds, series, progress, ratings, tags, shelves)
+    // now applies to 'to', before 'from' is removed by a merge
+    pub(super) fn move_state(&mut self, from: MediaId, to: MediaId) {
+        self.lending.move_to(from, to);
+        self.series.move_to(from, to);
+        self.progress.move_to(from, to);
+        self.ratings.move_to(from, to);
+        self.tags.move_to(from, to);
+    }
+
+    pub fn remove(&mut self, id: MediaId) -> Result<Media, CatalogError> {
+        let index = self.position(id).ok_or(CatalogError::UnknownId(id))?;
+        self.lending.forget(id);
+        self.series.forget(id);
+        self.progress.forget(id);
+        self.ratings.forget(id);
+        self.tags.forget(id);
+        if let Some(index) = &mut self.index {
+            index.remove(id);
+        }
+        Ok(self.items.remove(index).1)
+    }
+
+    // The id the next added item will get
+    pub fn next_id(&self) -> MediaId {
+        MediaId(self.next_id)
+    }
+
+    // Makes sure ids below 'next' are never given out again
+    // (they may have belonged to items that were removed before the catalog was saved)
+    pub fn reserve_ids(&mut self, next: MediaId) {
+        self.next_id = self.next_id.max(next.0);
+    }
+
+    fn position(&self, id: MediaId) -> Option<usize> {
+        self.items.iter().position(|(item_id, _)| *item_id == id)
+    }
+
+    // Builds a full-text index of the titles and creators, for faster 'search'es.
+    // Worth it for large catalogs: it takes memory, and time on every change.
+    pub fn enable_index(&mut self) {
+        let mut index = SearchIndex::new();
+        for (id, media) in &self.items {
+            index.add(*id, media);
+        }
+        self.index = Some(index);
+    }
+
+    pub fn disable_index(&mut self) {
+        self.index = None;
+    }
+
+    // The item at 'position' has changed
+    fn reindex(&mut self, position: usize) {
+        let (id, media) = &self.items[position];
+        if let Some(index) = &mut self.index {
+            index.remove(*id);
+            index.add(*id, media);
+        }
+    }
+
+    pub(super) fn index(&self) -> Option<&SearchIndex> {
+        self.index.as_ref()
+    }
+
+    // 'pub(super)': only the other 'content' modules can reach the loans directly
+    pub(super) fn lending(&self) -> &Lending {
+        &self.lending
+    }
+
+    pub(super) fn lending_mut(&mut self) -> &mut Lending {
+        &mut self.lending
+    }
+
+    pub(super) fn progress_log(&self) -> &ProgressLog {
+        &self.progress
+    }
+
+    pub(super) fn progress_log_mut(&mut self) -> &mut ProgressLog {
+        &mut self.progress
+    }
+
+    pub(super) fn user_ratings(&self) -> &UserRatings {
+        &self.ratings
+    }
+
+    pub(super) fn user_ratings_mut(&mut self) -> &mut UserRatings {
+        &mut self.ratings
+    }
+
+    pub(super) fn tag_list(&self) -> &Tags {
+        &self.tags
+    }
+
+    pub(super) fn tag_list_mut(&mut self) -> &mut Tags {
+        &mut self.tags
+    }
+
+    pub(super) fn series_list(&self) -> &SeriesList {
+        &self.series
+    }
+
+    pub(super) fn series_list_mut(&mut self) -> &mut SeriesList {
+        &mut self.series
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
     }
 
-    pub fn add(&mut self, media: Media) {
-        self.items.push(media);
+    pub fn iter(&self) -> impl Iterator<Item = &Media> {
+        self.items.iter().map(|(_, media)| media)
+    }
+
+    // Every item along with its id
+    pub fn entries(&self) -> impl Iterator<Item = (MediaId, &Media)> {
+        self.items.iter().map(|(id, media)| (*id, media))
+    }
+
+    // Search with filters, see 'Query'
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self)
     }
 
     pub fn get_by_index(&self, index: usize) -> Option<&Media> {
         if self.items.len() > index {
             // Good case
-            Some(&self.items[index])
+            Some(&self.items[index].1)
         } else {
             // Bad case
             None
@@ -36,4 +285,4 @@ impl Catalog {
          */
         // self.items.get(index)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,177 @@
+use super::catalog::{Catalog, CatalogError, MediaId};
+use super::media::Media;
+use super::query::edit_distance;
+
+// Articles that get moved around by catalogs ("Hobbit, The")
+const ARTICLES: [&str; 3] = ["the", "a", "an"];
+
+// Two items that look like the same one
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeSuggestion {
+    // The richer of the two records (the one 'merge' keeps)
+    pub keep: MediaId,
+    pub duplicate: MediaId,
+    // From 0.0 (nothing in common) to 1.0 (same normalized title and creator)
+    pub score: f64,
+}
+
+// "The Hobbit", "Hobbit, The" and "the hobbit!" all become "hobbit"
+pub fn normalize_title(title: &str) -> String {
+    let mut words = words(title);
+    if words.len() > 1 && ARTICLES.contains(&words[0].as_str()) {
+        words.remove(0);
+    } else if words.len() > 1 && ARTICLES.contains(&words[words.len() - 1].as_str()) {
+        words.pop();
+    }
+    words.join(" ")
+}
+
+// "Tolkien, J.R.R." and "J. R. R. Tolkien" both become "j r r tolkien":
+// the words are sorted, so their order doesn't matter
+pub fn normalize_creator(creator: &str) -> String {
+    let mut words = words(creator);
+    words.sort();
+    words.join(" ")
+}
+
+// Lowercase words, punctuation removed
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect()
+}
+
+// 1.0 for the same text, down to 0.0 when nothing matches
+fn similarity(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f64 / longest as f64
+}
+
+// Only items of the same kind can be duplicates. The title counts the most;
+// the creator only when both have one, and two different years lower the score.
+// Two different episodes of a show are never the same item, however alike their titles.
+fn score(a: &Media, b: &Media) -> f64 {
+    if a.kind() != b.kind() || matches!(a, Media::Placeholder) {
+        return 0.0;
+    }
+    if let (Media::Podcast(x), Media::Podcast(y)) = (a, b)
+        && x.episode != y.episode
+    {
+        return 0.0;
+    }
+
+    let title = similarity(&normalize_title(a.title()), &normalize_title(b.title()));
+    let mut score = match (a.creator(), b.creator()) {
+        (Some(x), Some(y)) => {
+            0.7 * title + 0.3 * similarity(&normalize_creator(x), &normalize_creator(y))
+        }
+        _ => title,
+    };
+
+    let years = (a.meta().and_then(|m| m.year), b.meta().and_then(|m| m.year));
+    if let (Some(x), Some(y)) = years
+        && x.abs_diff(y) > 1
+    {
+        score *= 0.8;
+    }
+
+    score
+}
+
+// The record that knows the most about the item
+fn richness(media: &Media) -> usize {
+    let creator = media.creator().is_some_and(|name| !name.is_empty());
+    media.meta().map(|meta| meta.known_fields()).unwrap_or(0) + creator as usize
+}
+
+impl Catalog {
+    // Every pair scoring at least 'threshold' (0.85 is a good start), best first.
+    // Compares every item with every other one of the same kind.
+    pub fn find_duplicates(&self, threshold: f64) -> Vec<MergeSuggestion> {
+        let entries: Vec<(MediaId, &Media)> = self.entries().collect();
+        let mut suggestions = vec![];
+
+        for (i, (id_a, a)) in entries.iter().enumerate() {
+            for (id_b, b) in &entries[i + 1..] {
+                let score = score(a, b);
+                if score < threshold {
+                    continue;
+                }
+
+                let (keep, duplicate) = if richness(b) > richness(a) {
+                    (*id_b, *id_a)
+                } else {
+                    (*id_a, *id_b)
+                };
+                suggestions.push(MergeSuggestion {
+                    keep,
+                    duplicate,
+                    score,
+                });
+            }
+        }
+
+        suggestions.sort_by(|x, y| y.score.total_cmp(&x.score));
+        suggestions
+    }
+
+    // Keeps the richer record of the two, completed with what only the other one knows,
+    // and removes the other one. Its loan, holds, series, progress, ratings, tags and
+    // shelves go to the record that's kept. Returns the id of the item that's left.
+    pub fn merge(&mut self, a: MediaId, b: MediaId) -> Result<MediaId, CatalogError> {
+        let (media_a, media_b) = (self.get(a)?, self.get(b)?);
+        if a == b {
+            return Ok(a);
+        }
+        if media_a.kind() != media_b.kind() {
+            return Err(CatalogError::KindMismatch(a, b));
+        }
+        if self.loan(a).is_some() && self.loan(b).is_some() {
+            return Err(CatalogError::BothCheckedOut(a, b));
+        }
+
+        let (keep, other) = if richness(media_b) > richness(media_a) {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        self.move_state(other, keep);
+        let other = self.remove(other)?;
+        self.update(keep, |media| {
+            if let (Some(meta), Some(other)) = (media.meta_mut(), other.meta()) {
+                meta.fill_from(other);
+            }
+        })?;
+
+        Ok(keep)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::media::{Metadata, Podcast};
+
+    fn episode(episode: u32, title: &str) -> Media {
+        Media::Podcast(Podcast {
+            show: "Rustacean Station".to_string(),
+            episode,
+            title: title.to_string(),
+            meta: Metadata::new().with_year(2023),
+        })
+    }
+
+    #[test]
+    fn different_episodes_arent_duplicates() {
+        assert_eq!(
+            score(&episode(1, "Episode 1"), &episode(2, "Episode 2")),
+            0.0
+        );
+        assert!(score(&episode(1, "Episode 1"), &episode(1, "Episode 1!")) > 0.85);
+    }
+}
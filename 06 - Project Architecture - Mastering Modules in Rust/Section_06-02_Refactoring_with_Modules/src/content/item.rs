@@ -0,0 +1,218 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use super::media::{Media, MediaKind, Metadata};
+
+// What every kind of media can tell about itself. The built-in kinds (books, movies,
+// audiobooks, podcasts) implement it and have a variant of 'Media' each; other kinds
+// (comics, games, albums...) implement it and are stored as 'Media::Custom', so that
+// adding one doesn't mean changing 'Media' or any of its matches. 'Media' itself doesn't
+// implement it, so that a 'Media::Custom' can't hold another 'Media'.
+pub trait MediaItem: fmt::Debug {
+    // MediaKind::Custom("comic".into()): the same name as the one given to 'MediaRegistry'
+    fn kind(&self) -> MediaKind;
+
+    fn title(&self) -> &str;
+
+    // Whoever made it, when it makes sense: used by searches, queries and sorting
+    fn creator(&self) -> Option<&str> {
+        None
+    }
+
+    fn meta(&self) -> Option<&Metadata>;
+
+    fn meta_mut(&mut self) -> Option<&mut Metadata>;
+
+    // "Comic: Watchmen #1 by Alan Moore (1986)"
+    fn description(&self) -> String;
+
+    // What's specific to this kind ("issue" -> "1"), as text: this is what gets saved,
+    // along with the title, the creator and the metadata
+    fn fields(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    // Box<dyn MediaItem> can't derive Clone: each kind clones itself
+    fn clone_item(&self) -> Box<dyn MediaItem>;
+}
+
+impl Clone for Box<dyn MediaItem> {
+    fn clone(&self) -> Self {
+        self.clone_item()
+    }
+}
+
+// Two items are the same when everything that gets saved is the same
+impl PartialEq for Box<dyn MediaItem> {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind() == other.kind()
+            && self.title() == other.title()
+            && self.creator() == other.creator()
+            && self.meta() == other.meta()
+            && self.fields() == other.fields()
+    }
+}
+
+// What a saved item of a custom kind is made from, given back to its 'Build' function
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SavedItem {
+    pub title: String,
+    // Empty when there's none
+    pub creator: String,
+    pub meta: Metadata,
+    pub fields: BTreeMap<String, String>,
+}
+
+// Turns a saved item back into one of its kind, or tells what's wrong with it
+pub type Build = fn(SavedItem) -> Result<Box<dyn MediaItem>, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    // "book", "movie"... are always known
+    BuiltIn(&'static str),
+    AlreadyRegistered(&'static str),
+    // Names are saved in files: lowercase letters, digits and '-' only
+    InvalidName(&'static str),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegistryError::BuiltIn(kind) => write!(f, "'{}' is a built-in kind", kind),
+            RegistryError::AlreadyRegistered(kind) => {
+                write!(f, "'{}' is already registered", kind)
+            }
+            RegistryError::InvalidName(kind) => write!(
+                f,
+                "'{}' isn't a valid kind name (lowercase letters, digits and '-')",
+                kind
+            ),
+        }
+    }
+}
+
+impl Error for RegistryError {}
+
+fn valid_name(kind: &str) -> bool {
+    let valid = kind
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    !kind.is_empty() && valid
+}
+
+const BUILT_IN: [&str; 5] = ["book", "movie", "audiobook", "podcast", "placeholder"];
+
+// The kinds that can be loaded besides the built-in ones. Downstream crates register
+// theirs, then load with 'Catalog::load_with':
+//   let mut kinds = MediaRegistry::new();
+//   kinds.register("comic", Comic::build)?;
+//   let catalog = Catalog::load_with("catalog.json", &kinds)?;
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaRegistry {
+    kinds: BTreeMap<&'static str, Build>,
+}
+
+impl MediaRegistry {
+    pub fn new() -> Self {
+        MediaRegistry::default()
+    }
+
+    pub fn register(&mut self, kind: &'static str, build: Build) -> Result<(), RegistryError> {
+        if !valid_name(kind) {
+            return Err(RegistryError::InvalidName(kind));
+        }
+        if BUILT_IN.contains(&kind) {
+            return Err(RegistryError::BuiltIn(kind));
+        }
+        if self.kinds.contains_key(kind) {
+            return Err(RegistryError::AlreadyRegistered(kind));
+        }
+
+        self.kinds.insert(kind, build);
+        Ok(())
+    }
+
+    // The registered kinds, in alphabetical order (built-in ones not included)
+    pub fn kinds(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.kinds.keys().copied()
+    }
+
+    // None when the kind isn't registered
+    pub fn build(&self, kind: &str, saved: SavedItem) -> Option<Result<Media, String>> {
+        let build = self.kinds.get(kind)?;
+        let item = build(saved).and_then(|item| match item.kind() {
+            MediaKind::Custom(name) if name == kind => Ok(item),
+            other => Err(format!("a '{}' was built as a {}", kind, other)),
+        });
+        Some(item.map(Media::Custom))
+    }
+}
+
+// An item of a kind nobody registered (saved by a program that knew about comics, loaded
+// by one that doesn't): it can't do anything of its own, but it's kept as it was
+// saved, so that saving the catalog again doesn't lose it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownItem {
+    kind: String,
+    saved: SavedItem,
+}
+
+impl UnknownItem {
+    // The kind name must be one that could have been registered
+    pub fn new(kind: &str, saved: SavedItem) -> Result<Self, String> {
+        if !valid_name(kind) || BUILT_IN.contains(&kind) {
+            return Err(format!("invalid kind '{}'", kind));
+        }
+        Ok(UnknownItem {
+            kind: kind.to_string(),
+            saved,
+        })
+    }
+}
+
+impl MediaItem for UnknownItem {
+    fn kind(&self) -> MediaKind {
+        MediaKind::Custom(Cow::Owned(self.kind.clone()))
+    }
+
+    fn title(&self) -> &str {
+        &self.saved.title
+    }
+
+    fn creator(&self) -> Option<&str> {
+        Some(self.saved.creator.as_str()).filter(|creator| !creator.is_empty())
+    }
+
+    fn meta(&self) -> Option<&Metadata> {
+        Some(&self.saved.meta)
+    }
+
+    fn meta_mut(&mut self) -> Option<&mut Metadata> {
+        Some(&mut self.saved.meta)
+    }
+
+    // "Vinyl: Blue Train by John Coltrane (1957)"
+    fn description(&self) -> String {
+        let creator = self
+            .creator()
+            .map(|creator| format!(" by {}", creator))
+            .unwrap_or_default();
+        format!(
+            "{}: {}{}{}",
+            self.kind(),
+            self.saved.title,
+            creator,
+            self.saved.meta.details()
+        )
+    }
+
+    fn fields(&self) -> BTreeMap<String, String> {
+        self.saved.fields.clone()
+    }
+
+    fn clone_item(&self) -> Box<dyn MediaItem> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+use super::catalog::{Catalog, MediaId};
+use super::date::Date;
+use super::media::Media;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LendingError {
+    UnknownItem(MediaId),
+    // A Placeholder isn't a real item: there's nothing to lend
+    NotLendable(MediaId),
+    AlreadyCheckedOut {
+        id: MediaId,
+        borrower: String,
+        due: Date,
+    },
+    NotCheckedOut(MediaId),
+    // Someone else is first in line for it
+    ReservedFor {
+        id: MediaId,
+        borrower: String,
+    },
+    AlreadyOnHold {
+        id: MediaId,
+        borrower: String,
+    },
+    NoHold {
+        id: MediaId,
+        borrower: String,
+    },
+}
+
+impl fmt::Display for LendingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LendingError::UnknownItem(id) => write!(f, "no item with id {}", id),
+            LendingError::NotLendable(id) => write!(f, "item {} is a placeholder", id),
+            LendingError::AlreadyCheckedOut { id, borrower, due } => {
+                write!(
+                    f,
+                    "item {} is checked out by {} until {}",
+                    id, borrower, due
+                )
+            }
+            LendingError::NotCheckedOut(id) => write!(f, "item {} isn't checked out", id),
+            LendingError::ReservedFor { id, borrower } => {
+                write!(f, "item {} is reserved for {}", id, borrower)
+            }
+            LendingError::AlreadyOnHold { id, borrower } => {
+                write!(f, "{} already has a hold on item {}", borrower, id)
+            }
+            LendingError::NoHold { id, borrower } => {
+                write!(f, "{} has no hold on item {}", borrower, id)
+            }
+        }
+    }
+}
+
+impl Error for LendingError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loan {
+    pub borrower: String,
+    pub checked_out: Date,
+    pub due: Date,
+}
+
+// What 'check_in' tells the person at the desk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Return {
+    pub borrower: String,
+    // 0 when returned on time
+    pub days_late: u32,
+    // Who the item should be put aside for
+    pub next_in_line: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overdue {
+    pub id: MediaId,
+    pub borrower: String,
+    pub due: Date,
+    pub days_late: u32,
+}
+
+// Who has what, and who's waiting for what
+#[derive(Debug, Default, PartialEq)]
+pub struct Lending {
+    loans: HashMap<MediaId, Loan>,
+    // First come, first served
+    holds: HashMap<MediaId, VecDeque<String>>,
+}
+
+impl Lending {
+    pub fn new() -> Self {
+        Lending::default()
+    }
+
+    // The item is gone: so are its loan and its holds
+    pub fn forget(&mut self, id: MediaId) {
+        self.loans.remove(&id);
+        self.holds.remove(&id);
+    }
+
+    // 'from' was merged into 'to': its loan (when 'to' has none) and its holds go to 'to',
+    // behind the people already waiting for 'to'
+    pub fn move_to(&mut self, from: MediaId, to: MediaId) {
+        if let Some(loan) = self.loans.remove(&from) {
+            self.loans.entry(to).or_insert(loan);
+        }
+        let Some(waiting) = self.holds.remove(&from) else {
+            return;
+        };
+        let queue = self.holds.entry(to).or_default();
+        for borrower in waiting {
+            if !queue.contains(&borrower) {
+                queue.push_back(borrower);
+            }
+        }
+    }
+}
+
+impl Catalog {
+    // Lends the item for 'days' days, and returns the due date.
+    // When people are waiting for it, only the first one in line can take it.
+    pub fn check_out(
+        &mut self,
+        id: MediaId,
+        borrower: &str,
+        on: Date,
+        days: u32,
+    ) -> Result<Date, LendingError> {
+        self.check_lendable(id)?;
+        if let Some(loan) = self.loan(id) {
+            return Err(LendingError::AlreadyCheckedOut {
+                id,
+                borrower: loan.borrower.clone(),
+                due: loan.due,
+            });
+        }
+
+        let lending = self.lending_mut();
+        if let Some(queue) = lending.holds.get_mut(&id) {
+            match queue.front() {
+                Some(first) if first != borrower => {
+                    return Err(LendingError::ReservedFor {
+                        id,
+                        borrower: first.clone(),
+                    });
+                }
+                Some(_) => {
+                    queue.pop_front();
+                }
+                None => {}
+            }
+            if queue.is_empty() {
+                lending.holds.remove(&id);
+            }
+        }
+
+        let due = on.add_days(days as i64);
+        lending.loans.insert(
+            id,
+            Loan {
+                borrower: borrower.to_string(),
+                checked_out: on,
+                due,
+            },
+        );
+        Ok(due)
+    }
+
+    pub fn check_in(&mut self, id: MediaId, on: Date) -> Result<Return, LendingError> {
+        self.check_lendable(id)?;
+        let lending = self.lending_mut();
+        let loan = lending
+            .loans
+            .remove(&id)
+            .ok_or(LendingError::NotCheckedOut(id))?;
+
+        Ok(Return {
+            borrower: loan.borrower,
+            days_late: loan.due.days_until(on).max(0) as u32,
+            next_in_line: lending
+                .holds
+                .get(&id)
+                .and_then(|queue| queue.front().cloned()),
+        })
+    }
+
+    // Returns the position in the queue (1 = next one to get it)
+    pub fn place_hold(&mut self, id: MediaId, borrower: &str) -> Result<usize, LendingError> {
+        self.check_lendable(id)?;
+        let queue = self.lending_mut().holds.entry(id).or_default();
+        if queue.iter().any(|name| name == borrower) {
+            return Err(LendingError::AlreadyOnHold {
+                id,
+                borrower: borrower.to_string(),
+            });
+        }
+
+        queue.push_back(borrower.to_string());
+        Ok(queue.len())
+    }
+
+    pub fn cancel_hold(&mut self, id: MediaId, borrower: &str) -> Result<(), LendingError> {
+        let no_hold = || LendingError::NoHold {
+            id,
+            borrower: borrower.to_string(),
+        };
+        let holds = &mut self.lending_mut().holds;
+        let queue = holds.get_mut(&id).ok_or_else(no_hold)?;
+        let position = queue
+            .iter()
+            .position(|name| name == borrower)
+            .ok_or_else(no_hold)?;
+
+        queue.remove(position);
+        if queue.is_empty() {
+            holds.remove(&id);
+        }
+        Ok(())
+    }
+
+    pub fn loan(&self, id: MediaId) -> Option<&Loan> {
+        self.lending().loans.get(&id)
+    }
+
+    // In the order they were placed
+    pub fn holds(&self, id: MediaId) -> Vec<&str> {
+        self.lending()
+            .holds
+            .get(&id)
+            .map(|queue| queue.iter().map(|name| name.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    // Every item that's lent, by id (that's how they're saved)
+    pub fn all_loans(&self) -> Vec<(MediaId, &Loan)> {
+        let mut loans: Vec<(MediaId, &Loan)> = self
+            .lending()
+            .loans
+            .iter()
+            .map(|(id, loan)| (*id, loan))
+            .collect();
+        loans.sort_by_key(|(id, _)| *id);
+        loans
+    }
+
+    // Every item someone's waiting for, by id, with the people in line in order
+    pub fn all_holds(&self) -> Vec<(MediaId, Vec<&str>)> {
+        let mut holds: Vec<(MediaId, Vec<&str>)> = self
+            .lending()
+            .holds
+            .keys()
+            .map(|id| (*id, self.holds(*id)))
+            .collect();
+        holds.sort_by_key(|(id, _)| *id);
+        holds
+    }
+
+    // Everything 'borrower' has, the first due first
+    pub fn loans_of(&self, borrower: &str) -> Vec<(MediaId, &Loan)> {
+        let mut loans: Vec<(MediaId, &Loan)> = self
+            .lending()
+            .loans
+            .iter()
+            .filter(|(_, loan)| loan.borrower == borrower)
+            .map(|(id, loan)| (*id, loan))
+            .collect();
+        loans.sort_by_key(|(id, loan)| (loan.due, *id));
+        loans
+    }
+
+    // Everything that should have been back before 'today', the latest first
+    pub fn overdue(&self, today: Date) -> Vec<Overdue> {
+        let mut overdue: Vec<Overdue> = self
+            .lending()
+            .loans
+            .iter()
+            .filter(|(_, loan)| loan.due < today)
+            .map(|(id, loan)| Overdue {
+                id: *id,
+                borrower: loan.borrower.clone(),
+                due: loan.due,
+                days_late: loan.due.days_until(today) as u32,
+            })
+            .collect();
+        overdue.sort_by(|a, b| b.days_late.cmp(&a.days_late).then(a.id.cmp(&b.id)));
+        overdue
+    }
+
+    fn check_lendable(&self, id: MediaId) -> Result<(), LendingError> {
+        match self.get(id) {
+            Err(_) => Err(LendingError::UnknownItem(id)),
+            Ok(Media::Placeholder) => Err(LendingError::NotLendable(id)),
+            Ok(_) => Ok(()),
+        }
+    }
+}
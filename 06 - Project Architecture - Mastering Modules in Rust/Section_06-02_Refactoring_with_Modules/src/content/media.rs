@@ -1,29 +1,397 @@
-#[derive(Debug)]
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::item::MediaItem;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MediaKind {
+    Book,
+    Movie,
+    Audiobook,
+    Podcast,
+    Placeholder,
+    // A kind registered by another crate, see 'item': MediaKind::Custom("comic".into()).
+    // A kind name read from a file is owned, since no crate gave it.
+    Custom(Cow<'static, str>),
+}
+
+impl fmt::Display for MediaKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            MediaKind::Book => "Book",
+            MediaKind::Movie => "Movie",
+            MediaKind::Audiobook => "Audiobook",
+            MediaKind::Podcast => "Podcast",
+            MediaKind::Placeholder => "Placeholder",
+            // "comic" -> "Comic"
+            MediaKind::Custom(name) => {
+                let mut chars = name.chars();
+                if let Some(first) = chars.next() {
+                    write!(f, "{}", first.to_ascii_uppercase())?;
+                }
+                return write!(f, "{}", chars.as_str());
+            }
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Pages(u32),
+    Minutes(u32),
+}
+
+// Everything a Media can have in common (besides its title), filled in with the 'with_*'
+// methods. The id of an item isn't part of it: it's given by the Catalog (see MediaId).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub year: Option<u16>,
+    pub genres: Vec<String>,
+    pub length: Option<Length>,
+    pub language: Option<String>,
+    // Out of 5
+    pub rating: Option<u8>,
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Metadata::default()
+    }
+
+    pub fn with_year(mut self, year: u16) -> Self {
+        self.year = Some(year);
+        self
+    }
+
+    pub fn with_genres(mut self, genres: &[&str]) -> Self {
+        self.genres = genres.iter().map(|genre| genre.to_string()).collect();
+        self
+    }
+
+    pub fn with_pages(mut self, pages: u32) -> Self {
+        self.length = Some(Length::Pages(pages));
+        self
+    }
+
+    pub fn with_minutes(mut self, minutes: u32) -> Self {
+        self.length = Some(Length::Minutes(minutes));
+        self
+    }
+
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    // Anything above 5 counts as 5
+    pub fn with_rating(mut self, rating: u8) -> Self {
+        self.rating = Some(rating.min(5));
+        self
+    }
+
+    // How many fields are known (genres count as one)
+    pub fn known_fields(&self) -> usize {
+        [
+            self.year.is_some(),
+            !self.genres.is_empty(),
+            self.length.is_some(),
+            self.language.is_some(),
+            self.rating.is_some(),
+        ]
+        .iter()
+        .filter(|known| **known)
+        .count()
+    }
+
+    // Fields we don't know are taken from 'other', genres of both are kept
+    pub fn fill_from(&mut self, other: &Metadata) {
+        self.year = self.year.or(other.year);
+        self.length = self.length.or(other.length);
+        self.rating = self.rating.or(other.rating);
+        if self.language.is_none() {
+            self.language = other.language.clone();
+        }
+        for genre in &other.genres {
+            if !self.genres.iter().any(|g| g.eq_ignore_ascii_case(genre)) {
+                self.genres.push(genre.clone());
+            }
+        }
+    }
+
+    // "(1937, 310 pages, en, fantasy/adventure, rated 5/5)",
+    // only with the fields that are known
+    pub fn details(&self) -> String {
+        let mut parts = vec![];
+        if let Some(year) = self.year {
+            parts.push(year.to_string());
+        }
+        match self.length {
+            Some(Length::Pages(pages)) => parts.push(format!("{} pages", pages)),
+            Some(Length::Minutes(minutes)) => parts.push(format!("{} min", minutes)),
+            None => {}
+        }
+        if let Some(language) = &self.language {
+            parts.push(language.clone());
+        }
+        if !self.genres.is_empty() {
+            parts.push(self.genres.join("/"));
+        }
+        if let Some(rating) = self.rating {
+            parts.push(format!("rated {}/5", rating));
+        }
+
+        if parts.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", parts.join(", "))
+        }
+    }
+}
+
+// The built-in kinds: like any other kind, each one is a 'MediaItem'
+#[derive(Debug, Clone, PartialEq)]
+pub struct Book {
+    pub title: String,
+    pub author: String,
+    pub meta: Metadata,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub title: String,
+    pub director: String,
+    pub meta: Metadata,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audiobook {
+    pub title: String,
+    pub meta: Metadata,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Podcast {
+    pub show: String,
+    pub episode: u32,
+    pub title: String,
+    pub meta: Metadata,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Media {
-    Book { title: String, author: String },
-    Movie { title: String, director: String },
-    Audiobook { title: String },
-    Podcast(u32),
+    Book(Book),
+    Movie(Movie),
+    Audiobook(Audiobook),
+    Podcast(Podcast),
+    // Stands for "no media at all", so it has nothing to describe
     Placeholder,
+    // Any other kind of media (see 'MediaItem'): new kinds don't need a new variant.
+    // 'Media' itself isn't a 'MediaItem', so it can't end up in here.
+    Custom(Box<dyn MediaItem>),
 }
 
 impl Media {
-    pub fn description(&self) -> String {
+    // Every kind but the Placeholder, built-in or not, seen the same way
+    pub fn item(&self) -> Option<&dyn MediaItem> {
         match self {
-            Media::Book { title, author } => {
-                format!("Book: {} {}", title, author)
-            }
-            Media::Movie { title, director } => {
-                format!("Movie: {} {}", title, director)
-            }
-            Media::Audiobook { title } => {
-                format!("Audiobook: {}", title)
-            }
-            Media::Podcast(episode_number) => {
-                format!("Podcast: {}", episode_number)
-            }
-            Media::Placeholder => "Placeholder".to_string(),
-            //            _ => String::from("Media description"),
+            Media::Book(book) => Some(book),
+            Media::Movie(movie) => Some(movie),
+            Media::Audiobook(audiobook) => Some(audiobook),
+            Media::Podcast(podcast) => Some(podcast),
+            Media::Placeholder => None,
+            Media::Custom(item) => Some(item.as_ref()),
         }
     }
-}
\ No newline at end of file
+
+    pub fn item_mut(&mut self) -> Option<&mut dyn MediaItem> {
+        match self {
+            Media::Book(book) => Some(book),
+            Media::Movie(movie) => Some(movie),
+            Media::Audiobook(audiobook) => Some(audiobook),
+            Media::Podcast(podcast) => Some(podcast),
+            Media::Placeholder => None,
+            Media::Custom(item) => Some(item.as_mut()),
+        }
+    }
+
+    pub fn kind(&self) -> MediaKind {
+        self.item()
+            .map(|item| item.kind())
+            .unwrap_or(MediaKind::Placeholder)
+    }
+
+    // A Placeholder has an empty title
+    pub fn title(&self) -> &str {
+        self.item().map(|item| item.title()).unwrap_or("")
+    }
+
+    // Author of a book, director of a movie, show of a podcast
+    pub fn creator(&self) -> Option<&str> {
+        self.item().and_then(|item| item.creator())
+    }
+
+    pub fn meta(&self) -> Option<&Metadata> {
+        self.item().and_then(|item| item.meta())
+    }
+
+    pub fn meta_mut(&mut self) -> Option<&mut Metadata> {
+        self.item_mut().and_then(|item| item.meta_mut())
+    }
+
+    pub fn description(&self) -> String {
+        self.item()
+            .map(|item| item.description())
+            .unwrap_or_else(|| "Placeholder".to_string())
+    }
+}
+
+impl MediaItem for Book {
+    fn kind(&self) -> MediaKind {
+        MediaKind::Book
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn creator(&self) -> Option<&str> {
+        Some(&self.author)
+    }
+
+    fn meta(&self) -> Option<&Metadata> {
+        Some(&self.meta)
+    }
+
+    fn meta_mut(&mut self) -> Option<&mut Metadata> {
+        Some(&mut self.meta)
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Book: {}{}{}",
+            self.title,
+            by(&self.author),
+            self.meta.details()
+        )
+    }
+
+    fn clone_item(&self) -> Box<dyn MediaItem> {
+        Box::new(self.clone())
+    }
+}
+
+impl MediaItem for Movie {
+    fn kind(&self) -> MediaKind {
+        MediaKind::Movie
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn creator(&self) -> Option<&str> {
+        Some(&self.director)
+    }
+
+    fn meta(&self) -> Option<&Metadata> {
+        Some(&self.meta)
+    }
+
+    fn meta_mut(&mut self) -> Option<&mut Metadata> {
+        Some(&mut self.meta)
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Movie: {}{}{}",
+            self.title,
+            by(&self.director),
+            self.meta.details()
+        )
+    }
+
+    fn clone_item(&self) -> Box<dyn MediaItem> {
+        Box::new(self.clone())
+    }
+}
+
+impl MediaItem for Audiobook {
+    fn kind(&self) -> MediaKind {
+        MediaKind::Audiobook
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn meta(&self) -> Option<&Metadata> {
+        Some(&self.meta)
+    }
+
+    fn meta_mut(&mut self) -> Option<&mut Metadata> {
+        Some(&mut self.meta)
+    }
+
+    fn description(&self) -> String {
+        format!("Audiobook: {}{}", self.title, self.meta.details())
+    }
+
+    fn clone_item(&self) -> Box<dyn MediaItem> {
+        Box::new(self.clone())
+    }
+}
+
+impl MediaItem for Podcast {
+    fn kind(&self) -> MediaKind {
+        MediaKind::Podcast
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn creator(&self) -> Option<&str> {
+        Some(&self.show)
+    }
+
+    fn meta(&self) -> Option<&Metadata> {
+        Some(&self.meta)
+    }
+
+    fn meta_mut(&mut self) -> Option<&mut Metadata> {
+        Some(&mut self.meta)
+    }
+
+    fn description(&self) -> String {
+        // Episode 0 stands for the whole show (a subscription)
+        if self.episode == 0 && self.title.is_empty() {
+            return format!("Podcast: {}{}", self.show, self.meta.details());
+        }
+        format!(
+            "Podcast: {} #{}: {}{}",
+            self.show,
+            self.episode,
+            self.title,
+            self.meta.details()
+        )
+    }
+
+    fn fields(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([(String::from("episode"), self.episode.to_string())])
+    }
+
+    fn clone_item(&self) -> Box<dyn MediaItem> {
+        Box::new(self.clone())
+    }
+}
+
+// " by J.R.R. Tolkien", or nothing when the creator isn't known
+fn by(creator: &str) -> String {
+    if creator.is_empty() {
+        String::new()
+    } else {
+        format!(" by {}", creator)
+    }
+}
@@ -1,2 +1,14 @@
 pub mod catalog;
-pub mod media;
\ No newline at end of file
+pub mod date;
+pub mod dedup;
+pub mod item;
+pub mod lending;
+pub mod media;
+pub mod page;
+pub mod progress;
+pub mod query;
+pub mod recommend;
+pub mod search;
+pub mod series;
+pub mod stats;
+pub mod tags;
@@ -0,0 +1,188 @@
+use std::error::Error;
+use std::fmt;
+
+use super::catalog::{Catalog, MediaId};
+use super::media::Media;
+use super::query::{Key, SortBy, sort_key};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageError {
+    // At least one item per page
+    ZeroLimit,
+    // Not a cursor given by 'page'
+    InvalidCursor(String),
+    // A cursor only goes with the order it was made for
+    SortMismatch { cursor: SortBy, requested: SortBy },
+}
+
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PageError::ZeroLimit => write!(f, "a page holds at least one item"),
+            PageError::InvalidCursor(text) => write!(f, "'{}' isn't a valid cursor", text),
+            PageError::SortMismatch { cursor, requested } => write!(
+                f,
+                "the cursor is for items sorted by {:?}, not by {:?}",
+                cursor, requested
+            ),
+        }
+    }
+}
+
+impl Error for PageError {}
+
+// Where the next page starts. It remembers the sort key and the id of the last item
+// that was given, not its position: items added or removed in between don't make
+// the next page skip or repeat any item.
+// It's meant to be passed around as text ("title.12.t6875..."), not read or built by hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    sort: SortBy,
+    // None for an item without the field: those come last
+    key: Option<Key>,
+    after: MediaId,
+}
+
+#[derive(Debug)]
+pub struct Page<'a> {
+    pub items: Vec<(MediaId, &'a Media)>,
+    // None on the last page
+    pub next: Option<Cursor>,
+}
+
+const SORTS: [(SortBy, &str); 7] = [
+    (SortBy::Id, "id"),
+    (SortBy::Kind, "kind"),
+    (SortBy::Title, "title"),
+    (SortBy::Creator, "creator"),
+    (SortBy::Year, "year"),
+    (SortBy::Length, "length"),
+    (SortBy::Rating, "rating"),
+];
+
+// "title.12.t6875626f", "year.3.n1937", "kind.4.k0book", "rating.8.-".
+// Text is written in hexadecimal, so that the cursor fits in a URL as it is.
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sort = SORTS
+            .iter()
+            .find(|(sort, _)| *sort == self.sort)
+            .map(|(_, name)| *name)
+            .unwrap_or("id");
+        write!(f, "{}.{}.", sort, self.after.0)?;
+        match &self.key {
+            None => write!(f, "-"),
+            Some(Key::Number(number)) => write!(f, "n{}", number),
+            Some(Key::Text(text)) => write!(f, "t{}", hex(text)),
+            Some(Key::Kind(rank, name)) => write!(f, "k{}{}", rank, hex(name)),
+        }
+    }
+}
+
+fn hex(text: &str) -> String {
+    text.bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<String> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    let bytes: Option<Vec<u8>> = (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect();
+    String::from_utf8(bytes?).ok()
+}
+
+impl Cursor {
+    // The text given by Display, back to a cursor
+    pub fn parse(text: &str) -> Result<Cursor, PageError> {
+        let invalid = || PageError::InvalidCursor(text.to_string());
+        let mut parts = text.splitn(3, '.');
+        let (Some(sort), Some(after), Some(key)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        let sort = SORTS
+            .iter()
+            .find(|(_, name)| *name == sort)
+            .map(|(sort, _)| *sort)
+            .ok_or_else(invalid)?;
+        let after = MediaId(after.parse().map_err(|_| invalid())?);
+        let key = match key.split_at_checked(1) {
+            Some(("-", "")) => None,
+            Some(("n", number)) => Some(Key::Number(number.parse().map_err(|_| invalid())?)),
+            Some(("t", text)) => Some(Key::Text(unhex(text).ok_or_else(invalid)?)),
+            Some(("k", kind)) => {
+                let (rank, name) = kind.split_at_checked(1).ok_or_else(invalid)?;
+                let rank = rank.parse().map_err(|_| invalid())?;
+                Some(Key::Kind(rank, unhex(name).ok_or_else(invalid)?))
+            }
+            _ => return Err(invalid()),
+        };
+
+        Ok(Cursor { sort, key, after })
+    }
+}
+
+// Items without the field come last; items with the same key, by id
+type Position = (bool, Option<Key>, MediaId);
+
+fn position(key: Option<Key>, id: MediaId) -> Position {
+    (key.is_none(), key, id)
+}
+
+impl Catalog {
+    // Up to 'limit' items sorted by 'sort' (ascending), starting after 'cursor' (or at
+    // the beginning without one). Pass the 'next' cursor of a page to get the one after.
+    pub fn page(
+        &self,
+        cursor: Option<&Cursor>,
+        limit: usize,
+        sort: SortBy,
+    ) -> Result<Page<'_>, PageError> {
+        if limit == 0 {
+            return Err(PageError::ZeroLimit);
+        }
+        let start = match cursor {
+            Some(cursor) if cursor.sort != sort => {
+                return Err(PageError::SortMismatch {
+                    cursor: cursor.sort,
+                    requested: sort,
+                });
+            }
+            Some(cursor) => Some(position(cursor.key.clone(), cursor.after)),
+            None => None,
+        };
+
+        let mut items: Vec<(Position, MediaId, &Media)> = self
+            .entries()
+            .map(|(id, media)| (position(sort_key(id, media, sort), id), id, media))
+            .filter(|(position, _, _)| start.as_ref().is_none_or(|start| position > start))
+            .collect();
+        // Only the smallest ones are needed: no need to sort all the others
+        if items.len() > limit {
+            items.select_nth_unstable_by(limit, |a, b| a.0.cmp(&b.0));
+        }
+        let more = items.len() > limit;
+        items.truncate(limit);
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let next = match items.last() {
+            Some(((_, key, _), id, _)) if more => Some(Cursor {
+                sort,
+                key: key.clone(),
+                after: *id,
+            }),
+            _ => None,
+        };
+        Ok(Page {
+            items: items
+                .into_iter()
+                .map(|(_, id, media)| (id, media))
+                .collect(),
+            next,
+        })
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use super::catalog::{Catalog, MediaId};
+use super::date::Date;
+use super::media::{Length, Media, MediaKind};
+
+// How far someone got through an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    // Books
+    Pages(u32),
+    // Movies, audiobooks and podcasts: where the playback stopped
+    Minutes(u32),
+    // Any item, even when we don't know its length (a podcast that was listened to...)
+    Finished,
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Progress::Pages(pages) => write!(f, "{} pages read", pages),
+            Progress::Minutes(minutes) => write!(f, "stopped at {} min", minutes),
+            Progress::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressError {
+    UnknownItem(MediaId),
+    // There's nothing to read or watch in a Placeholder
+    NotTrackable(MediaId),
+    // Pages of a movie, minutes of a book
+    WrongUnit {
+        id: MediaId,
+        kind: MediaKind,
+        progress: Progress,
+    },
+}
+
+impl fmt::Display for ProgressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgressError::UnknownItem(id) => write!(f, "no item with id {}", id),
+            ProgressError::NotTrackable(id) => write!(f, "item {} is a placeholder", id),
+            ProgressError::WrongUnit { id, kind, progress } => {
+                write!(f, "item {} is a {}: it can't be '{}'", id, kind, progress)
+            }
+        }
+    }
+}
+
+impl Error for ProgressError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgressUpdate {
+    pub user: String,
+    pub id: MediaId,
+    pub progress: Progress,
+    pub on: Date,
+}
+
+// Every update ever made, in order: the last one of a user for an item is where they are.
+// Keeping them all gives the history for free.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProgressLog {
+    updates: Vec<ProgressUpdate>,
+    // (user, item) -> index of their last update
+    latest: HashMap<(String, MediaId), usize>,
+}
+
+impl ProgressLog {
+    pub fn new() -> Self {
+        ProgressLog::default()
+    }
+
+    // The item is gone: so is everyone's progress on it
+    pub fn forget(&mut self, id: MediaId) {
+        self.updates.retain(|update| update.id != id);
+        self.rebuild_latest();
+    }
+
+    // 'from' was merged into 'to': its updates are now about 'to' (the most recent
+    // update of a user, whichever item it was made on, is where they are)
+    pub fn move_to(&mut self, from: MediaId, to: MediaId) {
+        for update in self.updates.iter_mut().filter(|update| update.id == from) {
+            update.id = to;
+        }
+        self.rebuild_latest();
+    }
+
+    fn rebuild_latest(&mut self) {
+        self.latest = self
+            .updates
+            .iter()
+            .enumerate()
+            .map(|(index, update)| ((update.user.clone(), update.id), index))
+            .collect();
+    }
+
+    fn push(&mut self, update: ProgressUpdate) {
+        self.latest
+            .insert((update.user.clone(), update.id), self.updates.len());
+        self.updates.push(update);
+    }
+
+    fn latest(&self, user: &str, id: MediaId) -> Option<&ProgressUpdate> {
+        let index = self.latest.get(&(user.to_string(), id))?;
+        Some(&self.updates[*index])
+    }
+
+    // The current progress of 'user' on every item they started, the most recent first
+    fn current(&self, user: &str) -> Vec<&ProgressUpdate> {
+        let mut indexes: Vec<usize> = self
+            .latest
+            .iter()
+            .filter(|((name, _), _)| name == user)
+            .map(|(_, index)| *index)
+            .collect();
+        indexes.sort_by_key(|index| (self.updates[*index].on, *index));
+        indexes
+            .iter()
+            .rev()
+            .map(|index| &self.updates[*index])
+            .collect()
+    }
+}
+
+// From 0.0 to 100.0, when the length of the item is known
+fn completion(media: &Media, progress: Progress) -> Option<f64> {
+    let percent = |done: u32, total: u32| 100.0 * done.min(total) as f64 / total.max(1) as f64;
+    match (progress, media.meta()?.length?) {
+        (Progress::Finished, _) => Some(100.0),
+        (Progress::Pages(pages), Length::Pages(total)) => Some(percent(pages, total)),
+        (Progress::Minutes(minutes), Length::Minutes(total)) => Some(percent(minutes, total)),
+        _ => None,
+    }
+}
+
+impl Catalog {
+    // Where 'user' got in the item. Going past the end counts as finishing it.
+    pub fn record_progress(
+        &mut self,
+        user: &str,
+        id: MediaId,
+        progress: Progress,
+        on: Date,
+    ) -> Result<(), ProgressError> {
+        let media = self.get(id).map_err(|_| ProgressError::UnknownItem(id))?;
+        let valid = match (media, progress) {
+            (Media::Placeholder, _) => return Err(ProgressError::NotTrackable(id)),
+            (_, Progress::Finished) => true,
+            (Media::Book(_), Progress::Pages(_)) => true,
+            (Media::Movie(_) | Media::Audiobook(_) | Media::Podcast(_), Progress::Minutes(_)) => {
+                true
+            }
+            // Other kinds are tracked in the unit of their length
+            (Media::Custom(_), progress) => matches!(
+                (media.meta().and_then(|meta| meta.length), progress),
+                (Some(Length::Pages(_)), Progress::Pages(_))
+                    | (Some(Length::Minutes(_)), Progress::Minutes(_))
+            ),
+            _ => false,
+        };
+        if !valid {
+            return Err(ProgressError::WrongUnit {
+                id,
+                kind: media.kind(),
+                progress,
+            });
+        }
+
+        let progress = if completion(media, progress) == Some(100.0) {
+            Progress::Finished
+        } else {
+            progress
+        };
+        self.progress_log_mut().push(ProgressUpdate {
+            user: user.to_string(),
+            id,
+            progress,
+            on,
+        });
+        Ok(())
+    }
+
+    // Every update of every user, in the order they were made (that's how they're saved)
+    pub fn progress_updates(&self) -> &[ProgressUpdate] {
+        &self.progress_log().updates
+    }
+
+    pub fn finish(&mut self, user: &str, id: MediaId, on: Date) -> Result<(), ProgressError> {
+        self.record_progress(user, id, Progress::Finished, on)
+    }
+
+    pub fn progress(&self, user: &str, id: MediaId) -> Option<&ProgressUpdate> {
+        self.progress_log().latest(user, id)
+    }
+
+    // How much of the item 'user' went through, in percent.
+    // None when they haven't started it, or when we don't know how long it is.
+    pub fn completion(&self, user: &str, id: MediaId) -> Option<f64> {
+        let update = self.progress(user, id)?;
+        completion(self.get(id).ok()?, update.progress)
+    }
+
+    // What 'user' started and hasn't finished yet, the most recent first
+    pub fn continue_watching(&self, user: &str) -> Vec<(&ProgressUpdate, &Media)> {
+        self.progress_log()
+            .current(user)
+            .into_iter()
+            .filter(|update| match update.progress {
+                Progress::Pages(done) | Progress::Minutes(done) => done > 0,
+                Progress::Finished => false,
+            })
+            .filter_map(|update| Some((update, self.get(update.id).ok()?)))
+            .collect()
+    }
+
+    // Every update 'user' made, the most recent first
+    pub fn history(&self, user: &str) -> Vec<&ProgressUpdate> {
+        let mut history: Vec<&ProgressUpdate> = self
+            .progress_updates()
+            .iter()
+            .filter(|update| update.user == user)
+            .collect();
+        // 'sort_by_key' keeps the order of updates made on the same day:
+        // once reversed, the last one comes first
+        history.sort_by_key(|update| update.on);
+        history.reverse();
+        history
+    }
+
+    // Everything 'user' finished (see 'next_unwatched' for series)
+    pub fn finished(&self, user: &str) -> HashSet<MediaId> {
+        self.progress_log()
+            .current(user)
+            .into_iter()
+            .filter(|update| update.progress == Progress::Finished)
+            .map(|update| update.id)
+            .collect()
+    }
+}
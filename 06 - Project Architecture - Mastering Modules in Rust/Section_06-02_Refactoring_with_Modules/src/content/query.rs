@@ -0,0 +1,266 @@
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+use super::catalog::{Catalog, MediaId};
+use super::media::{Length, Media, MediaKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Id,
+    Kind,
+    Title,
+    Creator,
+    Year,
+    Length,
+    Rating,
+}
+
+// Builds a search over a Catalog, step by step:
+//   catalog.query().kind(MediaKind::Book).genre("fantasy").sort_by(SortBy::Year).run()
+// Every filter is optional; they all have to match. Text is compared case-insensitively.
+// The results borrow the items of the catalog, nothing gets cloned.
+#[derive(Debug)]
+pub struct Query<'a> {
+    catalog: &'a Catalog,
+    kinds: Vec<MediaKind>,
+    creator: Option<String>,
+    years: Option<RangeInclusive<u16>>,
+    genre: Option<String>,
+    title: Option<String>,
+    // Title to look for, and how many typos are tolerated
+    fuzzy: Option<(String, usize)>,
+    sort: Option<SortBy>,
+    descending: bool,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(catalog: &'a Catalog) -> Self {
+        Query {
+            catalog,
+            kinds: vec![],
+            creator: None,
+            years: None,
+            genre: None,
+            title: None,
+            fuzzy: None,
+            sort: None,
+            descending: false,
+        }
+    }
+
+    // Can be called several times: "books or audiobooks"
+    pub fn kind(mut self, kind: MediaKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    // Author or director (or show, for a podcast) containing 'creator'
+    pub fn creator(mut self, creator: &str) -> Self {
+        self.creator = Some(creator.to_lowercase());
+        self
+    }
+
+    // Items without a year never match
+    pub fn years(mut self, years: RangeInclusive<u16>) -> Self {
+        self.years = Some(years);
+        self
+    }
+
+    pub fn genre(mut self, genre: &str) -> Self {
+        self.genre = Some(genre.to_lowercase());
+        self
+    }
+
+    // Title containing 'text'
+    pub fn title(mut self, text: &str) -> Self {
+        self.title = Some(text.to_lowercase());
+        self
+    }
+
+    // Title close to 'text' (or one of its words is), with at most 'max_typos'
+    // letters added, removed or changed. Unless sorted otherwise, the closest come first.
+    pub fn fuzzy_title(mut self, text: &str, max_typos: usize) -> Self {
+        self.fuzzy = Some((text.to_lowercase(), max_typos));
+        self
+    }
+
+    pub fn sort_by(mut self, sort: SortBy) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    // Only changes the order of 'sort_by'
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+
+    pub fn run(self) -> impl Iterator<Item = &'a Media> {
+        self.entries().map(|(_, media)| media)
+    }
+
+    // Same as 'run', with the id of every item
+    pub fn entries(self) -> impl Iterator<Item = (MediaId, &'a Media)> {
+        let mut results: Vec<(usize, MediaId, &'a Media)> = self
+            .catalog
+            .entries()
+            .filter(|(_, media)| self.matches(media))
+            .map(|(id, media)| (self.typos(media), id, media))
+            .collect();
+
+        // 'sort_by' is stable: equal items keep the order of the catalog
+        match self.sort {
+            Some(sort) => results.sort_by(|(_, id_a, a), (_, id_b, b)| {
+                compare((*id_a, a), (*id_b, b), sort, self.descending)
+            }),
+            None => results.sort_by_key(|(typos, _, _)| *typos),
+        }
+
+        results.into_iter().map(|(_, id, media)| (id, media))
+    }
+
+    fn matches(&self, media: &Media) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&media.kind()) {
+            return false;
+        }
+
+        if let Some(creator) = &self.creator {
+            let found = media
+                .creator()
+                .is_some_and(|name| name.to_lowercase().contains(creator));
+            if !found {
+                return false;
+            }
+        }
+
+        let meta = media.meta();
+        if let Some(years) = &self.years {
+            let year = meta.and_then(|meta| meta.year);
+            if !year.is_some_and(|year| years.contains(&year)) {
+                return false;
+            }
+        }
+
+        if let Some(genre) = &self.genre {
+            let found = meta.is_some_and(|meta| {
+                meta.genres
+                    .iter()
+                    .any(|candidate| candidate.to_lowercase() == *genre)
+            });
+            if !found {
+                return false;
+            }
+        }
+
+        if let Some(text) = &self.title
+            && !media.title().to_lowercase().contains(text)
+        {
+            return false;
+        }
+
+        match &self.fuzzy {
+            Some((_, max_typos)) => self.typos(media) <= *max_typos,
+            None => true,
+        }
+    }
+
+    // How far the title is from the fuzzy search (0 without a fuzzy search)
+    fn typos(&self, media: &Media) -> usize {
+        let Some((text, _)) = &self.fuzzy else {
+            return 0;
+        };
+
+        let title = media.title().to_lowercase();
+        let whole = edit_distance(text, &title);
+        let best_word = title
+            .split_whitespace()
+            .map(|word| edit_distance(text, word))
+            .min()
+            .unwrap_or(whole);
+
+        whole.min(best_word)
+    }
+}
+
+// Items without the field go last, in both directions
+fn compare(
+    (id_a, a): (MediaId, &Media),
+    (id_b, b): (MediaId, &Media),
+    sort: SortBy,
+    descending: bool,
+) -> Ordering {
+    match (sort_key(id_a, a, sort), sort_key(id_b, b, sort)) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+// What the item gets sorted on: None when it doesn't have the field
+pub(super) fn sort_key(id: MediaId, media: &Media, sort: SortBy) -> Option<Key> {
+    let meta = media.meta();
+    match sort {
+        SortBy::Id => Some(Key::Number(id.0)),
+        SortBy::Kind => Some(kind_key(media.kind())),
+        SortBy::Title => Some(Key::Text(media.title().to_lowercase())),
+        SortBy::Creator => media.creator().map(|name| Key::Text(name.to_lowercase())),
+        SortBy::Year => meta
+            .and_then(|m| m.year)
+            .map(|year| Key::Number(year as u64)),
+        SortBy::Length => meta.and_then(|m| m.length).map(length_key),
+        SortBy::Rating => meta.and_then(|m| m.rating).map(|r| Key::Number(r as u64)),
+    }
+}
+
+// What the items get sorted on
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum Key {
+    Number(u64),
+    Text(String),
+    // The rank of the kind, then its name: see 'kind_key'
+    Kind(u8, String),
+}
+
+// In the order of the variants of MediaKind, custom kinds last (by name)
+fn kind_key(kind: MediaKind) -> Key {
+    let rank = match kind {
+        MediaKind::Book => 0,
+        MediaKind::Movie => 1,
+        MediaKind::Audiobook => 2,
+        MediaKind::Podcast => 3,
+        MediaKind::Placeholder => 4,
+        MediaKind::Custom(_) => 5,
+    };
+    Key::Kind(rank, kind.to_string().to_lowercase())
+}
+
+// Pages and minutes can't be compared: books come before everything else
+fn length_key(length: Length) -> Key {
+    match length {
+        Length::Pages(pages) => Key::Number(pages as u64),
+        Length::Minutes(minutes) => Key::Number((1 << 32) + minutes as u64),
+    }
+}
+
+// Levenshtein distance: the number of letters to add, remove or change to go from 'a' to 'b'
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Only the previous row of the matrix is needed
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            let value = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+            current.push(value);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
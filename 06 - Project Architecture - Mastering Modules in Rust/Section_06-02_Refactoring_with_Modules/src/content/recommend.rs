@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+
+use super::catalog::{Catalog, MediaId};
+use super::dedup::normalize_creator;
+use super::media::{Media, MediaKind};
+use super::progress::Progress;
+
+// How much each way of finding items counts in the final score
+const CONTENT_WEIGHT: f64 = 0.6;
+const COLLABORATIVE_WEIGHT: f64 = 0.4;
+
+// How much a shared creator and shared genres make two items alike
+const SAME_CREATOR: f64 = 0.6;
+const SAME_GENRES: f64 = 0.4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RatingError {
+    UnknownItem(MediaId),
+    NotRateable(MediaId),
+    // From 1 to 5
+    InvalidStars(u8),
+}
+
+impl fmt::Display for RatingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RatingError::UnknownItem(id) => write!(f, "no item with id {}", id),
+            RatingError::NotRateable(id) => write!(f, "item {} is a placeholder", id),
+            RatingError::InvalidStars(stars) => {
+                write!(f, "a rating goes from 1 to 5 stars, not {}", stars)
+            }
+        }
+    }
+}
+
+impl Error for RatingError {}
+
+// What each user thinks of the items they rated (unlike 'Metadata::rating', which is
+// the same for everyone). A BTreeMap keeps them in order, so recommendations don't change
+// from one run to the next.
+#[derive(Debug, Default, PartialEq)]
+pub struct UserRatings {
+    ratings: BTreeMap<(String, MediaId), u8>,
+}
+
+impl UserRatings {
+    pub fn new() -> Self {
+        UserRatings::default()
+    }
+
+    // The item is gone: so are its ratings
+    pub fn forget(&mut self, id: MediaId) {
+        self.ratings.retain(|(_, rated), _| *rated != id);
+    }
+
+    // 'from' was merged into 'to': a user who only rated 'from' keeps their rating
+    pub fn move_to(&mut self, from: MediaId, to: MediaId) {
+        let moved: Vec<(String, u8)> = self
+            .ratings
+            .iter()
+            .filter(|((_, rated), _)| *rated == from)
+            .map(|((user, _), stars)| (user.clone(), *stars))
+            .collect();
+        self.forget(from);
+        for (user, stars) in moved {
+            self.ratings.entry((user, to)).or_insert(stars);
+        }
+    }
+}
+
+// Why an item was recommended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    SameCreator { liked: MediaId, creator: String },
+    SameGenre { liked: MediaId, genre: String },
+    // People who liked 'liked' also liked the item
+    AlsoLiked { liked: MediaId },
+    // When nothing the user liked leads to it
+    Popular { fans: usize, rating: Option<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    pub id: MediaId,
+    pub score: f64,
+    pub reason: Reason,
+    // The reason, in words: "because you liked The Hobbit (same author: J.R.R. Tolkien)"
+    pub explanation: String,
+}
+
+// How much a user likes the items they've been through: positive when they liked it,
+// negative when they didn't. Their rating says it best; without one, finishing an item
+// says more than starting it.
+type Affinities = BTreeMap<String, BTreeMap<MediaId, f64>>;
+
+// The genres of an item, lowercase
+fn genres(media: &Media) -> BTreeSet<String> {
+    media
+        .meta()
+        .map(|meta| {
+            meta.genres
+                .iter()
+                .map(|genre| genre.to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// How alike two items are (from 0.0 to 1.0) going by their content, and the main reason
+fn content_similarity(liked_id: MediaId, liked: &Media, other: &Media) -> Option<(f64, Reason)> {
+    let mut score = 0.0;
+    let mut reason = None;
+
+    if let (Some(a), Some(b)) = (liked.creator(), other.creator()) {
+        let a = normalize_creator(a);
+        if !a.is_empty() && a == normalize_creator(b) {
+            score += SAME_CREATOR;
+            reason = Some(Reason::SameCreator {
+                liked: liked_id,
+                creator: b.to_string(),
+            });
+        }
+    }
+
+    let (a, b) = (genres(liked), genres(other));
+    let shared: Vec<&String> = a.intersection(&b).collect();
+    if let Some(genre) = shared.first() {
+        // Jaccard index: the shared genres, out of all the genres of both
+        score += SAME_GENRES * shared.len() as f64 / a.union(&b).count() as f64;
+        reason.get_or_insert(Reason::SameGenre {
+            liked: liked_id,
+            genre: genre.to_string(),
+        });
+    }
+
+    reason.map(|reason| (score, reason))
+}
+
+// Cosine similarity of what everyone (but 'user') thinks of the two items
+fn collaborative_similarity(affinities: &Affinities, user: &str, a: MediaId, b: MediaId) -> f64 {
+    let (mut product, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+    for (name, items) in affinities {
+        if name == user {
+            continue;
+        }
+        let x = items.get(&a).copied().unwrap_or(0.0);
+        let y = items.get(&b).copied().unwrap_or(0.0);
+        product += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    product / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+impl Catalog {
+    // From 1 to 5 stars, for 'user' only. Rating it again replaces the previous rating.
+    pub fn rate(&mut self, user: &str, id: MediaId, stars: u8) -> Result<(), RatingError> {
+        match self.get(id) {
+            Err(_) => return Err(RatingError::UnknownItem(id)),
+            Ok(Media::Placeholder) => return Err(RatingError::NotRateable(id)),
+            Ok(_) => {}
+        }
+        if !(1..=5).contains(&stars) {
+            return Err(RatingError::InvalidStars(stars));
+        }
+
+        self.user_ratings_mut()
+            .ratings
+            .insert((user.to_string(), id), stars);
+        Ok(())
+    }
+
+    pub fn user_rating(&self, user: &str, id: MediaId) -> Option<u8> {
+        self.user_ratings()
+            .ratings
+            .get(&(user.to_string(), id))
+            .copied()
+    }
+
+    // Every rating of every user, by user then item (that's how they're saved)
+    pub fn all_ratings(&self) -> Vec<(&str, MediaId, u8)> {
+        self.user_ratings()
+            .ratings
+            .iter()
+            .map(|((user, id), stars)| (user.as_str(), *id, *stars))
+            .collect()
+    }
+
+    fn affinities(&self) -> Affinities {
+        let mut affinities = Affinities::new();
+        // The last update of each user for each item wins
+        for update in self.progress_updates() {
+            let affinity = match update.progress {
+                Progress::Finished => 1.0,
+                Progress::Pages(_) | Progress::Minutes(_) => 0.5,
+            };
+            affinities
+                .entry(update.user.clone())
+                .or_default()
+                .insert(update.id, affinity);
+        }
+        // 3 stars is "neither good nor bad"
+        for ((user, id), stars) in &self.user_ratings().ratings {
+            affinities
+                .entry(user.clone())
+                .or_default()
+                .insert(*id, *stars as f64 - 3.0);
+        }
+        affinities
+    }
+
+    // Up to 'n' items 'user' hasn't been through yet, the best first. Combines items
+    // alike the ones they liked (content) and items liked by the people who liked
+    // the same ones (collaborative). The same data always gives the same recommendations.
+    pub fn recommend(&self, user: &str, n: usize) -> Vec<Recommendation> {
+        let affinities = self.affinities();
+        let empty = BTreeMap::new();
+        let mine = affinities.get(user).unwrap_or(&empty);
+
+        let candidates: Vec<(MediaId, &Media)> = self
+            .entries()
+            .filter(|(id, media)| !matches!(media, Media::Placeholder) && !mine.contains_key(id))
+            .collect();
+
+        let mut recommendations = vec![];
+        let mut others = vec![];
+        for (id, media) in candidates {
+            let mut score = 0.0;
+            // The reason that counted the most
+            let mut best: Option<(f64, Reason)> = None;
+
+            for (liked_id, affinity) in mine {
+                let Ok(liked) = self.get(*liked_id) else {
+                    continue;
+                };
+                let mut contributions = vec![];
+                if let Some((similarity, reason)) = content_similarity(*liked_id, liked, media) {
+                    contributions.push((CONTENT_WEIGHT * similarity * affinity, reason));
+                }
+                let similarity = collaborative_similarity(&affinities, user, *liked_id, id);
+                if similarity > 0.0 {
+                    contributions.push((
+                        COLLABORATIVE_WEIGHT * similarity * affinity,
+                        Reason::AlsoLiked { liked: *liked_id },
+                    ));
+                }
+
+                for (contribution, reason) in contributions {
+                    score += contribution;
+                    // On a tie, the first one (the lowest id) stays
+                    if best.as_ref().is_none_or(|(top, _)| contribution > *top) {
+                        best = Some((contribution, reason));
+                    }
+                }
+            }
+
+            match best {
+                Some((contribution, reason)) if score > 0.0 && contribution > 0.0 => {
+                    recommendations.push(self.recommendation(id, score, reason))
+                }
+                // Nothing they liked leads there: only suggested when there's nothing better
+                _ if score >= 0.0 => others.push((id, media)),
+                _ => {}
+            }
+        }
+
+        recommendations.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        if recommendations.len() < n {
+            recommendations.extend(self.popular(&affinities, user, others));
+        }
+        recommendations.truncate(n);
+        recommendations
+    }
+
+    // The items liked by the most people, then the best rated ones
+    fn popular(
+        &self,
+        affinities: &Affinities,
+        user: &str,
+        items: Vec<(MediaId, &Media)>,
+    ) -> Vec<Recommendation> {
+        let mut popular: Vec<(usize, Option<u8>, MediaId)> = items
+            .iter()
+            .map(|(id, media)| {
+                let fans = affinities
+                    .iter()
+                    .filter(|(name, items)| {
+                        *name != user && items.get(id).is_some_and(|a| *a > 0.0)
+                    })
+                    .count();
+                (fans, media.meta().and_then(|meta| meta.rating), *id)
+            })
+            .collect();
+        popular.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+
+        popular
+            .into_iter()
+            .map(|(fans, rating, id)| {
+                // Always below the personal recommendations (whose score is above 0)
+                self.recommendation(id, 0.0, Reason::Popular { fans, rating })
+            })
+            .collect()
+    }
+
+    fn recommendation(&self, id: MediaId, score: f64, reason: Reason) -> Recommendation {
+        let title = |id: MediaId| {
+            self.get(id)
+                .map(|media| media.title().to_string())
+                .unwrap_or_default()
+        };
+        let creator = match self.get(id).map(|media| media.kind()) {
+            Ok(MediaKind::Movie) => "director",
+            Ok(MediaKind::Podcast) => "show",
+            Ok(MediaKind::Custom(_)) => "creator",
+            _ => "author",
+        };
+
+        let explanation = match &reason {
+            Reason::SameCreator {
+                liked,
+                creator: name,
+            } => format!(
+                "because you liked {} (same {}: {})",
+                title(*liked),
+                creator,
+                name
+            ),
+            Reason::SameGenre { liked, genre } => {
+                format!("because you liked {} (also {})", title(*liked), genre)
+            }
+            Reason::AlsoLiked { liked } => {
+                format!("people who liked {} also liked it", title(*liked))
+            }
+            Reason::Popular { fans, rating } => match (fans, rating) {
+                (0, Some(rating)) => format!("rated {}/5", rating),
+                (0, None) => String::from("something new to try"),
+                (1, _) => String::from("liked by 1 person"),
+                (fans, _) => format!("liked by {} people", fans),
+            },
+        };
+
+        Recommendation {
+            id,
+            score,
+            reason,
+            explanation,
+        }
+    }
+}
@@ -0,0 +1,558 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use super::catalog::{Catalog, MediaId};
+use super::media::Media;
+
+// BM25 parameters: how fast repeating a word stops counting (K1),
+// and how much long titles are penalized (B). These are the usual values.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+// Separates the title from the creator, so that a phrase can't start in one and end in the other
+const FIELD_BREAK: &str = "";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchError {
+    Empty,
+    UnterminatedPhrase,
+    UnbalancedParenthesis,
+    // AND, OR or NOT with nothing to apply to
+    MissingOperand(String),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::Empty => write!(f, "nothing to search for"),
+            SearchError::UnterminatedPhrase => write!(f, "a phrase is missing its closing quote"),
+            SearchError::UnbalancedParenthesis => write!(f, "unbalanced parenthesis"),
+            SearchError::MissingOperand(operator) => {
+                write!(f, "{} needs something to search for", operator)
+            }
+        }
+    }
+}
+
+impl Error for SearchError {}
+
+// An item matching the search. The higher the score, the better it matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub id: MediaId,
+    pub score: f64,
+}
+
+// Lowercase words, punctuation removed
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect()
+}
+
+// A light version of Porter's stemmer: "movies", "movie" -> "movi", "stories", "story" -> "stori",
+// "running" -> "runn". The stems aren't always words, but the same stem means the same word.
+pub fn stem(word: &str) -> String {
+    let mut word = word.to_string();
+    // Short words are left alone: "is", "bus", "red"...
+    let long_enough = |word: &str, suffix: &str| word.len() >= suffix.len() + 3;
+
+    // "classes" -> "class", "movies" -> "movi"
+    if word.ends_with("sses") || (word.ends_with("ies") && long_enough(&word, "ies")) {
+        word.truncate(word.len() - 2);
+    } else if word.ends_with('s') && !word.ends_with("ss") && long_enough(&word, "s") {
+        word.pop();
+    }
+
+    for suffix in ["ing", "ed", "ly"] {
+        if word.ends_with(suffix) && long_enough(&word, suffix) {
+            word.truncate(word.len() - suffix.len());
+            break;
+        }
+    }
+
+    if word.ends_with('e') && long_enough(&word, "e") {
+        word.pop();
+    } else if word.ends_with('y') && long_enough(&word, "y") {
+        word.pop();
+        word.push('i');
+    }
+
+    word
+}
+
+// What gets indexed: the stemmed words of the title, then those of the creator
+fn document(media: &Media) -> Vec<String> {
+    let mut words: Vec<String> = tokenize(media.title()).iter().map(|w| stem(w)).collect();
+    if let Some(creator) = media.creator() {
+        words.push(FIELD_BREAK.to_string());
+        words.extend(tokenize(creator).iter().map(|w| stem(w)));
+    }
+    words
+}
+
+fn length(words: &[String]) -> usize {
+    words.iter().filter(|word| *word != FIELD_BREAK).count()
+}
+
+fn contains_phrase(words: &[String], phrase: &[String]) -> bool {
+    words.windows(phrase.len()).any(|window| window == phrase)
+}
+
+// How well a document matches one word:
+// the more often the word appears in it, and the rarer the word is elsewhere, the better.
+fn bm25(frequency: usize, documents_with_word: usize, stats: &Stats) -> f64 {
+    let documents = stats.documents as f64;
+    let with_word = documents_with_word as f64;
+    let idf = (1.0 + (documents - with_word + 0.5) / (with_word + 0.5)).ln();
+
+    let frequency = frequency as f64;
+    let relative_length = stats.length as f64 / stats.average_length;
+    idf * frequency * (K1 + 1.0) / (frequency + K1 * (1.0 - B + B * relative_length))
+}
+
+// What BM25 needs to know about the whole catalog, and about one document
+struct Stats {
+    documents: usize,
+    average_length: f64,
+    length: usize,
+}
+
+// A parsed search:
+//   tolkien hobbit          both words (AND is implied)
+//   hobbit OR rings         either word
+//   hobbit NOT movie        or '-movie'
+//   "lord of the rings"     the words next to each other, in that order
+//   (hobbit OR rings) AND tolkien
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Word(String),
+    Phrase(Vec<String>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    fn parse(text: &str) -> Result<Expr, SearchError> {
+        let tokens = lex(text)?;
+        if tokens.is_empty() {
+            return Err(SearchError::Empty);
+        }
+
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or()?;
+        if parser.next < parser.tokens.len() {
+            // Only a ')' can stop the parser early
+            return Err(SearchError::UnbalancedParenthesis);
+        }
+        Ok(expr)
+    }
+
+    // The linear way: does this list of words match?
+    fn matches(&self, words: &[String]) -> bool {
+        match self {
+            Expr::Word(word) => words.contains(word),
+            Expr::Phrase(phrase) => contains_phrase(words, phrase),
+            Expr::And(parts) => parts.iter().all(|part| part.matches(words)),
+            Expr::Or(parts) => parts.iter().any(|part| part.matches(words)),
+            Expr::Not(part) => !part.matches(words),
+        }
+    }
+
+    // The words that make an item rank higher (those after a NOT don't)
+    fn scored_words(&self, words: &mut Vec<String>) {
+        match self {
+            Expr::Word(word) => words.push(word.clone()),
+            Expr::Phrase(phrase) => words.extend(phrase.iter().cloned()),
+            Expr::And(parts) | Expr::Or(parts) => {
+                for part in parts {
+                    part.scored_words(words);
+                }
+            }
+            Expr::Not(_) => {}
+        }
+    }
+
+    fn scored_words_sorted(&self) -> Vec<String> {
+        let mut words = vec![];
+        self.scored_words(&mut words);
+        words.retain(|word| word != FIELD_BREAK);
+        words.sort();
+        words.dedup();
+        words
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(Vec<String>),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn lex(text: &str) -> Result<Vec<Token>, SearchError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err(SearchError::UnterminatedPhrase),
+                    }
+                }
+                tokens.push(words_token(&phrase));
+            }
+            '-' => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => words_token(&word),
+                });
+            }
+        }
+    }
+
+    // Punctuation alone ("!!!") has no words: it's dropped
+    tokens.retain(|token| *token != Token::Phrase(vec![]));
+    Ok(tokens)
+}
+
+// "sci-fi" is two words, that have to be next to each other
+fn words_token(text: &str) -> Token {
+    let words: Vec<String> = tokenize(text).iter().map(|word| stem(word)).collect();
+    if words.len() == 1 {
+        Token::Word(words[0].clone())
+    } else {
+        Token::Phrase(words)
+    }
+}
+
+// Recursive descent: OR binds the loosest, then AND, then NOT
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn or(&mut self) -> Result<Expr, SearchError> {
+        let mut parts = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            parts.push(self.operand("OR", Parser::and)?);
+        }
+        Ok(if parts.len() == 1 {
+            parts.remove(0)
+        } else {
+            Expr::Or(parts)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, SearchError> {
+        let mut parts = vec![self.not()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next += 1;
+                    parts.push(self.operand("AND", Parser::not)?);
+                }
+                // Two terms next to each other: AND is implied
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open) => {
+                    parts.push(self.not()?)
+                }
+                _ => break,
+            }
+        }
+        Ok(if parts.len() == 1 {
+            parts.remove(0)
+        } else {
+            Expr::And(parts)
+        })
+    }
+
+    fn not(&mut self) -> Result<Expr, SearchError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next += 1;
+            let part = self.operand("NOT", Parser::not)?;
+            return Ok(Expr::Not(Box::new(part)));
+        }
+        self.term()
+    }
+
+    fn term(&mut self) -> Result<Expr, SearchError> {
+        let token = self.peek().cloned();
+        self.next += 1;
+        match token {
+            Some(Token::Word(word)) => Ok(Expr::Word(word)),
+            Some(Token::Phrase(words)) => Ok(Expr::Phrase(words)),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(SearchError::UnbalancedParenthesis);
+                }
+                self.next += 1;
+                Ok(expr)
+            }
+            Some(Token::Close) => Err(SearchError::UnbalancedParenthesis),
+            Some(Token::And) => Err(SearchError::MissingOperand(String::from("AND"))),
+            Some(Token::Or) => Err(SearchError::MissingOperand(String::from("OR"))),
+            // 'not' takes care of NOT
+            Some(Token::Not) | None => Err(SearchError::Empty),
+        }
+    }
+
+    // What follows an operator: reports which operator is missing its operand
+    fn operand(
+        &mut self,
+        operator: &str,
+        parse: fn(&mut Parser) -> Result<Expr, SearchError>,
+    ) -> Result<Expr, SearchError> {
+        match self.peek() {
+            None | Some(Token::Close | Token::And | Token::Or) => {
+                Err(SearchError::MissingOperand(operator.to_string()))
+            }
+            _ => parse(self),
+        }
+    }
+}
+
+// For every word, the items containing it and how many times:
+// a search only looks at the items containing its words, instead of all of them.
+#[derive(Debug, Default, PartialEq)]
+pub struct SearchIndex {
+    postings: HashMap<String, HashMap<MediaId, usize>>,
+    // The words of every item, in order (for phrases)
+    documents: HashMap<MediaId, Vec<String>>,
+    total_length: usize,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        SearchIndex::default()
+    }
+
+    pub fn add(&mut self, id: MediaId, media: &Media) {
+        // There's nothing to search in a Placeholder
+        if matches!(media, Media::Placeholder) {
+            return;
+        }
+
+        let words = document(media);
+        for word in words.iter().filter(|word| *word != FIELD_BREAK) {
+            *self
+                .postings
+                .entry(word.clone())
+                .or_default()
+                .entry(id)
+                .or_default() += 1;
+        }
+        self.total_length += length(&words);
+        self.documents.insert(id, words);
+    }
+
+    pub fn remove(&mut self, id: MediaId) {
+        let Some(words) = self.documents.remove(&id) else {
+            return;
+        };
+
+        for word in words.iter().filter(|word| *word != FIELD_BREAK) {
+            if let Some(items) = self.postings.get_mut(word) {
+                items.remove(&id);
+                if items.is_empty() {
+                    self.postings.remove(word);
+                }
+            }
+        }
+        self.total_length -= length(&words);
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    fn all(&self) -> HashSet<MediaId> {
+        self.documents.keys().copied().collect()
+    }
+
+    fn matching(&self, expr: &Expr) -> HashSet<MediaId> {
+        match expr {
+            Expr::Word(word) => self
+                .postings
+                .get(word)
+                .map(|items| items.keys().copied().collect())
+                .unwrap_or_default(),
+            Expr::Phrase(phrase) => {
+                // Only the items containing the first word can contain the phrase
+                let Some(first) = phrase.first() else {
+                    return HashSet::new();
+                };
+                self.matching(&Expr::Word(first.clone()))
+                    .into_iter()
+                    .filter(|id| contains_phrase(&self.documents[id], phrase))
+                    .collect()
+            }
+            Expr::And(parts) => {
+                // 'a NOT b' removes b's items from a's, instead of going through all the items
+                let (excluded, included): (Vec<&Expr>, Vec<&Expr>) =
+                    parts.iter().partition(|part| matches!(part, Expr::Not(_)));
+                let mut items = match included.split_first() {
+                    Some((first, others)) => {
+                        let mut items = self.matching(first);
+                        for part in others {
+                            let other = self.matching(part);
+                            items.retain(|id| other.contains(id));
+                        }
+                        items
+                    }
+                    None => self.all(),
+                };
+                for part in excluded {
+                    if let Expr::Not(part) = part {
+                        for id in self.matching(part) {
+                            items.remove(&id);
+                        }
+                    }
+                }
+                items
+            }
+            Expr::Or(parts) => parts.iter().flat_map(|part| self.matching(part)).collect(),
+            Expr::Not(part) => {
+                let excluded = self.matching(part);
+                self.all()
+                    .into_iter()
+                    .filter(|id| !excluded.contains(id))
+                    .collect()
+            }
+        }
+    }
+
+    fn search(&self, expr: &Expr) -> Vec<Hit> {
+        let words = expr.scored_words_sorted();
+        let average_length = self.total_length as f64 / self.len().max(1) as f64;
+
+        let hits = self
+            .matching(expr)
+            .into_iter()
+            .map(|id| {
+                let document = &self.documents[&id];
+                let stats = Stats {
+                    documents: self.len(),
+                    average_length,
+                    length: length(document),
+                };
+                let score = words
+                    .iter()
+                    .filter_map(|word| {
+                        let items = self.postings.get(word)?;
+                        Some(bm25(*items.get(&id)?, items.len(), &stats))
+                    })
+                    .sum();
+                Hit { id, score }
+            })
+            .collect();
+        sorted(hits)
+    }
+}
+
+// Best first; the same score goes to the oldest item first
+fn sorted(mut hits: Vec<Hit>) -> Vec<Hit> {
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    hits
+}
+
+impl Catalog {
+    // Full-text search in titles and creators, best matches first (see 'Expr' for the syntax).
+    // Uses the index when there's one ('enable_index'), and goes through every item otherwise.
+    pub fn search(&self, text: &str) -> Result<Vec<Hit>, SearchError> {
+        let expr = Expr::parse(text)?;
+        match self.index() {
+            Some(index) => Ok(index.search(&expr)),
+            None => Ok(self.scan(&expr)),
+        }
+    }
+
+    // Same results as the index, but every item is read on every search
+    fn scan(&self, expr: &Expr) -> Vec<Hit> {
+        let documents: Vec<(MediaId, Vec<String>)> = self
+            .entries()
+            .filter(|(_, media)| !matches!(media, Media::Placeholder))
+            .map(|(id, media)| (id, document(media)))
+            .collect();
+        let total_length: usize = documents.iter().map(|(_, words)| length(words)).sum();
+        let average_length = total_length as f64 / documents.len().max(1) as f64;
+
+        let words = expr.scored_words_sorted();
+        let documents_with: Vec<usize> = words
+            .iter()
+            .map(|word| {
+                documents
+                    .iter()
+                    .filter(|(_, document)| document.contains(word))
+                    .count()
+            })
+            .collect();
+
+        let hits = documents
+            .iter()
+            .filter(|(_, document)| expr.matches(document))
+            .map(|(id, document)| {
+                let stats = Stats {
+                    documents: documents.len(),
+                    average_length,
+                    length: length(document),
+                };
+                let score = words
+                    .iter()
+                    .zip(&documents_with)
+                    .filter_map(|(word, with_word)| {
+                        let frequency = document.iter().filter(|w| *w == word).count();
+                        (frequency > 0).then(|| bm25(frequency, *with_word, &stats))
+                    })
+                    .sum();
+                Hit { id: *id, score }
+            })
+            .collect();
+        sorted(hits)
+    }
+}
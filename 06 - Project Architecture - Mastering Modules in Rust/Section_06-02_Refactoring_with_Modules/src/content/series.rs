@@ -0,0 +1,369 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+use super::catalog::{Catalog, MediaId};
+use super::media::{Media, Podcast};
+
+// Given by the catalog like MediaId, and never reused either
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SeriesId(pub u64);
+
+impl fmt::Display for SeriesId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "series #{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeriesKind {
+    // Meant to be followed in order: a trilogy, a TV show, the volumes of a saga...
+    Series,
+    // Grouped by someone, in the order they chose: "Best of 1999", "Criterion Collection"...
+    Collection,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeriesError {
+    UnknownSeries(SeriesId),
+    UnknownItem(MediaId),
+    // Placeholders can't be part of a series
+    NotGroupable(MediaId),
+    AlreadyInSeries(SeriesId, MediaId),
+    NotInSeries(SeriesId, MediaId),
+    PositionTaken { series: SeriesId, part: Part },
+}
+
+impl fmt::Display for SeriesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeriesError::UnknownSeries(series) => write!(f, "there's no {}", series),
+            SeriesError::UnknownItem(id) => write!(f, "no item with id {}", id),
+            SeriesError::NotGroupable(id) => write!(f, "item {} is a placeholder", id),
+            SeriesError::AlreadyInSeries(series, id) => {
+                write!(f, "item {} is already part of {}", id, series)
+            }
+            SeriesError::NotInSeries(series, id) => {
+                write!(f, "item {} isn't part of {}", id, series)
+            }
+            SeriesError::PositionTaken { series, part } => {
+                write!(f, "{} of {} is already item {}", part, series, part.id)
+            }
+        }
+    }
+}
+
+impl Error for SeriesError {}
+
+// Where an item is in a series: episode 3 of season 2, or volume 3 (no season)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Part {
+    pub id: MediaId,
+    pub season: Option<u32>,
+    pub number: u32,
+}
+
+impl Part {
+    // What a series is sorted by: seasons first, then numbers (parts without a season come first)
+    fn position(&self) -> (Option<u32>, u32) {
+        (self.season, self.number)
+    }
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.season {
+            Some(season) => write!(f, "S{:02}E{:02}", season, self.number),
+            None => write!(f, "part {}", self.number),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub name: String,
+    pub kind: SeriesKind,
+    // Always kept in order
+    parts: Vec<Part>,
+}
+
+impl Series {
+    pub fn new(name: &str, kind: SeriesKind) -> Self {
+        Series {
+            name: name.to_string(),
+            kind,
+            parts: vec![],
+        }
+    }
+
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    pub fn part(&self, id: MediaId) -> Option<&Part> {
+        self.parts.iter().find(|part| part.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    // The seasons that have at least one episode, in order
+    pub fn seasons(&self) -> Vec<u32> {
+        let mut seasons: Vec<u32> = self.parts.iter().filter_map(|part| part.season).collect();
+        seasons.dedup();
+        seasons
+    }
+
+    // The number after the last one in 'season'
+    fn next_number(&self, season: Option<u32>) -> u32 {
+        self.parts
+            .iter()
+            .filter(|part| part.season == season)
+            .map(|part| part.number + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn insert(&mut self, part: Part) {
+        let index = self
+            .parts
+            .partition_point(|other| other.position() < part.position());
+        self.parts.insert(index, part);
+    }
+
+    fn remove(&mut self, id: MediaId) -> Option<Part> {
+        let index = self.parts.iter().position(|part| part.id == id)?;
+        Some(self.parts.remove(index))
+    }
+}
+
+// Every series of a catalog
+#[derive(Debug, PartialEq)]
+pub struct SeriesList {
+    series: Vec<(SeriesId, Series)>,
+    next_id: u64,
+}
+
+impl Default for SeriesList {
+    fn default() -> Self {
+        SeriesList::new()
+    }
+}
+
+impl SeriesList {
+    pub fn new() -> Self {
+        SeriesList {
+            series: vec![],
+            next_id: 1,
+        }
+    }
+
+    // The item is gone: it isn't part of any series anymore
+    pub fn forget(&mut self, id: MediaId) {
+        for (_, series) in &mut self.series {
+            series.remove(id);
+        }
+    }
+
+    // 'from' was merged into 'to': 'to' takes its place in the series it wasn't part of
+    pub fn move_to(&mut self, from: MediaId, to: MediaId) {
+        for (_, series) in &mut self.series {
+            let Some(part) = series.remove(from) else {
+                continue;
+            };
+            if series.part(to).is_none() {
+                series.insert(Part { id: to, ..part });
+            }
+        }
+    }
+
+    fn get(&self, id: SeriesId) -> Result<&Series, SeriesError> {
+        self.series
+            .iter()
+            .find(|(series_id, _)| *series_id == id)
+            .map(|(_, series)| series)
+            .ok_or(SeriesError::UnknownSeries(id))
+    }
+
+    fn get_mut(&mut self, id: SeriesId) -> Result<&mut Series, SeriesError> {
+        self.series
+            .iter_mut()
+            .find(|(series_id, _)| *series_id == id)
+            .map(|(_, series)| series)
+            .ok_or(SeriesError::UnknownSeries(id))
+    }
+}
+
+impl Catalog {
+    pub fn create_series(&mut self, name: &str, kind: SeriesKind) -> SeriesId {
+        let list = self.series_list_mut();
+        let id = SeriesId(list.next_id);
+        list.next_id += 1;
+        list.series.push((id, Series::new(name, kind)));
+        id
+    }
+
+    // The items stay in the catalog
+    pub fn delete_series(&mut self, series: SeriesId) -> Result<Series, SeriesError> {
+        let list = self.series_list_mut();
+        let index = list
+            .series
+            .iter()
+            .position(|(id, _)| *id == series)
+            .ok_or(SeriesError::UnknownSeries(series))?;
+        Ok(list.series.remove(index).1)
+    }
+
+    pub fn series(&self, series: SeriesId) -> Result<&Series, SeriesError> {
+        self.series_list().get(series)
+    }
+
+    // The id the next created series will get
+    pub fn next_series_id(&self) -> SeriesId {
+        SeriesId(self.series_list().next_id)
+    }
+
+    // Like 'reserve_ids': ids below 'next' are never given out again
+    pub fn reserve_series_ids(&mut self, next: SeriesId) {
+        let list = self.series_list_mut();
+        list.next_id = list.next_id.max(next.0);
+    }
+
+    // Every series, in the order they were created
+    pub fn all_series(&self) -> impl Iterator<Item = (SeriesId, &Series)> {
+        self.series_list()
+            .series
+            .iter()
+            .map(|(id, series)| (*id, series))
+    }
+
+    // Puts the item at a given place: season 2, episode 3 is 'add_to_series(s, id, Some(2), 3)'
+    pub fn add_to_series(
+        &mut self,
+        series: SeriesId,
+        id: MediaId,
+        season: Option<u32>,
+        number: u32,
+    ) -> Result<Part, SeriesError> {
+        match self.get(id) {
+            Err(_) => return Err(SeriesError::UnknownItem(id)),
+            Ok(Media::Placeholder) => return Err(SeriesError::NotGroupable(id)),
+            Ok(_) => {}
+        }
+
+        let part = Part { id, season, number };
+        let target = self.series_list_mut().get_mut(series)?;
+        if target.part(id).is_some() {
+            return Err(SeriesError::AlreadyInSeries(series, id));
+        }
+        if let Some(taken) = target
+            .parts
+            .iter()
+            .find(|other| other.position() == part.position())
+        {
+            return Err(SeriesError::PositionTaken {
+                series,
+                part: *taken,
+            });
+        }
+
+        target.insert(part);
+        Ok(part)
+    }
+
+    // Puts the item after the last one of 'season' (or of the series, without a season)
+    pub fn append_to_series(
+        &mut self,
+        series: SeriesId,
+        id: MediaId,
+        season: Option<u32>,
+    ) -> Result<Part, SeriesError> {
+        let number = self.series(series)?.next_number(season);
+        self.add_to_series(series, id, season, number)
+    }
+
+    pub fn remove_from_series(
+        &mut self,
+        series: SeriesId,
+        id: MediaId,
+    ) -> Result<Part, SeriesError> {
+        self.series_list_mut()
+            .get_mut(series)?
+            .remove(id)
+            .ok_or(SeriesError::NotInSeries(series, id))
+    }
+
+    // Every series the item is part of, and where ("volume 3 of ...")
+    pub fn series_of(&self, id: MediaId) -> Vec<(SeriesId, Part)> {
+        self.all_series()
+            .filter_map(|(series_id, series)| Some((series_id, *series.part(id)?)))
+            .collect()
+    }
+
+    // All the items of the series, in order
+    pub fn in_series(&self, series: SeriesId) -> Result<Vec<(Part, &Media)>, SeriesError> {
+        self.series(series)?
+            .parts()
+            .iter()
+            .map(|part| {
+                let media = self
+                    .get(part.id)
+                    .map_err(|_| SeriesError::UnknownItem(part.id))?;
+                Ok((*part, media))
+            })
+            .collect()
+    }
+
+    // The episodes of one season, in order
+    pub fn season(
+        &self,
+        series: SeriesId,
+        season: u32,
+    ) -> Result<Vec<(Part, &Media)>, SeriesError> {
+        let mut items = self.in_series(series)?;
+        items.retain(|(part, _)| part.season == Some(season));
+        Ok(items)
+    }
+
+    // The first item of the series that isn't in 'watched' (None once everything is watched)
+    pub fn next_unwatched(
+        &self,
+        series: SeriesId,
+        watched: &HashSet<MediaId>,
+    ) -> Result<Option<(Part, &Media)>, SeriesError> {
+        Ok(self
+            .in_series(series)?
+            .into_iter()
+            .find(|(part, _)| !watched.contains(&part.id)))
+    }
+
+    // A series with every episode of a podcast, numbered like the episodes.
+    // Episodes added to the catalog later aren't added to it.
+    pub fn podcast_series(&mut self, show: &str) -> SeriesId {
+        let episodes: Vec<(MediaId, u32)> = self
+            .entries()
+            .filter_map(|(id, media)| match media {
+                Media::Podcast(Podcast {
+                    show: name,
+                    episode,
+                    ..
+                }) if name.eq_ignore_ascii_case(show) => Some((id, *episode)),
+                _ => None,
+            })
+            .collect();
+
+        let series = self.create_series(show, SeriesKind::Series);
+        for (id, episode) in episodes {
+            // Two items with the same episode number: the second goes at the end
+            if self.add_to_series(series, id, None, episode).is_err() {
+                let _ = self.append_to_series(series, id, None);
+            }
+        }
+        series
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::catalog::Catalog;
+use super::dedup::normalize_creator;
+use super::media::{Book, Length, Media, MediaKind, Movie};
+
+// How many authors and directors make the top
+const TOP: usize = 5;
+
+// A summary of the whole catalog, see 'Catalog::stats'.
+// Printed, it's a text report; 'to_json' gives the same as JSON (see 'storage').
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub items: usize,
+    pub per_kind: BTreeMap<MediaKind, usize>,
+    // Lowercase, so that "Fantasy" and "fantasy" count together
+    pub per_genre: BTreeMap<String, usize>,
+    // 1930 stands for the items from 1930 to 1939
+    pub per_decade: BTreeMap<u16, usize>,
+    // The most items first, then by name; up to TOP of them
+    pub top_authors: Vec<(String, usize)>,
+    pub top_directors: Vec<(String, usize)>,
+    // Out of 5, over the items that have a rating
+    pub average_rating: Option<f64>,
+    pub average_rating_per_kind: BTreeMap<MediaKind, f64>,
+    // Movies, audiobooks and podcasts (whatever is measured in minutes)
+    pub total_minutes: u64,
+    pub total_pages: u64,
+}
+
+// Counts the items of each creator. Names are compared normalized ("Tolkien, J.R.R." is
+// "J.R.R. Tolkien"), and shown the way they were first written.
+#[derive(Default)]
+struct Creators {
+    counts: HashMap<String, (String, usize)>,
+}
+
+impl Creators {
+    fn add(&mut self, name: &str) {
+        let key = normalize_creator(name);
+        if key.is_empty() {
+            return;
+        }
+        self.counts.entry(key).or_insert((name.to_string(), 0)).1 += 1;
+    }
+
+    fn top(self) -> Vec<(String, usize)> {
+        let mut creators: Vec<(String, usize)> = self.counts.into_values().collect();
+        creators.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        creators.truncate(TOP);
+        creators
+    }
+}
+
+impl Catalog {
+    // Everything is counted in one pass over the items
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        let (mut authors, mut directors) = (Creators::default(), Creators::default());
+        // The sum and the number of ratings, for each kind
+        let mut ratings: BTreeMap<MediaKind, (u64, u64)> = BTreeMap::new();
+
+        for media in self.iter() {
+            stats.items += 1;
+            *stats.per_kind.entry(media.kind()).or_insert(0) += 1;
+
+            match media {
+                Media::Book(Book { author, .. }) => authors.add(author),
+                Media::Movie(Movie { director, .. }) => directors.add(director),
+                _ => {}
+            }
+
+            let Some(meta) = media.meta() else {
+                continue;
+            };
+            for genre in &meta.genres {
+                *stats.per_genre.entry(genre.to_lowercase()).or_insert(0) += 1;
+            }
+            if let Some(year) = meta.year {
+                *stats.per_decade.entry(year - year % 10).or_insert(0) += 1;
+            }
+            match meta.length {
+                Some(Length::Minutes(minutes)) => stats.total_minutes += minutes as u64,
+                Some(Length::Pages(pages)) => stats.total_pages += pages as u64,
+                None => {}
+            }
+            if let Some(rating) = meta.rating {
+                let (sum, count) = ratings.entry(media.kind()).or_insert((0, 0));
+                *sum += rating as u64;
+                *count += 1;
+            }
+        }
+
+        stats.top_authors = authors.top();
+        stats.top_directors = directors.top();
+        let (sum, count) = ratings
+            .values()
+            .fold((0, 0), |(sum, count), (s, c)| (sum + s, count + c));
+        if count > 0 {
+            stats.average_rating = Some(sum as f64 / count as f64);
+        }
+        stats.average_rating_per_kind = ratings
+            .into_iter()
+            .map(|(kind, (sum, count))| (kind, sum as f64 / count as f64))
+            .collect();
+        stats
+    }
+}
+
+// "1262 min" -> "21 h 2 min"
+fn runtime(minutes: u64) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{} min", minutes),
+        (hours, 0) => format!("{} h", hours),
+        (hours, minutes) => format!("{} h {} min", hours, minutes),
+    }
+}
+
+// A title, then one row per line with the counts aligned:
+//   Top authors:
+//     J.R.R. Tolkien   2
+//     Frank Herbert    1
+fn section(f: &mut fmt::Formatter, title: &str, rows: Vec<(String, usize)>) -> fmt::Result {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    writeln!(f, "{}:", title)?;
+    for (name, count) in rows {
+        writeln!(f, "  {:width$}  {}", name, count, width = width)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} item(s), {} of runtime, {} pages",
+            self.items,
+            runtime(self.total_minutes),
+            self.total_pages
+        )?;
+
+        let kinds = self
+            .per_kind
+            .iter()
+            .map(|(kind, count)| (kind.to_string(), *count))
+            .collect();
+        section(f, "Kinds", kinds)?;
+        // The most common genres first
+        let mut genres: Vec<(String, usize)> = self
+            .per_genre
+            .iter()
+            .map(|(genre, count)| (genre.clone(), *count))
+            .collect();
+        genres.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        section(f, "Genres", genres)?;
+        let decades = self
+            .per_decade
+            .iter()
+            .map(|(decade, count)| (format!("{}s", decade), *count))
+            .collect();
+        section(f, "Decades", decades)?;
+        section(f, "Top authors", self.top_authors.clone())?;
+        section(f, "Top directors", self.top_directors.clone())?;
+
+        match self.average_rating {
+            Some(average) => {
+                let per_kind: Vec<String> = self
+                    .average_rating_per_kind
+                    .iter()
+                    .map(|(kind, average)| format!("{} {:.1}", kind, average))
+                    .collect();
+                write!(
+                    f,
+                    "Average rating: {:.1}/5 ({})",
+                    average,
+                    per_kind.join(", ")
+                )
+            }
+            None => write!(f, "Average rating: no ratings yet"),
+        }
+    }
+}
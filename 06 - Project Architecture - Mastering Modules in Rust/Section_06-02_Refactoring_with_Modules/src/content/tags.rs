@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+
+use super::catalog::{Catalog, MediaId};
+use super::media::Media;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagError {
+    UnknownItem(MediaId),
+    // A placeholder has nothing to tag
+    NotTaggable(MediaId),
+    // Tags and shelves need a name
+    EmptyName,
+    UnknownTag(String),
+    // Renaming onto an existing tag: that's what 'merge_tags' is for
+    TagExists(String),
+    UnknownShelf(String),
+    ShelfExists(String),
+    AlreadyOnShelf { shelf: String, id: MediaId },
+    NotOnShelf { shelf: String, id: MediaId },
+}
+
+impl fmt::Display for TagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TagError::UnknownItem(id) => write!(f, "no item with id {}", id),
+            TagError::NotTaggable(id) => write!(f, "item {} is a placeholder", id),
+            TagError::EmptyName => write!(f, "the name is empty"),
+            TagError::UnknownTag(tag) => write!(f, "no item is tagged '{}'", tag),
+            TagError::TagExists(tag) => {
+                write!(f, "'{}' is already a tag (merge the tags instead)", tag)
+            }
+            TagError::UnknownShelf(shelf) => write!(f, "no shelf named '{}'", shelf),
+            TagError::ShelfExists(shelf) => write!(f, "there's already a shelf named '{}'", shelf),
+            TagError::AlreadyOnShelf { shelf, id } => write!(f, "{} is already on '{}'", id, shelf),
+            TagError::NotOnShelf { shelf, id } => write!(f, "{} isn't on '{}'", id, shelf),
+        }
+    }
+}
+
+impl Error for TagError {}
+
+// "  Science   Fiction " -> "science fiction": tags are compared that way, and kept that way
+fn normalize_tag(tag: &str) -> Result<String, TagError> {
+    let tag = tag
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    if tag.is_empty() {
+        return Err(TagError::EmptyName);
+    }
+    Ok(tag)
+}
+
+// A named list of items, in the order the user wants them
+#[derive(Debug, Clone, PartialEq)]
+struct Shelf {
+    name: String,
+    items: Vec<MediaId>,
+}
+
+// Free-form tags (unlike genres, they're the user's) and shelves ("to read", "favorites")
+#[derive(Debug, Default, PartialEq)]
+pub struct Tags {
+    // Tag -> the items that have it. A tag without items is gone.
+    tags: BTreeMap<String, BTreeSet<MediaId>>,
+    // In the order they were created. Names keep their case, but "Kids" and "kids"
+    // are the same shelf.
+    shelves: Vec<Shelf>,
+}
+
+impl Tags {
+    pub fn new() -> Self {
+        Tags::default()
+    }
+
+    // The item is gone: so are its tags, and its place on the shelves
+    pub fn forget(&mut self, id: MediaId) {
+        for items in self.tags.values_mut() {
+            items.remove(&id);
+        }
+        self.tags.retain(|_, items| !items.is_empty());
+        for shelf in &mut self.shelves {
+            shelf.items.retain(|item| *item != id);
+        }
+    }
+
+    // 'from' was merged into 'to': 'to' gets its tags, and its place on the shelves
+    // 'to' wasn't on
+    pub fn move_to(&mut self, from: MediaId, to: MediaId) {
+        for items in self.tags.values_mut() {
+            if items.remove(&from) {
+                items.insert(to);
+            }
+        }
+        for shelf in &mut self.shelves {
+            if shelf.items.contains(&to) {
+                shelf.items.retain(|item| *item != from);
+            } else if let Some(item) = shelf.items.iter_mut().find(|item| **item == from) {
+                *item = to;
+            }
+        }
+    }
+
+    fn shelf(&self, name: &str) -> Result<&Shelf, TagError> {
+        self.shelves
+            .iter()
+            .find(|shelf| shelf.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| TagError::UnknownShelf(name.to_string()))
+    }
+
+    fn shelf_mut(&mut self, name: &str) -> Result<&mut Shelf, TagError> {
+        self.shelves
+            .iter_mut()
+            .find(|shelf| shelf.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| TagError::UnknownShelf(name.to_string()))
+    }
+}
+
+impl Catalog {
+    fn taggable(&self, id: MediaId) -> Result<(), TagError> {
+        match self.get(id) {
+            Err(_) => Err(TagError::UnknownItem(id)),
+            Ok(Media::Placeholder) => Err(TagError::NotTaggable(id)),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    fn entries_of(&self, ids: impl IntoIterator<Item = MediaId>) -> Vec<(MediaId, &Media)> {
+        ids.into_iter()
+            .filter_map(|id| self.get(id).ok().map(|media| (id, media)))
+            .collect()
+    }
+
+    // False when the item already had the tag
+    pub fn tag(&mut self, id: MediaId, tag: &str) -> Result<bool, TagError> {
+        self.taggable(id)?;
+        let tag = normalize_tag(tag)?;
+        Ok(self.tag_list_mut().tags.entry(tag).or_default().insert(id))
+    }
+
+    // False when the item didn't have the tag
+    pub fn untag(&mut self, id: MediaId, tag: &str) -> Result<bool, TagError> {
+        self.taggable(id)?;
+        let tag = normalize_tag(tag)?;
+        let tags = &mut self.tag_list_mut().tags;
+        let Some(items) = tags.get_mut(&tag) else {
+            return Ok(false);
+        };
+        let removed = items.remove(&id);
+        if items.is_empty() {
+            tags.remove(&tag);
+        }
+        Ok(removed)
+    }
+
+    // In alphabetical order
+    pub fn tags_of(&self, id: MediaId) -> Vec<&str> {
+        self.tag_list()
+            .tags
+            .iter()
+            .filter(|(_, items)| items.contains(&id))
+            .map(|(tag, _)| tag.as_str())
+            .collect()
+    }
+
+    // Every tag, with the number of items that have it, in alphabetical order
+    pub fn tags(&self) -> Vec<(&str, usize)> {
+        self.tag_list()
+            .tags
+            .iter()
+            .map(|(tag, items)| (tag.as_str(), items.len()))
+            .collect()
+    }
+
+    // The items that have all the tags ("fantasy" AND "kids"), by id
+    pub fn tagged_all(&self, tags: &[&str]) -> Vec<(MediaId, &Media)> {
+        let Some((first, others)) = tags.split_first() else {
+            return vec![];
+        };
+        let set = |tag: &str| {
+            normalize_tag(tag)
+                .ok()
+                .and_then(|tag| self.tag_list().tags.get(&tag))
+        };
+
+        let Some(items) = set(first) else {
+            return vec![];
+        };
+        let ids = items.iter().copied().filter(|id| {
+            others
+                .iter()
+                .all(|tag| set(tag).is_some_and(|items| items.contains(id)))
+        });
+        self.entries_of(ids)
+    }
+
+    // The items that have at least one of the tags ("fantasy" OR "kids"), by id
+    pub fn tagged_any(&self, tags: &[&str]) -> Vec<(MediaId, &Media)> {
+        let mut ids = BTreeSet::new();
+        for tag in tags {
+            let items = normalize_tag(tag)
+                .ok()
+                .and_then(|tag| self.tag_list().tags.get(&tag));
+            ids.extend(items.into_iter().flatten().copied());
+        }
+        self.entries_of(ids)
+    }
+
+    // Gives back the number of items that have the new name
+    pub fn rename_tag(&mut self, old: &str, new: &str) -> Result<usize, TagError> {
+        let (old_tag, new_tag) = (normalize_tag(old)?, normalize_tag(new)?);
+        let tags = &mut self.tag_list_mut().tags;
+        if !tags.contains_key(&old_tag) {
+            return Err(TagError::UnknownTag(old.to_string()));
+        }
+        if old_tag != new_tag && tags.contains_key(&new_tag) {
+            return Err(TagError::TagExists(new_tag));
+        }
+
+        let items = tags.remove(&old_tag).unwrap_or_default();
+        let count = items.len();
+        tags.insert(new_tag, items);
+        Ok(count)
+    }
+
+    // The items tagged 'from' get tagged 'into', and 'from' is gone.
+    // Gives back the number of items tagged 'into' now.
+    pub fn merge_tags(&mut self, from: &str, into: &str) -> Result<usize, TagError> {
+        let (from_tag, into_tag) = (normalize_tag(from)?, normalize_tag(into)?);
+        let tags = &mut self.tag_list_mut().tags;
+        let Some(items) = tags.remove(&from_tag) else {
+            return Err(TagError::UnknownTag(from.to_string()));
+        };
+
+        let merged = tags.entry(into_tag).or_default();
+        merged.extend(items);
+        Ok(merged.len())
+    }
+
+    pub fn create_shelf(&mut self, name: &str) -> Result<(), TagError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(TagError::EmptyName);
+        }
+        if self.tag_list().shelf(name).is_ok() {
+            return Err(TagError::ShelfExists(name.to_string()));
+        }
+
+        self.tag_list_mut().shelves.push(Shelf {
+            name: name.to_string(),
+            items: vec![],
+        });
+        Ok(())
+    }
+
+    // The items stay in the catalog: the ids of the ones that were on it are given back
+    pub fn delete_shelf(&mut self, name: &str) -> Result<Vec<MediaId>, TagError> {
+        let shelves = &mut self.tag_list_mut().shelves;
+        let index = shelves
+            .iter()
+            .position(|shelf| shelf.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| TagError::UnknownShelf(name.to_string()))?;
+        Ok(shelves.remove(index).items)
+    }
+
+    // At 'position' (from 0), or at the end without one (or when it's past the end).
+    // Gives back where the item went.
+    pub fn add_to_shelf(
+        &mut self,
+        shelf: &str,
+        id: MediaId,
+        position: Option<usize>,
+    ) -> Result<usize, TagError> {
+        self.taggable(id)?;
+        let shelf = self.tag_list_mut().shelf_mut(shelf)?;
+        if shelf.items.contains(&id) {
+            return Err(TagError::AlreadyOnShelf {
+                shelf: shelf.name.clone(),
+                id,
+            });
+        }
+
+        let position = position.unwrap_or(shelf.items.len()).min(shelf.items.len());
+        shelf.items.insert(position, id);
+        Ok(position)
+    }
+
+    pub fn remove_from_shelf(&mut self, shelf: &str, id: MediaId) -> Result<(), TagError> {
+        let shelf = self.tag_list_mut().shelf_mut(shelf)?;
+        let index = shelf
+            .items
+            .iter()
+            .position(|item| *item == id)
+            .ok_or_else(|| TagError::NotOnShelf {
+                shelf: shelf.name.clone(),
+                id,
+            })?;
+        shelf.items.remove(index);
+        Ok(())
+    }
+
+    // Puts the item at another place on the shelf: the others move along.
+    // Gives back where it went (the end, when 'position' is past it).
+    pub fn move_on_shelf(
+        &mut self,
+        shelf: &str,
+        id: MediaId,
+        position: usize,
+    ) -> Result<usize, TagError> {
+        self.remove_from_shelf(shelf, id)?;
+        self.add_to_shelf(shelf, id, Some(position))
+    }
+
+    // In the order of the shelf
+    pub fn shelf(&self, name: &str) -> Result<Vec<(MediaId, &Media)>, TagError> {
+        let shelf = self.tag_list().shelf(name)?;
+        Ok(self.entries_of(shelf.items.iter().copied()))
+    }
+
+    // Every shelf, with the number of items on it, in the order they were created
+    pub fn shelves(&self) -> Vec<(&str, usize)> {
+        self.tag_list()
+            .shelves
+            .iter()
+            .map(|shelf| (shelf.name.as_str(), shelf.items.len()))
+            .collect()
+    }
+
+    pub fn shelves_of(&self, id: MediaId) -> Vec<&str> {
+        self.tag_list()
+            .shelves
+            .iter()
+            .filter(|shelf| shelf.items.contains(&id))
+            .map(|shelf| shelf.name.as_str())
+            .collect()
+    }
+}
@@ -0,0 +1,4 @@
+// The library part of the crate: both the demo ('src/main.rs')
+// and the 'catalog' command ('src/bin/catalog.rs') use these modules.
+pub mod content;
+pub mod storage;
@@ -1,7 +1,18 @@
-mod content;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+use std::process;
 
-use content::catalog::Catalog;
-use content::media::Media;
+// The modules live in the library ('src/lib.rs'): the crate name is used like any dependency's
+use media_catalog::content::catalog::{Catalog, MediaId};
+use media_catalog::content::date::{self, Date};
+use media_catalog::content::item::{MediaItem, MediaRegistry, SavedItem};
+use media_catalog::content::media::{Audiobook, Book, Media, MediaKind, Metadata, Movie, Podcast};
+use media_catalog::content::page::Cursor;
+use media_catalog::content::progress::Progress;
+use media_catalog::content::query::SortBy;
+use media_catalog::content::series::SeriesKind;
+use media_catalog::storage::{ScanReport, Scanner};
 
 /*
 fn print_media(media: Media) {
@@ -9,53 +20,1055 @@ fn print_media(media: Media) {
 }
  */
 
+// A kind of media the library doesn't know about: this crate adds it
+// (any crate using 'media_catalog' can do the same)
+#[derive(Debug, Clone)]
+struct Comic {
+    title: String,
+    writer: String,
+    issue: u32,
+    meta: Metadata,
+}
+
+impl Comic {
+    fn build(saved: SavedItem) -> Result<Box<dyn MediaItem>, String> {
+        let issue = saved
+            .fields
+            .get("issue")
+            .ok_or_else(|| String::from("missing issue"))?;
+        Ok(Box::new(Comic {
+            title: saved.title,
+            writer: saved.creator,
+            issue: issue
+                .parse()
+                .map_err(|_| format!("invalid issue '{}'", issue))?,
+            meta: saved.meta,
+        }))
+    }
+}
+
+impl MediaItem for Comic {
+    fn kind(&self) -> MediaKind {
+        MediaKind::Custom("comic".into())
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn creator(&self) -> Option<&str> {
+        Some(&self.writer)
+    }
+
+    fn meta(&self) -> Option<&Metadata> {
+        Some(&self.meta)
+    }
+
+    fn meta_mut(&mut self) -> Option<&mut Metadata> {
+        Some(&mut self.meta)
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Comic: {} #{} by {}{}",
+            self.title,
+            self.issue,
+            self.writer,
+            self.meta.details()
+        )
+    }
+
+    fn fields(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([(String::from("issue"), self.issue.to_string())])
+    }
+
+    fn clone_item(&self) -> Box<dyn MediaItem> {
+        Box::new(self.clone())
+    }
+}
+
 fn main() {
-    let audiobook = Media::Audiobook {
+    let audiobook = Media::Audiobook(Audiobook {
         title: String::from("audiobook"),
-    };
-    let good_movie = Media::Movie {
+        meta: Metadata::new().with_minutes(540).with_language("en"),
+    });
+    let good_movie = Media::Movie(Movie {
         title: String::from("Good Movie"),
         director: String::from("Good Director"),
-    };
-    let bad_book = Media::Book {
+        meta: Metadata::new()
+            .with_year(1999)
+            .with_genres(&["drama", "comedy"])
+            .with_minutes(124)
+            .with_rating(5),
+    });
+    let bad_book = Media::Book(Book {
         title: String::from("Bad Book"),
         author: String::from("Bad Author"),
-    };
+        meta: Metadata::new()
+            .with_year(2011)
+            .with_genres(&["thriller"])
+            .with_pages(412)
+            .with_language("fr")
+            .with_rating(1),
+    });
 
-    let podcast = Media::Podcast(10);
+    let podcast = Media::Podcast(Podcast {
+        show: String::from("Good Podcast"),
+        episode: 10,
+        title: String::from("Tenth Episode"),
+        meta: Metadata::new().with_year(2023).with_minutes(45),
+    });
     let placeholder = Media::Placeholder;
 
     let mut catalog = Catalog::new();
     catalog.add(audiobook);
-    catalog.add(good_movie);
-    catalog.add(bad_book);
+    let movie_id = catalog.add(good_movie);
+    let book_id = catalog.add(bad_book);
     catalog.add(podcast);
-    catalog.add(placeholder);
+    let placeholder_id = catalog.add(placeholder);
 
-    let item_unwrap = catalog.get_by_index(40);
-    let item_expect = catalog.get_by_index(40);
-    let item_unwrap_or  = catalog.get_by_index(40);
-    let placeholder = Media::Placeholder;
+    for media in catalog.iter() {
+        println!("{}", media.description());
+    }
+    if let Some(meta) = catalog.get_by_index(1).and_then(|media| media.meta()) {
+        println!(
+            "Genres of item 1: {:?}, length: {:?}",
+            meta.genres, meta.length
+        );
+    }
 
-    /*
-    Will generate:
-    thread 'main' (16332) panicked at src\main.rs:89:35:
-    called `Option::unwrap()` on a `None` value
-    stack backtrace: ...
-     */
-    println!("{:#?}", item_unwrap.unwrap()); // Will panic
+    // Searching: every filter is optional, results are borrowed from the catalog
+    let titles = |results: Vec<&Media>| -> Vec<String> {
+        results
+            .iter()
+            .map(|media| media.title().to_string())
+            .collect()
+    };
+    let books_and_movies = catalog
+        .query()
+        .kind(MediaKind::Book)
+        .kind(MediaKind::Movie)
+        .years(1990..=2020)
+        .run()
+        .collect();
+    println!(
+        "Books and movies from 1990 to 2020: {:?}",
+        titles(books_and_movies)
+    );
+    let by_director = catalog
+        .query()
+        .creator("good dir")
+        .genre("Drama")
+        .run()
+        .collect();
+    println!("Dramas by a 'good dir...': {:?}", titles(by_director));
+    let containing = catalog.query().title("BOOK").run().collect();
+    println!("Titles containing 'BOOK': {:?}", titles(containing));
+    let typo = catalog.query().fuzzy_title("god movei", 3).run().collect();
+    println!("Titles close to 'god movei': {:?}", titles(typo));
+    for sort in [
+        SortBy::Id,
+        SortBy::Kind,
+        SortBy::Title,
+        SortBy::Creator,
+        SortBy::Year,
+        SortBy::Length,
+        SortBy::Rating,
+    ] {
+        let sorted = catalog.query().sort_by(sort).descending().run().collect();
+        println!("By {:?}, descending: {:?}", sort, titles(sorted));
+    }
 
-    /*
-    Will generate:
-    thread 'main' (8696) panicked at src\main.rs:96:35:
-    There should be a value here
-    stack backtrace: ...
-     */
-    println!("{:#?}", item_expect.expect("There should be a value here")); // Will panic but display "There should be a value here as the error message"
+    // Ids stay the same when other items are removed (unlike indexes)
+    let extra = catalog.add(Media::Audiobook(Audiobook {
+        title: String::from("Extra Audiobook"),
+        meta: Metadata::new(),
+    }));
+    println!(
+        "Removed {}: {:?}",
+        extra,
+        catalog.remove(extra).map(|m| m.description())
+    );
+    println!("Removing it twice: {:?}", catalog.remove(extra));
+    let _ = catalog.update(book_id, |media| {
+        if let Media::Book(Book { meta, .. }) = media {
+            meta.rating = Some(2);
+        }
+    });
+    println!(
+        "{} after update: {:?}",
+        book_id,
+        catalog.get(book_id).map(|m| m.description())
+    );
+    let old = catalog.replace(
+        movie_id,
+        Media::Movie(Movie {
+            title: String::from("Good Movie (Director's Cut)"),
+            director: String::from("Good Director"),
+            meta: Metadata::new().with_year(2004).with_minutes(151),
+        }),
+    );
+    println!("Replaced: {:?}", old.map(|m| m.title().to_string()));
+    println!(
+        "Updating {}: {:?}",
+        MediaId(99),
+        catalog.update(MediaId(99), |_| {})
+    );
+    println!(
+        "{} items, ids in search results: {:?}",
+        catalog.len(),
+        catalog
+            .query()
+            .kind(MediaKind::Movie)
+            .entries()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>()
+    );
+    println!(
+        "Inserting with a used id: {:?}, empty: {}",
+        catalog.insert(book_id, Media::Placeholder),
+        catalog.is_empty()
+    );
+
+    // The same book, imported from two other sources
+    let hobbit = catalog.add(Media::Book(Book {
+        title: String::from("The Hobbit"),
+        author: String::from("J.R.R. Tolkien"),
+        meta: Metadata::new().with_year(1937).with_genres(&["fantasy"]),
+    }));
+    let hobbit_again = catalog.add(Media::Book(Book {
+        title: String::from("Hobbit, The"),
+        author: String::from("Tolkien, J. R. R."),
+        meta: Metadata::new()
+            .with_year(1937)
+            .with_pages(310)
+            .with_language("en")
+            .with_genres(&["Fantasy", "adventure"]),
+    }));
+    for suggestion in catalog.find_duplicates(0.85) {
+        println!(
+            "Duplicate? keep {}, drop {} (score {:.2})",
+            suggestion.keep, suggestion.duplicate, suggestion.score
+        );
+    }
+    println!(
+        "Merging a book and a movie: {:?}",
+        catalog.merge(book_id, movie_id)
+    );
+    match catalog.merge(hobbit, hobbit_again) {
+        Ok(kept) => println!(
+            "Merged into {}: {:?}",
+            kept,
+            catalog.get(kept).map(|m| m.description())
+        ),
+        Err(error) => println!("Error: {}", error),
+    }
+
+    // Lending: who has what, until when, and who's waiting for it
+    let today = Date::parse("2024-02-20").expect("a valid date");
+    match catalog.check_out(book_id, "alice", today, 14) {
+        Ok(due) => println!("alice has {} until {}", book_id, due),
+        Err(error) => println!("Error: {}", error),
+    }
+    for (id, borrower) in [
+        (book_id, "bob"),
+        (placeholder_id, "bob"),
+        (MediaId(99), "bob"),
+    ] {
+        if let Err(error) = catalog.check_out(id, borrower, today, 14) {
+            println!("Lending {} to {}: {}", id, borrower, error);
+        }
+    }
+    for borrower in ["bob", "carol", "dave", "bob"] {
+        match catalog.place_hold(book_id, borrower) {
+            Ok(position) => println!("{} is number {} in line", borrower, position),
+            Err(error) => println!("Error: {}", error),
+        }
+    }
+    println!(
+        "dave changes their mind: {:?}, waiting: {:?}",
+        catalog.cancel_hold(book_id, "dave"),
+        catalog.holds(book_id)
+    );
+    let _ = catalog.check_out(
+        movie_id,
+        "alice",
+        Date::new(2024, 2, 1).expect("a valid date"),
+        7,
+    );
+    for (id, loan) in catalog.loans_of("alice") {
+        println!("alice: {} since {}, due {}", id, loan.checked_out, loan.due);
+    }
+
+    // February 2024 has 29 days
+    let later = today.add_days(20);
+    println!(
+        "{} is {} days after {} (leap year: {}, {} days in February)",
+        later,
+        today.days_until(later),
+        today,
+        date::is_leap_year(later.year),
+        date::days_in_month(later.year, 2)
+    );
+    for overdue in catalog.overdue(later) {
+        println!(
+            "Overdue: {} borrowed by {}, due {} ({} days late)",
+            overdue.id, overdue.borrower, overdue.due, overdue.days_late
+        );
+    }
+    match catalog.check_in(book_id, later) {
+        Ok(returned) => println!(
+            "{} returned by {} ({} days late), put it aside for {:?}",
+            book_id, returned.borrower, returned.days_late, returned.next_in_line
+        ),
+        Err(error) => println!("Error: {}", error),
+    }
+    if let Err(error) = catalog.check_out(book_id, "carol", later, 14) {
+        println!("carol tries to skip the line: {}", error);
+    }
+    let _ = catalog.check_out(book_id, "bob", later, 14);
+    println!(
+        "Now: {:?}, still waiting: {:?}",
+        catalog.loan(book_id).map(|loan| loan.borrower.clone()),
+        catalog.holds(book_id)
+    );
+    let _ = catalog.check_in(movie_id, later);
+    println!(
+        "Checking in {} twice: {:?}",
+        movie_id,
+        catalog.check_in(movie_id, later)
+    );
+
+    // Progress: how far everyone got, in pages or minutes
+    let audiobook_id = MediaId(1);
+    let podcast_id = MediaId(4);
+    let updates = [
+        ("alice", book_id, Progress::Pages(100), "2024-03-01"),
+        ("alice", audiobook_id, Progress::Minutes(60), "2024-03-02"),
+        ("alice", book_id, Progress::Pages(250), "2024-03-03"),
+        ("alice", podcast_id, Progress::Finished, "2024-03-03"),
+        ("bob", movie_id, Progress::Minutes(500), "2024-03-04"),
+        ("alice", movie_id, Progress::Pages(3), "2024-03-05"),
+        ("alice", placeholder_id, Progress::Finished, "2024-03-05"),
+    ];
+    for (user, id, progress, on) in updates {
+        let on = Date::parse(on).expect("a valid date");
+        if let Err(error) = catalog.record_progress(user, id, progress, on) {
+            println!("{} on {}: {}", user, id, error);
+        }
+    }
+    for (update, media) in catalog.continue_watching("alice") {
+        println!(
+            "alice, continue with {}: {} ({:.0}%)",
+            media.title(),
+            update.progress,
+            catalog.completion("alice", update.id).unwrap_or(0.0)
+        );
+    }
+    let _ = catalog.finish(
+        "alice",
+        audiobook_id,
+        Date::new(2024, 3, 6).expect("a valid date"),
+    );
+    for update in catalog.history("alice") {
+        println!("  {} {}: {}", update.on, update.id, update.progress);
+    }
+    println!(
+        "bob: {:?}, finished: {:?}",
+        catalog
+            .progress("bob", movie_id)
+            .map(|update| update.progress),
+        catalog.finished("bob")
+    );
+
+    // Recommendations: what alice liked, and what the people who liked the same things liked
+    let sequel = catalog.add(Media::Book(Book {
+        title: String::from("Bad Book Returns"),
+        author: String::from("Bad Author"),
+        meta: Metadata::new().with_year(2014).with_genres(&["thriller"]),
+    }));
+    let thriller = catalog.add(Media::Movie(Movie {
+        title: String::from("Night Chase"),
+        director: String::from("Someone Else"),
+        meta: Metadata::new()
+            .with_genres(&["Thriller", "action"])
+            .with_rating(4),
+    }));
+    let ratings = [
+        ("alice", book_id, 5),
+        ("alice", audiobook_id, 2),
+        ("carol", book_id, 4),
+        ("carol", hobbit_again, 5),
+        ("carol", audiobook_id, 5),
+        // Merged into the other one
+        ("carol", hobbit, 5),
+        ("alice", placeholder_id, 4),
+        ("alice", book_id, 7),
+    ];
+    for (user, id, stars) in ratings {
+        if let Err(error) = catalog.rate(user, id, stars) {
+            println!("{} rating {}: {}", user, id, error);
+        }
+    }
+    println!(
+        "alice gave {} {:?} stars",
+        book_id,
+        catalog.user_rating("alice", book_id)
+    );
+    for user in ["alice", "dave"] {
+        for recommendation in catalog.recommend(user, 3) {
+            println!(
+                "For {}: {} ({:.2}), {}",
+                user,
+                catalog
+                    .get(recommendation.id)
+                    .map(|m| m.title().to_string())
+                    .unwrap_or_default(),
+                recommendation.score,
+                recommendation.explanation
+            );
+        }
+    }
+    let again = catalog.recommend("alice", 3);
+    println!(
+        "Same recommendations twice: {}, first reason: {:?}, {} and {} included: {}",
+        again == catalog.recommend("alice", 3),
+        again.first().map(|r| &r.reason),
+        sequel,
+        thriller,
+        again.iter().any(|r| r.id == sequel) && again.iter().any(|r| r.id == thriller)
+    );
+
+    // Saving and loading: the format comes from the extension
+    let folder = env::temp_dir();
+    for name in ["catalog.json", "catalog.csv"] {
+        let path = folder.join(name).display().to_string();
+        let result = catalog.save(&path).and_then(|_| Catalog::load(&path));
+        match result {
+            // Everything is saved (the series come later)
+            Ok(loaded) => println!(
+                "{}: same items once loaded: {}, same progress: {}, same loans and holds: {}, same ratings: {}",
+                path,
+                loaded.entries().eq(catalog.entries()),
+                loaded.progress_updates() == catalog.progress_updates(),
+                loaded.all_loans() == catalog.all_loans()
+                    && loaded.all_holds() == catalog.all_holds(),
+                loaded.all_ratings() == catalog.all_ratings()
+            ),
+            Err(error) => println!("Error: {}", error),
+        }
+    }
+
+    // The id of a removed item isn't given out again, even after saving and loading
+    let csv = folder.join("catalog.csv").display().to_string();
+    let shorter = folder.join("shorter.csv").display().to_string();
+    let result = Catalog::load(&csv).and_then(|mut loaded| {
+        let last = loaded.entries().map(|(id, _)| id).max();
+        if let Some(last) = last {
+            let _ = loaded.remove(last);
+        }
+        loaded.save(&shorter)?;
+        Ok((last, Catalog::load(&shorter)?))
+    });
+    match result {
+        Ok((Some(last), mut loaded)) => println!(
+            "{}: {} removed, the next item gets {}",
+            shorter,
+            last,
+            loaded.add(Media::Placeholder)
+        ),
+        Ok((None, _)) => println!("{}: no items", shorter),
+        Err(error) => println!("Error: {}", error),
+    }
+    println!("Saving as .txt: {:?}", catalog.save("catalog.txt"));
+    println!(
+        "Loading a missing file: {:?}",
+        Catalog::load("missing.json").map(|_| ())
+    );
+
+    // A file written by a newer version, with a field we don't know about yet
+    let newer = folder.join("newer.csv").display().to_string();
+    let _ = fs::write(&newer, "title,kind,id,soundtrack\nNew Movie,movie,9,yes\n");
+    match Catalog::load(&newer) {
+        Ok(loaded) => println!(
+            "{}: {:?}",
+            newer,
+            loaded.get_by_index(0).map(|m| m.description())
+        ),
+        Err(error) => println!("Error: {}", error),
+    }
+    let broken = folder.join("broken.json").display().to_string();
+    let _ = fs::write(
+        &broken,
+        r#"{"items": [{"kind": "book", "id": 1}, {"kind": "podcast", "id": 2}]}"#,
+    );
+    println!("{}: {:?}", broken, Catalog::load(&broken).map(|_| ()));
+
+    // Importing what other tools export: books from BibTeX and library catalogs (MARCXML),
+    // podcast subscriptions (OPML). What can't be mapped is reported, not added.
+    let bibtex = folder.join("library.bib").display().to_string();
+    let _ = fs::write(
+        &bibtex,
+        r#"@string{ny = "New York"}
+@book{herbert1965, title = {{Dune}}, author = "Herbert, Frank", year = 1965,
+      keywords = {science fiction, classic}, pagetotal = 412, language = {english}}
+@book{pratchett1983, title = "The Colour of " # {Magic}, author = {Pratchett, Terry},
+      date = {1983-11-24}, address = ny}
+@article{turing1950, title = {Computing Machinery and Intelligence}, author = {Turing, Alan}}
+@book{anonymous, title = {Beowulf}}
+"#,
+    );
+    let marc = folder.join("library.xml").display().to_string();
+    let _ = fs::write(
+        &marc,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<marc:collection xmlns:marc="http://www.loc.gov/MARC21/slim">
+  <marc:record>
+    <marc:leader>00000cam a2200000 a 4500</marc:leader>
+    <marc:controlfield tag="001">ocm00012345</marc:controlfield>
+    <marc:controlfield tag="008">751104s1937    enk           000 1 eng d</marc:controlfield>
+    <marc:datafield tag="100" ind1="1" ind2=" "><marc:subfield code="a">Tolkien, J. R. R.,</marc:subfield></marc:datafield>
+    <marc:datafield tag="245" ind1="1" ind2="4">
+      <marc:subfield code="a">The hobbit :</marc:subfield>
+      <marc:subfield code="b">or, There and back again /</marc:subfield>
+    </marc:datafield>
+    <marc:datafield tag="300" ind1=" " ind2=" "><marc:subfield code="a">310 p. ;</marc:subfield></marc:datafield>
+    <marc:datafield tag="650" ind1=" " ind2="0"><marc:subfield code="a">Fantasy fiction.</marc:subfield></marc:datafield>
+  </marc:record>
+  <marc:record>
+    <marc:leader>00000nim a2200000 a 4500</marc:leader>
+    <marc:controlfield tag="001">ocm00067890</marc:controlfield>
+    <marc:datafield tag="245" ind1="0" ind2="0"><marc:subfield code="a">Dune &amp; more.</marc:subfield></marc:datafield>
+    <marc:datafield tag="264" ind1=" " ind2="1"><marc:subfield code="c">[2007]</marc:subfield></marc:datafield>
+    <marc:datafield tag="300" ind1=" " ind2=" "><marc:subfield code="a">1 audio disc (21 hr., 2 min.)</marc:subfield></marc:datafield>
+  </marc:record>
+  <marc:record>
+    <marc:leader>00000cem a2200000 a 4500</marc:leader>
+    <marc:controlfield tag="001">ocm00099999</marc:controlfield>
+    <marc:datafield tag="245" ind1="0" ind2="0"><marc:subfield code="a">Map of Middle-earth</marc:subfield></marc:datafield>
+  </marc:record>
+</marc:collection>
+"#,
+    );
+    let opml = folder.join("subscriptions.opml").display().to_string();
+    let _ = fs::write(
+        &opml,
+        r#"<?xml version="1.0"?>
+<opml version="2.0">
+  <head><title>Podcast subscriptions</title></head>
+  <body>
+    <outline text="History">
+      <outline type="rss" text="Hardcore History" xmlUrl="https://example.com/hh.xml" category="/Society/Politics"/>
+      <outline type="rss" text="The Rest Is History" xmlUrl="https://example.com/rih.xml" language="en"/>
+    </outline>
+    <outline type="rss" text="Good Podcast Bonus" xmlUrl="https://example.com/bonus.xml"/>
+    <outline text="Bookmarked page" htmlUrl="https://example.com/"/>
+  </body>
+</opml>
+"#,
+    );
+    for path in [&bibtex, &marc, &opml] {
+        match catalog.import(path) {
+            Ok(report) => {
+                println!("{}: {} imported", path, report.imported.len());
+                for id in &report.imported {
+                    if let Ok(media) = catalog.get(*id) {
+                        println!("  {} {}", id, media.description());
+                    }
+                }
+                for skipped in &report.skipped {
+                    println!(
+                        "  skipped record {} ({}): {}",
+                        skipped.record, skipped.label, skipped.reason
+                    );
+                }
+            }
+            Err(error) => println!("Error: {}", error),
+        }
+    }
+    let _ = fs::write(&bibtex, "@book{broken, title = {Never closed}\n");
+    println!("{}: {:?}", bibtex, catalog.import(&bibtex).map(|_| ()));
+    println!(
+        "Importing a .txt: {:?}",
+        catalog.import("notes.txt").map(|_| ())
+    );
+
+    // Kinds of media from other crates: stored, searched and saved like the others
+    let comic = catalog.add(Media::Custom(Box::new(Comic {
+        title: String::from("Watchmen"),
+        writer: String::from("Alan Moore"),
+        issue: 1,
+        // A ';' in a genre doesn't split it in two once saved as CSV
+        meta: Metadata::new()
+            .with_year(1986)
+            .with_pages(28)
+            .with_genres(&["crime; mystery", "superhero"]),
+    })));
+    if let Ok(media) = catalog.get(comic) {
+        println!("{} {} (kind {})", comic, media.description(), media.kind());
+    }
+    // Built-in or not, every kind is a MediaItem: only podcasts and comics have fields of their own
+    let fields: Vec<_> = catalog
+        .entries()
+        .filter_map(|(_, media)| media.item())
+        .map(|item| item.fields())
+        .filter(|fields| !fields.is_empty())
+        .collect();
+    println!("Fields of their own: {:?}", fields);
+    println!(
+        "Comics: {:?}, searching moore: {:?}",
+        catalog
+            .query()
+            .kind(MediaKind::Custom("comic".into()))
+            .run()
+            .map(|media| media.title())
+            .collect::<Vec<_>>(),
+        catalog
+            .search("moore")
+            .map(|hits| hits.iter().map(|hit| hit.id).collect::<Vec<_>>())
+    );
+    let mut kinds = MediaRegistry::new();
+    println!(
+        "Registering comic: {:?}, again: {:?}, book: {:?}",
+        kinds.register("comic", Comic::build),
+        kinds.register("comic", Comic::build),
+        kinds.register("book", Comic::build)
+    );
+    for name in ["comics.json", "comics.csv"] {
+        let path = folder.join(name).display().to_string();
+        let result = catalog
+            .save(&path)
+            .and_then(|_| Catalog::load_with(&path, &kinds));
+        match result {
+            Ok(loaded) => println!(
+                "{}: same items once loaded: {}",
+                path,
+                loaded.entries().eq(catalog.entries())
+            ),
+            Err(error) => println!("Error: {}", error),
+        }
+        // Without the registry, there's no telling what a comic is: it's kept as it was
+        // saved, and saved back the same way
+        let again = folder.join(format!("again-{}", name)).display().to_string();
+        let result = Catalog::load(&path).and_then(|loaded| {
+            let description = loaded.get(comic).map(|media| media.description());
+            loaded.save(&again)?;
+            Ok((description, Catalog::load_with(&again, &kinds)?))
+        });
+        match result {
+            Ok((description, reloaded)) => println!(
+                "{} without the comic kind: {:?}, saved back the same: {}",
+                path,
+                description,
+                reloaded.entries().eq(catalog.entries())
+            ),
+            Err(error) => println!("Error: {}", error),
+        }
+    }
+
+    // Series: a TV show with seasons, a collection, and a podcast's episodes in order
+    let show = catalog.create_series("Good Show", SeriesKind::Series);
+    let mut episodes = vec![];
+    for (season, episode) in [(2, 1), (1, 2), (1, 1)] {
+        let id = catalog.add(Media::Movie(Movie {
+            title: format!("Good Show, season {} episode {}", season, episode),
+            director: String::from("Good Director"),
+            meta: Metadata::new().with_minutes(42),
+        }));
+        match catalog.add_to_series(show, id, Some(season), episode) {
+            Ok(part) => println!("{} is {} of {}", id, part, show),
+            Err(error) => println!("Error: {}", error),
+        }
+        episodes.push(id);
+    }
+    println!(
+        "Adding {} again: {:?}",
+        episodes[0],
+        catalog.add_to_series(show, episodes[0], Some(2), 1)
+    );
+    if let Err(error) = catalog.add_to_series(show, book_id, Some(1), 1) {
+        println!("Adding {} as S01E01: {}", book_id, error);
+    }
+    let _ = catalog.append_to_series(show, book_id, Some(2));
+    println!(
+        "Removed from the show: {:?}",
+        catalog
+            .remove_from_series(show, book_id)
+            .map(|part| part.to_string())
+    );
+    if let Ok(series) = catalog.series(show) {
+        println!(
+            "{} ({:?}): {} episodes, seasons {:?}",
+            series.name,
+            series.kind,
+            series.len(),
+            series.seasons()
+        );
+    }
+    if let Ok(first_season) = catalog.season(show, 1) {
+        for (part, media) in first_season {
+            println!("  {}: {}", part, media.title());
+        }
+    }
+    let mut watched = HashSet::new();
+    loop {
+        match catalog.next_unwatched(show, &watched) {
+            Ok(Some((part, media))) => {
+                println!("Next to watch: {} {}", part, media.title());
+                watched.insert(part.id);
+            }
+            Ok(None) => {
+                println!("All caught up on {}", show);
+                break;
+            }
+            Err(error) => {
+                println!("Error: {}", error);
+                break;
+            }
+        }
+    }
+
+    let favorites = catalog.create_series("Favorites", SeriesKind::Collection);
+    let _ = catalog.append_to_series(favorites, episodes[2], None);
+    let _ = catalog.append_to_series(favorites, movie_id, None);
+    println!(
+        "Placeholder in a collection: {:?}",
+        catalog.append_to_series(favorites, placeholder_id, None)
+    );
+    for (series, part) in catalog.series_of(episodes[2]) {
+        println!("{} is {} of {}", episodes[2], part, series);
+    }
+    let podcast = catalog.podcast_series("good podcast");
+    if let Ok(items) = catalog.in_series(podcast) {
+        for (part, media) in items {
+            println!("{}: {}", part, media.description());
+        }
+    }
+    let _ = catalog.remove(episodes[2]);
+    println!(
+        "Once {} is removed, {} has {:?} parts",
+        episodes[2],
+        favorites,
+        catalog.series(favorites).map(|series| series.parts().len())
+    );
+    println!(
+        "Deleted: {:?}, {} series left: {:?}",
+        catalog.delete_series(favorites).map(|series| series.name),
+        catalog.all_series().count(),
+        catalog.series(favorites).map(|series| series.is_empty())
+    );
+    let path = env::temp_dir().join("series.csv").display().to_string();
+    match catalog.save(&path).and_then(|_| Catalog::load(&path)) {
+        Ok(mut loaded) => println!(
+            "{}: same series once loaded: {}, the next one is still {}",
+            path,
+            loaded.all_series().eq(catalog.all_series()),
+            loaded.create_series("Another Show", SeriesKind::Series)
+        ),
+        Err(error) => println!("Error: {}", error),
+    }
+
+    // Full-text search: words are matched whatever their form ("movies" finds "Movie")
+    catalog.enable_index();
+    catalog.add(Media::Book(Book {
+        title: String::from("The Lord of the Rings"),
+        author: String::from("J.R.R. Tolkien"),
+        meta: Metadata::new().with_year(1954),
+    }));
+    for text in [
+        "tolkien",
+        "good movies",
+        "\"lord of the rings\"",
+        "hobbit OR podcast",
+        "tolkien -rings",
+        "(bad OR good) AND NOT director",
+        "\"lord of",
+        "tolkien AND",
+    ] {
+        match catalog.search(text) {
+            Ok(hits) => {
+                let found: Vec<String> = hits
+                    .iter()
+                    .map(|hit| format!("{} ({:.2})", hit.id, hit.score))
+                    .collect();
+                println!("Searching {}: {:?}", text, found);
+            }
+            Err(error) => println!("Searching {}: {}", text, error),
+        }
+    }
+    catalog.disable_index();
+    println!(
+        "Same results without the index: {:?}",
+        catalog.search("tolkien").map(|hits| hits.len())
+    );
+
+    // Scanning a folder of media files: the extension gives the kind, the name gives the
+    // title (and the creator and the year, when the name follows one of the patterns)
+    let library = folder.join("media_library");
+    let _ = fs::remove_dir_all(&library);
+    for file in [
+        "Authors/J.R.R. Tolkien/The Lord of the Rings.epub",
+        "Authors/J.R.R. Tolkien/The Silmarillion (1977).epub",
+        "Books/Terry Pratchett - The Colour of Magic.mobi",
+        "Books/Ursula K. Le Guin - A Wizard of Earthsea (1968).epub",
+        "Books/Ursula K. Le Guin - A Wizard of Earthsea (1968).pdf",
+        "Books/___.epub",
+        "Movies/Alien.mkv",
+        "Movies/.hidden.mkv",
+        "Audiobooks/Project_Hail_Mary.m4b",
+        "Podcasts/Good Podcast - Episode 12.mp3",
+        "notes.txt",
+    ] {
+        let path = library.join(file);
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let _ = fs::write(&path, "");
+    }
+    let scanner = Scanner::new()
+        .with_extension("mp3", MediaKind::Podcast)
+        .with_pattern("Authors/{creator}/{title} ({year})")
+        .and_then(|scanner| scanner.with_pattern("Authors/{creator}/{title}"));
+    println!(
+        "Pattern without a title: {:?}",
+        Scanner::new()
+            .with_pattern("{creator} - {year}")
+            .map(|_| ())
+    );
+    let print_scan = |report: &ScanReport| {
+        for (what, found) in [
+            ("added", &report.added),
+            ("updated", &report.updated),
+            ("unchanged", &report.unchanged),
+        ] {
+            for found in found {
+                println!("  {} {}: {}", what, found.id, found.path);
+            }
+        }
+        for ignored in &report.ignored {
+            println!("  ignored {}: {}", ignored.path, ignored.reason);
+        }
+    };
+    if let Ok(scanner) = scanner {
+        let root = library.display().to_string();
+        match catalog.scan_directory(&root, &scanner) {
+            Ok(report) => {
+                println!("Scanning {}:", root);
+                print_scan(&report);
+                for found in &report.added {
+                    if let Ok(media) = catalog.get(found.id) {
+                        println!("  {} {}", found.id, media.description());
+                    }
+                }
+            }
+            Err(error) => println!("Error: {}", error),
+        }
+        // Scanning again only brings what's new: here, the year of a movie
+        let _ = fs::write(library.join("Movies/Alien (1979).mp4"), "");
+        match catalog.scan_directory(&root, &scanner) {
+            Ok(report) => {
+                println!(
+                    "Scanning again: {} added, {} updated, {} unchanged",
+                    report.added.len(),
+                    report.updated.len(),
+                    report.unchanged.len()
+                );
+                print_scan(&ScanReport {
+                    unchanged: vec![],
+                    ..report
+                });
+            }
+            Err(error) => println!("Error: {}", error),
+        }
+        println!(
+            "Scanning a missing folder: {:?}",
+            catalog
+                .scan_directory("missing_folder", &scanner)
+                .map(|_| ())
+        );
+    }
+
+    // Browsing page by page: the cursor remembers where the last page ended, not a position,
+    // so items added or removed in between don't make pages skip or repeat items
+    let mut cursor: Option<Cursor> = None;
+    let mut seen = vec![];
+    let mut removed = None;
+    loop {
+        let page = match catalog.page(cursor.as_ref(), 6, SortBy::Title) {
+            Ok(page) => page,
+            Err(error) => {
+                println!("Error: {}", error);
+                break;
+            }
+        };
+        let items: Vec<String> = page
+            .items
+            .iter()
+            .map(|(id, media)| format!("{} {}", id, media.title()))
+            .collect();
+        println!("Page: {:?}", items);
+        seen.extend(page.items.iter().map(|(id, _)| *id));
+        let Some(next) = page.next else {
+            break;
+        };
+        // Passed around as text, like an HTTP layer would
+        println!("  next cursor: {}", next);
+        cursor = Cursor::parse(&next.to_string()).ok();
+
+        if removed.is_none() {
+            // One already seen, one not seen yet, and one added before the cursor
+            let last_seen = seen[seen.len() - 1];
+            let upcoming = catalog
+                .page(cursor.as_ref(), 1, SortBy::Title)
+                .ok()
+                .and_then(|page| page.items.first().map(|(id, _)| *id));
+            let _ = catalog.remove(last_seen);
+            if let Some(upcoming) = upcoming {
+                let _ = catalog.remove(upcoming);
+            }
+            catalog.add(Media::Audiobook(Audiobook {
+                title: String::from("Aardvarks"),
+                meta: Metadata::new(),
+            }));
+            removed = Some((last_seen, upcoming));
+        }
+    }
+    let mut unique = seen.clone();
+    unique.sort();
+    unique.dedup();
+    println!(
+        "{} items seen, none twice: {}, removed before being seen: {:?}",
+        seen.len(),
+        unique.len() == seen.len(),
+        removed.and_then(|(_, upcoming)| upcoming)
+    );
+    println!(
+        "Cursor for another order: {:?}",
+        cursor.map(|cursor| catalog.page(Some(&cursor), 6, SortBy::Year).map(|_| ()))
+    );
+    println!("Not a cursor: {:?}", Cursor::parse("page=2"));
+
+    // Tags: the user's own labels, queried with AND or OR
+    // (a placeholder can't be tagged)
+    let ids: Vec<MediaId> = catalog
+        .entries()
+        .filter(|(_, media)| **media != Media::Placeholder)
+        .map(|(id, _)| id)
+        .take(4)
+        .collect();
+    for (id, tags) in ids.iter().zip([
+        vec!["Favorite", "kids"],
+        vec!["  favorite "],
+        vec!["kids", "to share"],
+        vec!["Comfort"],
+    ]) {
+        for tag in tags {
+            let _ = catalog.tag(*id, tag);
+        }
+    }
+    println!(
+        "Tagging {} twice: {:?}",
+        ids[0],
+        catalog.tag(ids[0], "KIDS")
+    );
+    println!("Tags: {:?}", catalog.tags());
+    println!("Tags of {}: {:?}", ids[0], catalog.tags_of(ids[0]));
+    let titles = |items: Vec<(MediaId, &Media)>| -> Vec<String> {
+        items
+            .iter()
+            .map(|(id, media)| format!("{} {}", id, media.title()))
+            .collect()
+    };
+    println!(
+        "favorite AND kids: {:?}",
+        titles(catalog.tagged_all(&["favorite", "kids"]))
+    );
+    println!(
+        "favorite OR kids: {:?}",
+        titles(catalog.tagged_any(&["favorite", "kids"]))
+    );
+    println!(
+        "Renaming 'to share' to 'lend': {:?}, 'kids' to 'favorite': {:?}",
+        catalog.rename_tag("to share", "lend"),
+        catalog.rename_tag("kids", "favorite")
+    );
+    println!(
+        "Merging 'comfort' into 'favorite': {:?}, again: {:?}",
+        catalog.merge_tags("comfort", "favorite"),
+        catalog.merge_tags("comfort", "favorite")
+    );
+    println!("Untagging {}: {:?}", ids[2], catalog.untag(ids[2], "lend"));
+    println!("Tags now: {:?}", catalog.tags());
+
+    // Shelves: named lists, in the order the user picks
+    println!(
+        "Creating 'To read': {:?}, 'to read' again: {:?}",
+        catalog.create_shelf("To read"),
+        catalog.create_shelf("to read")
+    );
+    let _ = catalog.create_shelf("Empty for now");
+    for id in &ids {
+        let _ = catalog.add_to_shelf("To read", *id, None);
+    }
+    println!(
+        "Moving {} first: {:?}, adding {} again: {:?}",
+        ids[3],
+        catalog.move_on_shelf("to read", ids[3], 0),
+        ids[0],
+        catalog.add_to_shelf("To read", ids[0], None)
+    );
+    println!(
+        "To read: {:?}",
+        catalog.shelf("To read").map(titles).unwrap_or_default()
+    );
+    println!(
+        "Shelves: {:?}, {} is on {:?}",
+        catalog.shelves(),
+        ids[1],
+        catalog.shelves_of(ids[1])
+    );
+    println!("Missing shelf: {:?}", catalog.shelf("Wishlist").map(|_| ()));
+
+    // Tags and shelves are saved with the catalog (next to it, for a CSV file)
+    for name in ["tagged.json", "tagged.csv"] {
+        let path = folder.join(name).display().to_string();
+        let result = catalog
+            .save(&path)
+            .and_then(|_| Catalog::load_with(&path, &kinds));
+        match result {
+            Ok(loaded) => println!(
+                "{}: same tags once loaded: {}, same shelves: {}, same order: {}",
+                path,
+                loaded.tags() == catalog.tags(),
+                loaded.shelves() == catalog.shelves(),
+                loaded.shelf("To read") == catalog.shelf("To read")
+            ),
+            Err(error) => println!("Error: {}", error),
+        }
+    }
+
+    // Statistics: as a report, or as JSON for other programs
+    let stats = catalog.stats();
+    println!("{}", stats);
+    println!("{}", stats.to_json());
+
+    // There's no 41st item: instead of unwrap() or expect(), which would panic,
+    // the missing item is either replaced or reported
+    let item_unwrap_or = catalog.get_by_index(40);
+    let placeholder = Media::Placeholder;
 
     /*
     Will generate:
     Placeholder
      */
     println!("{:#?}", item_unwrap_or.unwrap_or(&placeholder)); // Will return Placeholder
+
+    // Like the catalog binary: an error message, and a non-zero exit code
+    match catalog.get_by_index(40) {
+        Some(item) => println!("{:#?}", item),
+        None => {
+            eprintln!("error: there's no item at index 40");
+            process::exit(1);
+        }
+    }
 }
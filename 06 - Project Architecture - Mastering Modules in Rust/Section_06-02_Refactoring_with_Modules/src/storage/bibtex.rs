@@ -0,0 +1,276 @@
+// BibTeX (.bib): entries like
+//   @book{tolkien1937, title = {The {Hobbit}}, author = "Tolkien, J. R. R.", year = 1937}
+// Only books are imported; the other entries (@article, @misc...) are reported as skipped.
+
+use std::collections::HashMap;
+
+use super::StorageError;
+use super::import::{Entry, first_number, language, natural_name, year};
+use crate::content::media::{Book, Media, Metadata};
+
+// The entry types that describe a whole book
+const BOOK_TYPES: [&str; 3] = ["book", "mvbook", "booklet"];
+
+pub fn read(text: &str) -> Result<Vec<Entry>, StorageError> {
+    let mut parser = Parser {
+        text,
+        position: 0,
+        strings: HashMap::new(),
+    };
+
+    let mut entries = vec![];
+    while let Some(start) = parser.rest().find('@') {
+        parser.position += start + 1;
+        let result = parser.entry();
+        match result {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(message) => {
+                let line = text[..parser.position.min(text.len())]
+                    .matches('\n')
+                    .count()
+                    + 1;
+                return Err(StorageError::BibTex { line, message });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    // Defined with @string{name = "value"}, used as 'name' in values
+    strings: HashMap<String, String>,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.position = self.text.len() - trimmed.len();
+    }
+
+    fn word(&mut self) -> String {
+        self.skip_whitespace();
+        let length = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || "_-:.+/'".contains(c)))
+            .unwrap_or(self.rest().len());
+        let word = self.rest()[..length].to_string();
+        self.position += length;
+        word
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        let c = self.rest().chars().next()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    // After the '@'. None for what isn't an entry (@comment, @string, @preamble).
+    fn entry(&mut self) -> Result<Option<Entry>, String> {
+        let kind = self.word().to_lowercase();
+        let close = match self.next_char() {
+            Some('{') => '}',
+            Some('(') => ')',
+            _ => return Err(format!("@{} should be followed by '{{'", kind)),
+        };
+
+        match kind.as_str() {
+            "comment" | "preamble" => {
+                self.balanced(close)?;
+                return Ok(None);
+            }
+            "string" => {
+                let (name, value) = self.field()?;
+                self.strings.insert(name, value);
+                self.expect(close)?;
+                return Ok(None);
+            }
+            _ => {}
+        }
+
+        let key = self.word();
+        let mut fields = HashMap::new();
+        loop {
+            match self.next_char() {
+                Some(c) if c == close => break,
+                Some(',') => {}
+                _ => return Err(format!("entry '{}' isn't closed", key)),
+            }
+            self.skip_whitespace();
+            // A comma after the last field is allowed
+            if self.rest().starts_with(close) {
+                continue;
+            }
+            let (name, value) = self.field()?;
+            fields.insert(name, value);
+        }
+
+        let media = if BOOK_TYPES.contains(&kind.as_str()) {
+            book(&fields)
+        } else {
+            Err(format!("a @{} isn't a book", kind))
+        };
+        Ok(Some(Entry { label: key, media }))
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next_char() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(format!("expected '{}'", expected)),
+        }
+    }
+
+    // name = value # value # ...
+    fn field(&mut self) -> Result<(String, String), String> {
+        let name = self.word().to_lowercase();
+        if name.is_empty() {
+            return Err(String::from("expected a field name"));
+        }
+        self.expect('=')?;
+
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            match self.rest().chars().next() {
+                Some('{') => {
+                    self.position += 1;
+                    value.push_str(self.balanced('}')?);
+                }
+                Some('"') => {
+                    self.position += 1;
+                    value.push_str(self.quoted()?);
+                }
+                Some(_) => {
+                    let word = self.word();
+                    if word.is_empty() {
+                        return Err(format!("expected a value for '{}'", name));
+                    }
+                    // A number, or the name of a @string
+                    let known = self.strings.get(&word.to_lowercase()).cloned();
+                    value.push_str(&known.unwrap_or(word));
+                }
+                None => return Err(format!("expected a value for '{}'", name)),
+            }
+
+            self.skip_whitespace();
+            if !self.rest().starts_with('#') {
+                return Ok((name, clean(&value)));
+            }
+            self.position += 1;
+        }
+    }
+
+    // Everything up to the matching 'close' (nested braces included), which is skipped
+    fn balanced(&mut self, close: char) -> Result<&str, String> {
+        let start = self.position;
+        let mut depth = 0;
+        for (index, c) in self.rest().char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                c if c == close && depth == 0 => {
+                    self.position = start + index + 1;
+                    return Ok(&self.text[start..start + index]);
+                }
+                _ => {}
+            }
+        }
+        Err(format!("missing '{}'", close))
+    }
+
+    // Up to the closing quote: quotes between braces don't count
+    fn quoted(&mut self) -> Result<&str, String> {
+        let start = self.position;
+        let mut depth = 0;
+        for (index, c) in self.rest().char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' if depth == 0 => {
+                    self.position = start + index + 1;
+                    return Ok(&self.text[start..start + index]);
+                }
+                _ => {}
+            }
+        }
+        Err(String::from("missing '\"'"))
+    }
+}
+
+// Removes what's only there for LaTeX: "The {Hobbit}" -> "The Hobbit",
+// "Caf{\'e} \& Co" -> "Cafe & Co", "\emph{Dune}" -> "Dune"
+fn clean(value: &str) -> String {
+    let mut cleaned = String::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => {}
+            '~' => cleaned.push(' '),
+            '\\' => match chars.peek() {
+                Some(&c) if "&%$_#{}".contains(c) => {
+                    cleaned.push(c);
+                    chars.next();
+                }
+                // A command: its name goes, what it applies to stays
+                Some(c) if c.is_alphabetic() => {
+                    while chars.peek().is_some_and(|c| c.is_alphabetic()) {
+                        chars.next();
+                    }
+                }
+                // An accent: the letter stays
+                _ => {
+                    chars.next();
+                }
+            },
+            c => cleaned.push(c),
+        }
+    }
+
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn book(fields: &HashMap<String, String>) -> Result<Media, String> {
+    let field = |name: &str| fields.get(name).filter(|value| !value.is_empty());
+    let title = field("title").ok_or_else(|| String::from("no title"))?;
+    let authors = field("author")
+        .or_else(|| field("editor"))
+        .ok_or_else(|| String::from("no author or editor"))?;
+
+    // "Tolkien, J. R. R. and Carpenter, Humphrey"
+    let author: Vec<String> = authors.split(" and ").map(natural_name).collect();
+
+    let mut meta = Metadata::new();
+    meta.year = field("year")
+        .or_else(|| field("date"))
+        .and_then(|y| year(y));
+    meta.language = field("language").and_then(|l| language(l));
+    if let Some(keywords) = field("keywords") {
+        let keywords: Vec<&str> = keywords
+            .split([',', ';'])
+            .map(str::trim)
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+        meta = meta.with_genres(&keywords);
+    }
+    // 'pages' is usually a range of pages in a book ("12--25"): only the total counts
+    let pages = field("pagetotal")
+        .or_else(|| field("pages").filter(|pages| pages.chars().all(|c| c.is_ascii_digit())));
+    if let Some(pages) = pages.and_then(|pages| first_number(pages)) {
+        meta = meta.with_pages(pages);
+    }
+
+    Ok(Media::Book(Book {
+        title: title.to_string(),
+        author: author.join(", "),
+        meta,
+    }))
+}
@@ -0,0 +1,70 @@
+// Comma separated values (RFC 4180): a field containing a comma, a quote or a line break
+// is written between quotes, and its quotes are doubled ("say ""hi""").
+
+pub fn write_row(fields: &[String]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+
+    format!("{}\n", fields.join(","))
+}
+
+// Every row with its fields. On error: the line where it happened, and what went wrong.
+pub fn parse(text: &str) -> Result<Vec<Vec<String>>, (usize, String)> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            '"' => return Err((line, String::from("unexpected quote inside a field"))),
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err((line, String::from("unterminated quoted field")));
+    }
+    // The last line doesn't have to end with a line break
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    // Blank lines don't count as rows
+    rows.retain(|row| !(row.len() == 1 && row[0].is_empty()));
+    Ok(rows)
+}
@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::Path;
+
+use super::{StorageError, bibtex, marc, opml};
+use crate::content::catalog::{Catalog, MediaId};
+use crate::content::media::Media;
+
+// Formats used by other tools, that can be read (but not written)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    // .bib: books only
+    BibTex,
+    // .xml or .marcxml: the MARC 21 records of library catalogs
+    MarcXml,
+    // .opml: podcast subscriptions, as exported by podcast apps
+    Opml,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &str) -> Result<Self, StorageError> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        match extension.as_deref() {
+            Some("bib") => Ok(ImportFormat::BibTex),
+            Some("xml" | "marcxml") => Ok(ImportFormat::MarcXml),
+            Some("opml") => Ok(ImportFormat::Opml),
+            _ => Err(StorageError::UnsupportedFormat(path.to_string())),
+        }
+    }
+}
+
+// A record that couldn't be turned into a Media
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    // Starts at 1, in the order of the file
+    pub record: usize,
+    // Whatever identifies it best: a citation key, a control number, a title...
+    pub label: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: Vec<MediaId>,
+    pub skipped: Vec<Skipped>,
+}
+
+// One record of an imported file, mapped or not
+pub struct Entry {
+    pub label: String,
+    pub media: Result<Media, String>,
+}
+
+impl Catalog {
+    // Adds every record of the file that can be mapped to a Media (they get new ids),
+    // and reports the others. When the file itself can't be read, nothing is added.
+    pub fn import(&mut self, path: &str) -> Result<ImportReport, StorageError> {
+        let format = ImportFormat::from_path(path)?;
+        let text = fs::read_to_string(path).map_err(|error| StorageError::Io {
+            path: path.to_string(),
+            message: error.to_string(),
+        })?;
+
+        let entries = match format {
+            ImportFormat::BibTex => bibtex::read(&text)?,
+            ImportFormat::MarcXml => marc::read(&text)?,
+            ImportFormat::Opml => opml::read(&text)?,
+        };
+
+        let mut report = ImportReport::default();
+        for (index, entry) in entries.into_iter().enumerate() {
+            match entry.media {
+                Ok(media) => report.imported.push(self.add(media)),
+                Err(reason) => report.skipped.push(Skipped {
+                    record: index + 1,
+                    label: entry.label,
+                    reason,
+                }),
+            }
+        }
+        Ok(report)
+    }
+}
+
+// "Tolkien, J. R. R." -> "J. R. R. Tolkien", like the names typed in by hand
+pub fn natural_name(name: &str) -> String {
+    match name.split_once(',') {
+        Some((last, first)) if !first.trim().is_empty() => {
+            format!("{} {}", first.trim(), last.trim())
+        }
+        _ => name.trim().trim_end_matches(',').to_string(),
+    }
+}
+
+// The first four digits in a row: "c1937.", "1937-09-21" and "[1937?]" all give 1937
+pub fn year(text: &str) -> Option<u16> {
+    let digits: Vec<char> = text.chars().collect();
+    digits
+        .windows(4)
+        .find(|window| window.iter().all(|c| c.is_ascii_digit()))
+        .and_then(|window| window.iter().collect::<String>().parse().ok())
+}
+
+// The first number: "310 p. ;" gives 310
+pub fn first_number(text: &str) -> Option<u32> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+// Languages come as MARC codes ("eng") or names ("english"): the common ones
+// become the two letter codes used elsewhere in the catalog ("en")
+pub fn language(text: &str) -> Option<String> {
+    let text = text.trim().to_lowercase();
+    let code = match text.as_str() {
+        "" => return None,
+        "eng" | "english" => "en",
+        "fre" | "fra" | "french" | "francais" => "fr",
+        "ger" | "deu" | "german" | "deutsch" => "de",
+        "spa" | "spanish" | "espanol" => "es",
+        "ita" | "italian" | "italiano" => "it",
+        "jpn" | "japanese" => "ja",
+        _ => &text,
+    };
+    Some(code.to_string())
+}
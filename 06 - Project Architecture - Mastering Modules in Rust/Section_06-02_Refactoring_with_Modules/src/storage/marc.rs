@@ -0,0 +1,165 @@
+// MARCXML: the MARC 21 records of library catalogs, one <record> each
+// (in a <collection>, or alone). Fields are numbered:
+//   245 $a $b  title and subtitle        100 $a  author (110: an organization, 700: others)
+//   264/260 $c year of publication       300 $a  extent ("310 p.")
+//   650/655 $a subjects and genres       041 $a  language (or positions 35-37 of 008)
+// The 7th character of <leader> tells what's described: books are imported,
+// and so are audiobooks (spoken sound recordings); the rest is reported as skipped.
+
+use super::StorageError;
+use super::import::{Entry, first_number, language, natural_name, year};
+use super::xml::{self, Element};
+use crate::content::media::{Audiobook, Book, Media, Metadata};
+
+pub fn read(text: &str) -> Result<Vec<Entry>, StorageError> {
+    let root = xml::parse(text).map_err(|(line, message)| StorageError::Xml { line, message })?;
+
+    let records: Vec<&Element> = if root.name == "record" {
+        vec![&root]
+    } else {
+        root.named("record").collect()
+    };
+    if records.is_empty() {
+        return Err(StorageError::Xml {
+            line: 1,
+            message: String::from("no MARC <record> in the file"),
+        });
+    }
+
+    Ok(records.into_iter().map(entry).collect())
+}
+
+fn entry(record: &Element) -> Entry {
+    let title = subfields(record, "245", &["a", "b"]);
+    // The control number (001) identifies a record; not every file has one
+    let label = control_field(record, "001")
+        .or_else(|| (!title.is_empty()).then(|| title.join(" ")))
+        .unwrap_or_else(|| String::from("(no title)"));
+
+    Entry {
+        label,
+        media: media(record, title),
+    }
+}
+
+fn media(record: &Element, title: Vec<String>) -> Result<Media, String> {
+    let kind = record
+        .named("leader")
+        .next()
+        .and_then(|leader| leader.text().chars().nth(6))
+        .unwrap_or('a');
+    if !matches!(kind, 'a' | 't' | 'i') {
+        return Err(format!(
+            "record type '{}' is neither a book nor an audiobook",
+            kind
+        ));
+    }
+    if title.is_empty() {
+        return Err(String::from("no title (245 $a)"));
+    }
+    let title = title.join(": ");
+
+    let fixed = control_field(record, "008").unwrap_or_default();
+    let mut meta = Metadata::new();
+    meta.year = ["264", "260"]
+        .iter()
+        .find_map(|tag| subfields(record, tag, &["c"]).first().and_then(|c| year(c)))
+        .or_else(|| fixed.get(7..11).and_then(year));
+    meta.language = subfields(record, "041", &["a"])
+        .first()
+        .cloned()
+        .or_else(|| fixed.get(35..38).map(str::to_string))
+        .and_then(|code| language(&code));
+    let mut genres = subfields(record, "655", &["a"]);
+    genres.extend(subfields(record, "650", &["a"]));
+    let genres: Vec<&str> = genres.iter().map(String::as_str).collect();
+    meta = meta.with_genres(&genres);
+
+    let extent = subfields(record, "300", &["a"]).join(" ");
+    if kind == 'i' {
+        // "1 audio disc (9 hr., 30 min.)": only minutes are kept, when there are any
+        if let Some(minutes) = minutes(&extent) {
+            meta = meta.with_minutes(minutes);
+        }
+        return Ok(Media::Audiobook(Audiobook { title, meta }));
+    }
+    if let Some(pages) = first_number(&extent).filter(|_| extent.contains('p')) {
+        meta = meta.with_pages(pages);
+    }
+
+    let author = ["100", "110", "700"]
+        .iter()
+        .find_map(|tag| subfields(record, tag, &["a"]).into_iter().next())
+        .ok_or_else(|| String::from("no author (100 $a)"))?;
+
+    Ok(Media::Book(Book {
+        title,
+        author: natural_name(&author),
+        meta,
+    }))
+}
+
+// "9 hr., 30 min." -> 570
+fn minutes(extent: &str) -> Option<u32> {
+    let words: Vec<&str> = extent
+        .split([' ', '(', ','])
+        .filter(|w| !w.is_empty())
+        .collect();
+    let mut total = None;
+    for pair in words.windows(2) {
+        let Ok(number) = pair[0].parse::<u32>() else {
+            continue;
+        };
+        if pair[1].starts_with("hr") || pair[1].starts_with("hour") {
+            total = Some(total.unwrap_or(0) + number * 60);
+        } else if pair[1].starts_with("min") {
+            total = Some(total.unwrap_or(0) + number);
+        }
+    }
+    total
+}
+
+fn control_field(record: &Element, tag: &str) -> Option<String> {
+    record
+        .named("controlfield")
+        .find(|field| field.attribute("tag") == Some(tag))
+        .map(|field| field.text().trim().to_string())
+}
+
+// The subfields of the field with that tag, punctuation removed.
+// Subjects (650) and genres (655) are usually repeated: all of them count.
+fn subfields(record: &Element, tag: &str, codes: &[&str]) -> Vec<String> {
+    let fields = if matches!(tag, "650" | "655") {
+        usize::MAX
+    } else {
+        1
+    };
+
+    record
+        .named("datafield")
+        .filter(|field| field.attribute("tag") == Some(tag))
+        .take(fields)
+        .flat_map(|field| field.named("subfield"))
+        .filter(|subfield| {
+            subfield
+                .attribute("code")
+                .is_some_and(|code| codes.contains(&code))
+        })
+        .map(|subfield| trim_punctuation(&subfield.text()))
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+// MARC keeps the punctuation of printed cards: "The hobbit :", "Tolkien, J. R. R.,", "Fantasy."
+// A final period is only removed after a word (not after an initial, as in "J. R. R.")
+fn trim_punctuation(value: &str) -> String {
+    let value = value
+        .trim()
+        .trim_end_matches([' ', ',', '/', ':', ';', '=']);
+    let last_word = value.rsplit([' ', ',']).next().unwrap_or(value);
+    if value.ends_with('.') && last_word.len() > 2 {
+        value[..value.len() - 1].to_string()
+    } else {
+        value.to_string()
+    }
+}
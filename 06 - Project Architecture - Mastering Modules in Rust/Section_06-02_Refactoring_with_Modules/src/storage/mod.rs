@@ -0,0 +1,1721 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::content::catalog::{Catalog, MediaId};
+use crate::content::date::Date;
+use crate::content::item::{MediaRegistry, SavedItem, UnknownItem};
+use crate::content::lending::Loan;
+use crate::content::media::{Audiobook, Book, Length, Media, MediaKind, Metadata, Movie, Podcast};
+use crate::content::progress::{Progress, ProgressUpdate};
+use crate::content::series::{Part, SeriesId, SeriesKind};
+use crate::content::stats::Stats;
+use crate::content::tags::TagError;
+
+mod bibtex;
+mod csv;
+mod import;
+mod json;
+mod marc;
+mod opml;
+mod scan;
+mod xml;
+
+pub use import::{ImportFormat, ImportReport, Skipped};
+pub use scan::{Found, Ignored, ScanReport, Scanner};
+
+use json::Json;
+
+// Written in every JSON file. Files are read whatever their version:
+// fields we don't know about are simply ignored.
+pub const FORMAT_VERSION: i64 = 1;
+
+// The columns of a CSV file, in the order we write them
+const COLUMNS: [&str; 12] = [
+    "kind", "id", "title", "creator", "episode", "year", "genres", "pages", "minutes", "language",
+    "rating", "fields",
+];
+
+// The columns of the progress file that goes along with a CSV catalog
+const PROGRESS_COLUMNS: [&str; 5] = ["user", "id", "progress", "value", "on"];
+
+// The columns of the tags file that goes along with a CSV catalog: one row per item
+// ("tag" or "shelf" in 'list'), and a row without an id for an empty shelf
+const TAGS_COLUMNS: [&str; 3] = ["list", "name", "id"];
+
+// The columns of the loans file that goes along with a CSV catalog: "loan" or "hold"
+// in 'list', and the holds of an item in the order they were placed
+const LENDING_COLUMNS: [&str; 5] = ["list", "id", "borrower", "checked_out", "due"];
+
+// The columns of the series file that goes along with a CSV catalog: one row per part,
+// and a row without an id for an empty series. Its next id line is the next series id.
+const SERIES_COLUMNS: [&str; 6] = ["series", "name", "kind", "id", "season", "number"];
+
+// The columns of the ratings file that goes along with a CSV catalog
+const RATINGS_COLUMNS: [&str; 3] = ["user", "id", "stars"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    Io { path: String, message: String },
+    // An extension we don't know what to do with
+    UnsupportedFormat(String),
+    Json(String),
+    Csv { line: usize, message: String },
+    // Imported files (MARCXML, OPML, BibTeX)
+    Xml { line: usize, message: String },
+    BibTex { line: usize, message: String },
+    // 'record' starts at 1
+    InvalidRecord { record: usize, message: String },
+    InvalidProgress { record: usize, message: String },
+    // Tags come first, then shelves
+    InvalidTags { record: usize, message: String },
+    // Loans come first, then holds
+    InvalidLending { record: usize, message: String },
+    InvalidSeries { record: usize, message: String },
+    InvalidRating { record: usize, message: String },
+    // A file name pattern of a Scanner
+    InvalidPattern { pattern: String, message: String },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Io { path, message } => write!(f, "{}: {}", path, message),
+            StorageError::UnsupportedFormat(path) => {
+                write!(f, "{}: this kind of file isn't supported", path)
+            }
+            StorageError::Json(message) => write!(f, "invalid JSON: {}", message),
+            StorageError::Csv { line, message } => write!(f, "line {}: {}", line, message),
+            StorageError::Xml { line, message } => {
+                write!(f, "invalid XML, line {}: {}", line, message)
+            }
+            StorageError::BibTex { line, message } => {
+                write!(f, "invalid BibTeX, line {}: {}", line, message)
+            }
+            StorageError::InvalidRecord { record, message } => {
+                write!(f, "record {}: {}", record, message)
+            }
+            StorageError::InvalidProgress { record, message } => {
+                write!(f, "progress record {}: {}", record, message)
+            }
+            StorageError::InvalidTags { record, message } => {
+                write!(f, "tag or shelf record {}: {}", record, message)
+            }
+            StorageError::InvalidLending { record, message } => {
+                write!(f, "loan or hold record {}: {}", record, message)
+            }
+            StorageError::InvalidSeries { record, message } => {
+                write!(f, "series record {}: {}", record, message)
+            }
+            StorageError::InvalidRating { record, message } => {
+                write!(f, "rating record {}: {}", record, message)
+            }
+            StorageError::InvalidPattern { pattern, message } => {
+                write!(f, "pattern '{}': {}", pattern, message)
+            }
+        }
+    }
+}
+
+impl Error for StorageError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    // Picked from the extension of the file
+    pub fn from_path(path: &str) -> Result<Self, StorageError> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        match extension.as_deref() {
+            Some("json") => Ok(Format::Json),
+            Some("csv") => Ok(Format::Csv),
+            _ => Err(StorageError::UnsupportedFormat(path.to_string())),
+        }
+    }
+}
+
+// Where the progress of a CSV catalog goes: 'catalog.csv' -> 'catalog.progress.csv',
+// its tags and shelves: 'catalog.tags.csv', its loans and holds: 'catalog.loans.csv',
+// its series: 'catalog.series.csv', and the ratings of its users: 'catalog.ratings.csv'
+// (a JSON catalog keeps them in the same file)
+fn sidecar_path(path: &str, name: &str) -> String {
+    Path::new(path)
+        .with_extension(format!("{}.csv", name))
+        .display()
+        .to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GroupKind {
+    Tag,
+    Shelf,
+}
+
+impl GroupKind {
+    fn name(self) -> &'static str {
+        match self {
+            GroupKind::Tag => "tag",
+            GroupKind::Shelf => "shelf",
+        }
+    }
+}
+
+// A tag or a shelf with its items (in the order of the shelf), as saved
+#[derive(Debug)]
+struct Group {
+    kind: GroupKind,
+    name: String,
+    items: Vec<MediaId>,
+}
+
+// Tags first, then shelves in the order they were created
+fn groups(catalog: &Catalog) -> Vec<Group> {
+    let ids = |items: Vec<(MediaId, &Media)>| items.into_iter().map(|(id, _)| id).collect();
+    let tags = catalog.tags().into_iter().map(|(tag, _)| Group {
+        kind: GroupKind::Tag,
+        name: tag.to_string(),
+        items: ids(catalog.tagged_any(&[tag])),
+    });
+    let shelves = catalog.shelves().into_iter().map(|(shelf, _)| Group {
+        kind: GroupKind::Shelf,
+        name: shelf.to_string(),
+        items: ids(catalog.shelf(shelf).unwrap_or_default()),
+    });
+    tags.chain(shelves).collect()
+}
+
+// A loan or someone waiting for an item, as saved
+#[derive(Debug)]
+enum Lent {
+    Loan { id: MediaId, loan: Loan },
+    Hold { id: MediaId, borrower: String },
+}
+
+// Loans first, then holds, both by item (and the holds of an item in order)
+fn lending(catalog: &Catalog) -> Vec<Lent> {
+    let loans = catalog
+        .all_loans()
+        .into_iter()
+        .map(|(id, loan)| Lent::Loan {
+            id,
+            loan: loan.clone(),
+        });
+    let holds = catalog.all_holds().into_iter().flat_map(|(id, borrowers)| {
+        borrowers.into_iter().map(move |borrower| Lent::Hold {
+            id,
+            borrower: borrower.to_string(),
+        })
+    });
+    loans.chain(holds).collect()
+}
+
+// A series or a collection with its parts (in order), as saved
+#[derive(Debug)]
+struct SavedSeries {
+    id: SeriesId,
+    name: String,
+    kind: SeriesKind,
+    parts: Vec<Part>,
+}
+
+fn series(catalog: &Catalog) -> Vec<SavedSeries> {
+    catalog
+        .all_series()
+        .map(|(id, series)| SavedSeries {
+            id,
+            name: series.name.clone(),
+            kind: series.kind,
+            parts: series.parts().to_vec(),
+        })
+        .collect()
+}
+
+fn series_kind_name(kind: SeriesKind) -> &'static str {
+    match kind {
+        SeriesKind::Series => "series",
+        SeriesKind::Collection => "collection",
+    }
+}
+
+// The stars a user gave to an item, as saved
+#[derive(Debug)]
+struct Rating {
+    user: String,
+    id: MediaId,
+    stars: u8,
+}
+
+fn ratings(catalog: &Catalog) -> Vec<Rating> {
+    catalog
+        .all_ratings()
+        .into_iter()
+        .map(|(user, id, stars)| Rating {
+            user: user.to_string(),
+            id,
+            stars,
+        })
+        .collect()
+}
+
+// Everything a file holds: the items, then what's known about them
+#[derive(Debug, Default)]
+struct SavedCatalog {
+    records: Vec<Record>,
+    // Missing from older files
+    next_id: Option<u64>,
+    progress: Vec<ProgressUpdate>,
+    groups: Vec<Group>,
+    lending: Vec<Lent>,
+    series: Vec<SavedSeries>,
+    // Only written once a series was created
+    next_series_id: Option<u64>,
+    ratings: Vec<Rating>,
+}
+
+impl Catalog {
+    // Only the built-in kinds are loaded as such, see 'load_with'
+    pub fn load(path: &str) -> Result<Catalog, StorageError> {
+        Catalog::load_with(path, &MediaRegistry::new())
+    }
+
+    // Items of a kind that isn't in 'kinds' (nor built-in) are loaded as an 'UnknownItem':
+    // nothing specific to their kind works, but they're saved back as they were
+    pub fn load_with(path: &str, kinds: &MediaRegistry) -> Result<Catalog, StorageError> {
+        let format = Format::from_path(path)?;
+        let text = fs::read_to_string(path).map_err(|error| StorageError::Io {
+            path: path.to_string(),
+            message: error.to_string(),
+        })?;
+
+        // Without a next id (in an older CSV file), it's deduced from the items
+        let saved = match format {
+            Format::Json => read_json(&text)?,
+            Format::Csv => {
+                let (records, next_id) = read_csv(&text)?;
+                let (series, next_series_id) = read_series_csv(path)?;
+                SavedCatalog {
+                    records,
+                    next_id,
+                    progress: read_progress_csv(path)?,
+                    groups: read_tags_csv(path)?,
+                    lending: read_lending_csv(path)?,
+                    series,
+                    next_series_id,
+                    ratings: read_ratings_csv(path)?,
+                }
+            }
+        };
+
+        let mut catalog = Catalog::new();
+        for (index, record) in saved.records.into_iter().enumerate() {
+            let invalid = |message| StorageError::InvalidRecord {
+                record: index + 1,
+                message,
+            };
+            // Items saved without an id get a new one
+            let id = record.id;
+            let media = record.into_media(kinds).map_err(invalid)?;
+            match id {
+                Some(id) => catalog
+                    .insert(MediaId(id), media)
+                    .map_err(|error| invalid(error.to_string()))?,
+                None => {
+                    catalog.add(media);
+                }
+            }
+        }
+        if let Some(next_id) = saved.next_id {
+            catalog.reserve_ids(MediaId(next_id));
+        }
+
+        // Replayed in the order they were made, so that the history is the same
+        for (index, update) in saved.progress.into_iter().enumerate() {
+            catalog
+                .record_progress(&update.user, update.id, update.progress, update.on)
+                .map_err(|error| StorageError::InvalidProgress {
+                    record: index + 1,
+                    message: error.to_string(),
+                })?;
+        }
+
+        // The same group can come in several records (one per item, in a CSV file)
+        for (index, group) in saved.groups.into_iter().enumerate() {
+            let invalid = |error: TagError| StorageError::InvalidTags {
+                record: index + 1,
+                message: error.to_string(),
+            };
+            match group.kind {
+                GroupKind::Tag => {
+                    for id in group.items {
+                        catalog.tag(id, &group.name).map_err(invalid)?;
+                    }
+                }
+                GroupKind::Shelf => {
+                    match catalog.create_shelf(&group.name) {
+                        Ok(()) | Err(TagError::ShelfExists(_)) => {}
+                        Err(error) => return Err(invalid(error)),
+                    }
+                    for id in group.items {
+                        catalog
+                            .add_to_shelf(&group.name, id, None)
+                            .map_err(invalid)?;
+                    }
+                }
+            }
+        }
+
+        // Loans before holds, whatever their order in the file: once someone is waiting
+        // for an item, only they could check it out
+        let mut lending: Vec<(usize, Lent)> = saved.lending.into_iter().enumerate().collect();
+        lending.sort_by_key(|(_, lent)| matches!(lent, Lent::Hold { .. }));
+        for (index, lent) in lending {
+            let invalid = |message: String| StorageError::InvalidLending {
+                record: index + 1,
+                message,
+            };
+            match lent {
+                Lent::Loan { id, loan } => {
+                    let days = u32::try_from(loan.checked_out.days_until(loan.due))
+                        .map_err(|_| invalid(String::from("due before it was checked out")))?;
+                    catalog
+                        .check_out(id, &loan.borrower, loan.checked_out, days)
+                        .map_err(|error| invalid(error.to_string()))?;
+                }
+                Lent::Hold { id, borrower } => {
+                    catalog
+                        .place_hold(id, &borrower)
+                        .map_err(|error| invalid(error.to_string()))?;
+                }
+            }
+        }
+
+        // Created again with the same ids (a series can come in several records, one per part
+        // in a CSV file), so they must come in the order of their ids
+        for (index, saved) in saved.series.into_iter().enumerate() {
+            let invalid = |message: String| StorageError::InvalidSeries {
+                record: index + 1,
+                message,
+            };
+            if catalog.series(saved.id).is_err() {
+                catalog.reserve_series_ids(saved.id);
+                if catalog.create_series(&saved.name, saved.kind) != saved.id {
+                    return Err(invalid(format!("{} comes after a later one", saved.id)));
+                }
+            }
+            for part in saved.parts {
+                catalog
+                    .add_to_series(saved.id, part.id, part.season, part.number)
+                    .map_err(|error| invalid(error.to_string()))?;
+            }
+        }
+        if let Some(next_series_id) = saved.next_series_id {
+            catalog.reserve_series_ids(SeriesId(next_series_id));
+        }
+
+        for (index, rating) in saved.ratings.into_iter().enumerate() {
+            catalog
+                .rate(&rating.user, rating.id, rating.stars)
+                .map_err(|error| StorageError::InvalidRating {
+                    record: index + 1,
+                    message: error.to_string(),
+                })?;
+        }
+
+        Ok(catalog)
+    }
+
+    // Either the whole new file is there, or the old one is left untouched
+    // (see 'write_all_atomically' for the files of a CSV catalog).
+    // Saved: the items, the next id, the progress, the tags, the shelves, the loans,
+    // the holds, the series, the collections and the ratings users gave.
+    pub fn save(&self, path: &str) -> Result<(), StorageError> {
+        let saved = SavedCatalog {
+            records: self
+                .entries()
+                .map(|(id, media)| Record::from_media(id, media))
+                .collect(),
+            next_id: Some(self.next_id().0),
+            progress: self.progress_updates().to_vec(),
+            groups: groups(self),
+            lending: lending(self),
+            series: series(self),
+            next_series_id: (self.next_series_id() != Catalog::new().next_series_id())
+                .then_some(self.next_series_id().0),
+            ratings: ratings(self),
+        };
+        match Format::from_path(path)? {
+            Format::Json => write_atomically(path, &write_json(&saved)),
+            Format::Csv => write_all_atomically(&[
+                (
+                    sidecar_path(path, "progress"),
+                    progress_csv(&saved.progress),
+                ),
+                (sidecar_path(path, "tags"), tags_csv(&saved.groups)),
+                (sidecar_path(path, "loans"), lending_csv(&saved.lending)),
+                (sidecar_path(path, "series"), series_csv(&saved)),
+                (sidecar_path(path, "ratings"), ratings_csv(&saved.ratings)),
+                (
+                    path.to_string(),
+                    Some(write_csv(&saved.records, self.next_id())),
+                ),
+            ]),
+        }
+    }
+}
+
+impl Stats {
+    // The same as the text report, for other programs: kinds are lowercase
+    // ("book"), decades are their first year ("1930")
+    pub fn to_json(&self) -> String {
+        let counts = |counts: Vec<(String, usize)>| {
+            Json::Object(
+                counts
+                    .into_iter()
+                    .map(|(key, count)| (key, Json::Number(count as i64)))
+                    .collect(),
+            )
+        };
+        let top = |creators: &[(String, usize)]| {
+            let creators = creators
+                .iter()
+                .map(|(name, count)| {
+                    Json::object(vec![
+                        ("name", Json::text(name)),
+                        ("items", Json::Number(*count as i64)),
+                    ])
+                })
+                .collect();
+            Json::Array(creators)
+        };
+        let kind = |kind: &MediaKind| kind.to_string().to_lowercase();
+
+        let json = Json::object(vec![
+            ("items", Json::Number(self.items as i64)),
+            (
+                "kinds",
+                counts(self.per_kind.iter().map(|(k, n)| (kind(k), *n)).collect()),
+            ),
+            (
+                "genres",
+                counts(
+                    self.per_genre
+                        .iter()
+                        .map(|(g, n)| (g.clone(), *n))
+                        .collect(),
+                ),
+            ),
+            (
+                "decades",
+                counts(
+                    self.per_decade
+                        .iter()
+                        .map(|(d, n)| (d.to_string(), *n))
+                        .collect(),
+                ),
+            ),
+            ("top_authors", top(&self.top_authors)),
+            ("top_directors", top(&self.top_directors)),
+            (
+                "average_rating",
+                self.average_rating.map(Json::Decimal).unwrap_or(Json::Null),
+            ),
+            (
+                "average_rating_per_kind",
+                Json::Object(
+                    self.average_rating_per_kind
+                        .iter()
+                        .map(|(k, average)| (kind(k), Json::Decimal(*average)))
+                        .collect(),
+                ),
+            ),
+            ("total_minutes", Json::Number(self.total_minutes as i64)),
+            ("total_pages", Json::Number(self.total_pages as i64)),
+        ]);
+        json.to_string()
+    }
+}
+
+// Writes into a temporary file next to the real one, then renames it:
+// a crash in the middle never leaves a half written catalog behind.
+fn write_atomically(path: &str, text: &str) -> Result<(), StorageError> {
+    let temporary = write_temporary(path, text)?;
+    rename_temporary(&temporary, path)
+}
+
+// A CSV catalog is several files: all of them are written to temporary files first,
+// and only renamed once they're all there, the main file last. A crash during the
+// renames can still leave new sidecars next to the old main file, but never a
+// half written file, and never a new main file with stale sidecars.
+// 'None' removes the file.
+fn write_all_atomically(files: &[(String, Option<String>)]) -> Result<(), StorageError> {
+    let mut temporaries = vec![];
+    for (path, text) in files {
+        let Some(text) = text else {
+            temporaries.push(None);
+            continue;
+        };
+        match write_temporary(path, text) {
+            Ok(temporary) => temporaries.push(Some(temporary)),
+            Err(error) => {
+                for temporary in temporaries.into_iter().flatten() {
+                    let _ = fs::remove_file(temporary);
+                }
+                return Err(error);
+            }
+        }
+    }
+
+    let mut temporaries = temporaries.into_iter();
+    for ((path, _), temporary) in files.iter().zip(temporaries.by_ref()) {
+        let result = match temporary {
+            Some(temporary) => rename_temporary(&temporary, path),
+            None => remove_sidecar(path),
+        };
+        if result.is_err() {
+            for temporary in temporaries.flatten() {
+                let _ = fs::remove_file(temporary);
+            }
+            return result;
+        }
+    }
+    Ok(())
+}
+
+// Two saves at once (from two threads, or two programs) never share a temporary file
+fn temporary_path(path: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}.{}.{}.tmp", path, process::id(), count)
+}
+
+// The temporary file is synced to the disk before it's returned
+fn write_temporary(path: &str, text: &str) -> Result<String, StorageError> {
+    let temporary = temporary_path(path);
+    let result = fs::File::create(&temporary).and_then(|mut file| {
+        file.write_all(text.as_bytes())?;
+        file.sync_all()
+    });
+
+    match result {
+        Ok(()) => Ok(temporary),
+        Err(error) => {
+            let _ = fs::remove_file(&temporary);
+            Err(StorageError::Io {
+                path: path.to_string(),
+                message: error.to_string(),
+            })
+        }
+    }
+}
+
+fn rename_temporary(temporary: &str, path: &str) -> Result<(), StorageError> {
+    fs::rename(temporary, path).map_err(|error| {
+        let _ = fs::remove_file(temporary);
+        StorageError::Io {
+            path: path.to_string(),
+            message: error.to_string(),
+        }
+    })
+}
+
+fn write_json(saved: &SavedCatalog) -> String {
+    // One item per line, so that the file stays readable (and diffs nicely)
+    let lines = |values: Vec<Json>| -> String {
+        let values: Vec<String> = values.iter().map(|value| format!("  {}", value)).collect();
+        values.join(",\n")
+    };
+    let items = lines(saved.records.iter().map(Record::to_json).collect());
+    let mut text = format!("{{\"version\": {}", FORMAT_VERSION);
+    if let Some(next_id) = saved.next_id {
+        text.push_str(&format!(", \"next_id\": {}", next_id));
+    }
+    if let Some(next_series_id) = saved.next_series_id {
+        text.push_str(&format!(", \"next_series_id\": {}", next_series_id));
+    }
+    text.push_str(&format!(", \"items\": [\n{}\n]", items));
+
+    // Older versions of this file have no progress, tags, shelves, loans, holds, series
+    // or ratings:
+    // they're only written when there are some
+    let of_kind = |kind: GroupKind| -> Vec<Json> {
+        saved
+            .groups
+            .iter()
+            .filter(|group| group.kind == kind)
+            .map(group_to_json)
+            .collect()
+    };
+    let lent = |holds: bool| -> Vec<Json> {
+        saved
+            .lending
+            .iter()
+            .filter(|lent| matches!(lent, Lent::Hold { .. }) == holds)
+            .map(lent_to_json)
+            .collect()
+    };
+    let sections = [
+        (
+            "progress",
+            saved.progress.iter().map(progress_to_json).collect(),
+        ),
+        ("tags", of_kind(GroupKind::Tag)),
+        ("shelves", of_kind(GroupKind::Shelf)),
+        ("loans", lent(false)),
+        ("holds", lent(true)),
+        ("series", saved.series.iter().map(series_to_json).collect()),
+        (
+            "ratings",
+            saved.ratings.iter().map(rating_to_json).collect(),
+        ),
+    ];
+    for (name, values) in sections {
+        if !values.is_empty() {
+            text.push_str(&format!(", \"{}\": [\n{}\n]", name, lines(values)));
+        }
+    }
+    text.push_str("}\n");
+    text
+}
+
+fn read_json(text: &str) -> Result<SavedCatalog, StorageError> {
+    let json = Json::parse(text).map_err(StorageError::Json)?;
+    let items = json
+        .get("items")
+        .and_then(Json::as_array)
+        .ok_or_else(|| StorageError::Json(String::from("no \"items\" array")))?;
+
+    let records = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            Record::from_json(item).map_err(|message| StorageError::InvalidRecord {
+                record: index + 1,
+                message,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    let next_id = json_number(&json, "next_id").map_err(StorageError::Json)?;
+
+    let progress = json
+        .get("progress")
+        .and_then(Json::as_array)
+        .unwrap_or(&[])
+        .iter()
+        .enumerate()
+        .map(|(index, update)| {
+            progress_from_json(update).map_err(|message| StorageError::InvalidProgress {
+                record: index + 1,
+                message,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let tags = json.get("tags").and_then(Json::as_array).unwrap_or(&[]);
+    let shelves = json.get("shelves").and_then(Json::as_array).unwrap_or(&[]);
+    let tags = tags.iter().map(|group| (GroupKind::Tag, group));
+    let shelves = shelves.iter().map(|group| (GroupKind::Shelf, group));
+    let groups = tags
+        .chain(shelves)
+        .enumerate()
+        .map(|(index, (kind, group))| {
+            group_from_json(kind, group).map_err(|message| StorageError::InvalidTags {
+                record: index + 1,
+                message,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let loans = json.get("loans").and_then(Json::as_array).unwrap_or(&[]);
+    let holds = json.get("holds").and_then(Json::as_array).unwrap_or(&[]);
+    let loans = loans.iter().map(|lent| (false, lent));
+    let holds = holds.iter().map(|lent| (true, lent));
+    let lending = loans
+        .chain(holds)
+        .enumerate()
+        .map(|(index, (hold, lent))| {
+            lent_from_json(hold, lent).map_err(|message| StorageError::InvalidLending {
+                record: index + 1,
+                message,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let series = json
+        .get("series")
+        .and_then(Json::as_array)
+        .unwrap_or(&[])
+        .iter()
+        .enumerate()
+        .map(|(index, series)| {
+            series_from_json(series).map_err(|message| StorageError::InvalidSeries {
+                record: index + 1,
+                message,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    let next_series_id = json_number(&json, "next_series_id").map_err(StorageError::Json)?;
+
+    let ratings = json
+        .get("ratings")
+        .and_then(Json::as_array)
+        .unwrap_or(&[])
+        .iter()
+        .enumerate()
+        .map(|(index, rating)| {
+            rating_from_json(rating).map_err(|message| StorageError::InvalidRating {
+                record: index + 1,
+                message,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(SavedCatalog {
+        records,
+        next_id,
+        progress,
+        groups,
+        lending,
+        series,
+        next_series_id,
+        ratings,
+    })
+}
+
+// {"user": "alice", "id": 3, "stars": 4}
+fn rating_to_json(rating: &Rating) -> Json {
+    Json::object(vec![
+        ("user", Json::text(&rating.user)),
+        ("id", Json::Number(rating.id.0 as i64)),
+        ("stars", Json::Number(rating.stars as i64)),
+    ])
+}
+
+fn rating_from_json(json: &Json) -> Result<Rating, String> {
+    rating(
+        json.get("user").and_then(Json::as_str),
+        json_number(json, "id")?,
+        json_number(json, "stars")?,
+    )
+}
+
+// The same checks whatever the file format (the stars are checked by 'rate')
+fn rating(user: Option<&str>, id: Option<u64>, stars: Option<u8>) -> Result<Rating, String> {
+    Ok(Rating {
+        user: user
+            .ok_or_else(|| String::from("missing user"))?
+            .to_string(),
+        id: MediaId(id.ok_or_else(|| String::from("missing id"))?),
+        stars: stars.ok_or_else(|| String::from("missing stars"))?,
+    })
+}
+
+// {"series": 2, "name": "Good Show", "kind": "series",
+//  "parts": [{"id": 4, "season": 1, "number": 1}, {"id": 5, "season": 1, "number": 2}]}
+fn series_to_json(series: &SavedSeries) -> Json {
+    let parts = series
+        .parts
+        .iter()
+        .map(|part| {
+            let mut fields = vec![("id", Json::Number(part.id.0 as i64))];
+            if let Some(season) = part.season {
+                fields.push(("season", Json::Number(season as i64)));
+            }
+            fields.push(("number", Json::Number(part.number as i64)));
+            Json::object(fields)
+        })
+        .collect();
+    Json::object(vec![
+        ("series", Json::Number(series.id.0 as i64)),
+        ("name", Json::text(&series.name)),
+        ("kind", Json::text(series_kind_name(series.kind))),
+        ("parts", Json::Array(parts)),
+    ])
+}
+
+fn series_from_json(json: &Json) -> Result<SavedSeries, String> {
+    let mut series = saved_series(
+        json_number(json, "series")?,
+        json.get("name").and_then(Json::as_str),
+        json.get("kind").and_then(Json::as_str),
+    )?;
+    for part in json.get("parts").and_then(Json::as_array).unwrap_or(&[]) {
+        series.parts.push(part_of_series(
+            json_number(part, "id")?,
+            json_number(part, "season")?,
+            json_number(part, "number")?,
+        )?);
+    }
+    Ok(series)
+}
+
+// The same checks whatever the file format
+fn saved_series(
+    id: Option<u64>,
+    name: Option<&str>,
+    kind: Option<&str>,
+) -> Result<SavedSeries, String> {
+    let kind = match kind.ok_or_else(|| String::from("missing kind"))? {
+        "series" => SeriesKind::Series,
+        "collection" => SeriesKind::Collection,
+        kind => return Err(format!("unknown kind '{}'", kind)),
+    };
+    Ok(SavedSeries {
+        id: SeriesId(id.ok_or_else(|| String::from("missing series"))?),
+        name: name
+            .ok_or_else(|| String::from("missing name"))?
+            .to_string(),
+        kind,
+        parts: vec![],
+    })
+}
+
+fn part_of_series(
+    id: Option<u64>,
+    season: Option<u32>,
+    number: Option<u32>,
+) -> Result<Part, String> {
+    Ok(Part {
+        id: MediaId(id.ok_or_else(|| String::from("missing id"))?),
+        season,
+        number: number.ok_or_else(|| String::from("missing number"))?,
+    })
+}
+
+// {"id": 3, "borrower": "bob", "checked_out": "2024-03-01", "due": "2024-03-22"},
+// and a hold: {"id": 3, "borrower": "carol"}
+fn lent_to_json(lent: &Lent) -> Json {
+    match lent {
+        Lent::Loan { id, loan } => Json::object(vec![
+            ("id", Json::Number(id.0 as i64)),
+            ("borrower", Json::text(&loan.borrower)),
+            ("checked_out", Json::text(&loan.checked_out.to_string())),
+            ("due", Json::text(&loan.due.to_string())),
+        ]),
+        Lent::Hold { id, borrower } => Json::object(vec![
+            ("id", Json::Number(id.0 as i64)),
+            ("borrower", Json::text(borrower)),
+        ]),
+    }
+}
+
+fn lent_from_json(hold: bool, json: &Json) -> Result<Lent, String> {
+    let text = |key: &str| json.get(key).and_then(Json::as_str);
+    lent(
+        hold,
+        json_number(json, "id")?,
+        text("borrower"),
+        text("checked_out"),
+        text("due"),
+    )
+}
+
+// The same checks whatever the file format
+fn lent(
+    hold: bool,
+    id: Option<u64>,
+    borrower: Option<&str>,
+    checked_out: Option<&str>,
+    due: Option<&str>,
+) -> Result<Lent, String> {
+    let id = MediaId(id.ok_or_else(|| String::from("missing id"))?);
+    let borrower = borrower
+        .ok_or_else(|| String::from("missing borrower"))?
+        .to_string();
+    if hold {
+        return Ok(Lent::Hold { id, borrower });
+    }
+
+    let date = |name: &str, date: Option<&str>| {
+        let date = date.ok_or_else(|| format!("missing {} date", name))?;
+        Date::parse(date).ok_or_else(|| format!("invalid date '{}'", date))
+    };
+    Ok(Lent::Loan {
+        id,
+        loan: Loan {
+            borrower,
+            checked_out: date("checkout", checked_out)?,
+            due: date("due", due)?,
+        },
+    })
+}
+
+// {"tag": "kids", "items": [3, 1]}, {"shelf": "To read", "items": [1, 3]}
+fn group_to_json(group: &Group) -> Json {
+    let items = group
+        .items
+        .iter()
+        .map(|id| Json::Number(id.0 as i64))
+        .collect();
+    Json::object(vec![
+        (group.kind.name(), Json::text(&group.name)),
+        ("items", Json::Array(items)),
+    ])
+}
+
+fn group_from_json(kind: GroupKind, json: &Json) -> Result<Group, String> {
+    let name = json
+        .get(kind.name())
+        .and_then(Json::as_str)
+        .ok_or_else(|| format!("missing {} name", kind.name()))?;
+    let items = json
+        .get("items")
+        .and_then(Json::as_array)
+        .unwrap_or(&[])
+        .iter()
+        .map(|id| {
+            id.as_i64()
+                .and_then(|id| u64::try_from(id).ok())
+                .map(MediaId)
+                .ok_or_else(|| format!("invalid id in '{}'", name))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Group {
+        kind,
+        name: name.to_string(),
+        items,
+    })
+}
+
+fn progress_to_json(update: &ProgressUpdate) -> Json {
+    let (progress, value) = progress_fields(update.progress);
+    let mut fields = vec![
+        ("user", Json::text(&update.user)),
+        ("id", Json::Number(update.id.0 as i64)),
+        ("progress", Json::text(progress)),
+    ];
+    if let Some(value) = value {
+        fields.push(("value", Json::Number(value as i64)));
+    }
+    fields.push(("on", Json::text(&update.on.to_string())));
+    Json::object(fields)
+}
+
+fn progress_from_json(json: &Json) -> Result<ProgressUpdate, String> {
+    let text = |key: &str| json.get(key).and_then(Json::as_str);
+    progress_update(
+        text("user"),
+        json_number(json, "id")?,
+        text("progress"),
+        json_number(json, "value")?,
+        text("on"),
+    )
+}
+
+// "pages" and 120, or "finished" and no value
+fn progress_fields(progress: Progress) -> (&'static str, Option<u32>) {
+    match progress {
+        Progress::Pages(pages) => ("pages", Some(pages)),
+        Progress::Minutes(minutes) => ("minutes", Some(minutes)),
+        Progress::Finished => ("finished", None),
+    }
+}
+
+// The same checks whatever the file format
+fn progress_update(
+    user: Option<&str>,
+    id: Option<u64>,
+    progress: Option<&str>,
+    value: Option<u32>,
+    on: Option<&str>,
+) -> Result<ProgressUpdate, String> {
+    let value = || value.ok_or_else(|| String::from("missing value"));
+    let progress = match progress.ok_or_else(|| String::from("missing progress"))? {
+        "pages" => Progress::Pages(value()?),
+        "minutes" => Progress::Minutes(value()?),
+        "finished" => Progress::Finished,
+        progress => return Err(format!("unknown progress '{}'", progress)),
+    };
+    let on = on.ok_or_else(|| String::from("missing date"))?;
+
+    Ok(ProgressUpdate {
+        user: user
+            .ok_or_else(|| String::from("missing user"))?
+            .to_string(),
+        id: MediaId(id.ok_or_else(|| String::from("missing id"))?),
+        progress,
+        on: Date::parse(on).ok_or_else(|| format!("invalid date '{}'", on))?,
+    })
+}
+
+// Only written when there's some progress (None: an old file is removed, so that it isn't loaded again)
+fn progress_csv(progress: &[ProgressUpdate]) -> Option<String> {
+    if progress.is_empty() {
+        return None;
+    }
+
+    let header: Vec<String> = PROGRESS_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let mut text = csv::write_row(&header);
+    for update in progress {
+        let (kind, value) = progress_fields(update.progress);
+        text.push_str(&csv::write_row(&[
+            update.user.clone(),
+            update.id.0.to_string(),
+            kind.to_string(),
+            value.map(|value| value.to_string()).unwrap_or_default(),
+            update.on.to_string(),
+        ]));
+    }
+    Some(text)
+}
+
+fn remove_sidecar(path: &str) -> Result<(), StorageError> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(StorageError::Io {
+            path: path.to_string(),
+            message: error.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+// Like the progress: only written when there are tags or shelves
+fn tags_csv(groups: &[Group]) -> Option<String> {
+    if groups.is_empty() {
+        return None;
+    }
+
+    let header: Vec<String> = TAGS_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let mut text = csv::write_row(&header);
+    for group in groups {
+        let row = |id: String| vec![group.kind.name().to_string(), group.name.clone(), id];
+        if group.items.is_empty() {
+            text.push_str(&csv::write_row(&row(String::new())));
+        }
+        for id in &group.items {
+            text.push_str(&csv::write_row(&row(id.0.to_string())));
+        }
+    }
+    Some(text)
+}
+
+// Like the tags: only written when something is lent or waited for
+fn lending_csv(lending: &[Lent]) -> Option<String> {
+    if lending.is_empty() {
+        return None;
+    }
+
+    let header: Vec<String> = LENDING_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let mut text = csv::write_row(&header);
+    for lent in lending {
+        let row = match lent {
+            Lent::Loan { id, loan } => [
+                "loan".to_string(),
+                id.0.to_string(),
+                loan.borrower.clone(),
+                loan.checked_out.to_string(),
+                loan.due.to_string(),
+            ],
+            Lent::Hold { id, borrower } => [
+                "hold".to_string(),
+                id.0.to_string(),
+                borrower.clone(),
+                String::new(),
+                String::new(),
+            ],
+        };
+        text.push_str(&csv::write_row(&row));
+    }
+    Some(text)
+}
+
+// A CSV catalog without a loans file simply has nothing lent
+fn read_lending_csv(path: &str) -> Result<Vec<Lent>, StorageError> {
+    let invalid = |record, message| StorageError::InvalidLending { record, message };
+    read_sidecar(path, "loans", invalid, |fields| {
+        let hold = match fields.get("list") {
+            Some("loan") => false,
+            Some("hold") => true,
+            Some(list) => return Err(format!("unknown list '{}'", list)),
+            None => return Err(String::from("missing list")),
+        };
+        lent(
+            hold,
+            fields.number("id")?,
+            fields.get("borrower"),
+            fields.get("checked_out"),
+            fields.get("due"),
+        )
+    })
+}
+
+// Like the tags: only written once a series was created (even if it was deleted since)
+fn series_csv(saved: &SavedCatalog) -> Option<String> {
+    let next_series_id = saved.next_series_id?;
+
+    let header: Vec<String> = SERIES_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let mut text = format!("{}{}\n", NEXT_ID_PREFIX, next_series_id);
+    text.push_str(&csv::write_row(&header));
+    for series in &saved.series {
+        let row = |id: String, season: String, number: String| {
+            vec![
+                series.id.0.to_string(),
+                series.name.clone(),
+                series_kind_name(series.kind).to_string(),
+                id,
+                season,
+                number,
+            ]
+        };
+        if series.parts.is_empty() {
+            text.push_str(&csv::write_row(&row(
+                String::new(),
+                String::new(),
+                String::new(),
+            )));
+        }
+        for part in &series.parts {
+            text.push_str(&csv::write_row(&row(
+                part.id.0.to_string(),
+                part.season
+                    .map(|season| season.to_string())
+                    .unwrap_or_default(),
+                part.number.to_string(),
+            )));
+        }
+    }
+    Some(text)
+}
+
+// A CSV catalog without a series file simply has no series
+fn read_series_csv(path: &str) -> Result<(Vec<SavedSeries>, Option<u64>), StorageError> {
+    let sidecar = sidecar_path(path, "series");
+    let Ok(text) = fs::read_to_string(&sidecar) else {
+        return Ok((vec![], None));
+    };
+
+    let (next_series_id, text) = split_next_id(&text)?;
+    let invalid = |record, message| StorageError::InvalidSeries { record, message };
+    let series = parse_sidecar(&sidecar, text, invalid, |fields| {
+        let mut series = saved_series(
+            fields.number("series")?,
+            fields.get("name"),
+            fields.get("kind"),
+        )?;
+        // A row without an id is an empty series
+        if let Some(id) = fields.number("id")? {
+            series.parts.push(part_of_series(
+                Some(id),
+                fields.number("season")?,
+                fields.number("number")?,
+            )?);
+        }
+        Ok(series)
+    })?;
+    Ok((series, next_series_id))
+}
+
+// Like the progress: only written when someone rated something
+fn ratings_csv(ratings: &[Rating]) -> Option<String> {
+    if ratings.is_empty() {
+        return None;
+    }
+
+    let header: Vec<String> = RATINGS_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let mut text = csv::write_row(&header);
+    for rating in ratings {
+        text.push_str(&csv::write_row(&[
+            rating.user.clone(),
+            rating.id.0.to_string(),
+            rating.stars.to_string(),
+        ]));
+    }
+    Some(text)
+}
+
+// A CSV catalog without a ratings file simply has no ratings
+fn read_ratings_csv(path: &str) -> Result<Vec<Rating>, StorageError> {
+    let invalid = |record, message| StorageError::InvalidRating { record, message };
+    read_sidecar(path, "ratings", invalid, |fields| {
+        rating(
+            fields.get("user"),
+            fields.number("id")?,
+            fields.number("stars")?,
+        )
+    })
+}
+
+// The fields of a row of a sidecar file, found by the name of their column
+struct Fields<'a> {
+    header: &'a [String],
+    row: &'a [String],
+}
+
+impl Fields<'_> {
+    // None when the column is missing or the field is blank
+    fn get(&self, name: &str) -> Option<&str> {
+        self.header
+            .iter()
+            .position(|column| column == name)
+            .and_then(|index| self.row.get(index))
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid {} '{}'", name, value))
+            })
+            .transpose()
+    }
+}
+
+// Reads every row of the sidecar file 'name' of a CSV catalog ('invalid' tells which row
+// 'read' refused, from 1). Without the file, there's nothing to read.
+fn read_sidecar<T>(
+    path: &str,
+    name: &str,
+    invalid: fn(usize, String) -> StorageError,
+    read: impl Fn(&Fields) -> Result<T, String>,
+) -> Result<Vec<T>, StorageError> {
+    let sidecar = sidecar_path(path, name);
+    let Ok(text) = fs::read_to_string(&sidecar) else {
+        return Ok(vec![]);
+    };
+    parse_sidecar(&sidecar, &text, invalid, read)
+}
+
+fn parse_sidecar<T>(
+    sidecar: &str,
+    text: &str,
+    invalid: fn(usize, String) -> StorageError,
+    read: impl Fn(&Fields) -> Result<T, String>,
+) -> Result<Vec<T>, StorageError> {
+    let rows = csv::parse(text).map_err(|(line, message)| StorageError::Csv {
+        line,
+        message: format!("{}: {}", sidecar, message),
+    })?;
+    let Some((header, rows)) = rows.split_first() else {
+        return Ok(vec![]);
+    };
+
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| {
+            read(&Fields { header, row }).map_err(|message| invalid(index + 1, message))
+        })
+        .collect()
+}
+
+// A CSV catalog without a tags file simply has no tags nor shelves
+fn read_tags_csv(path: &str) -> Result<Vec<Group>, StorageError> {
+    let invalid = |record, message| StorageError::InvalidTags { record, message };
+    read_sidecar(path, "tags", invalid, |fields| {
+        let kind = match fields.get("list") {
+            Some("tag") => GroupKind::Tag,
+            Some("shelf") => GroupKind::Shelf,
+            Some(list) => return Err(format!("unknown list '{}'", list)),
+            None => return Err(String::from("missing list")),
+        };
+        let name = fields
+            .get("name")
+            .ok_or_else(|| String::from("missing name"))?;
+        let id = fields.number("id")?;
+        Ok(Group {
+            kind,
+            name: name.to_string(),
+            items: id.map(MediaId).into_iter().collect(),
+        })
+    })
+}
+
+// A CSV catalog without a progress file simply has no progress
+fn read_progress_csv(path: &str) -> Result<Vec<ProgressUpdate>, StorageError> {
+    let invalid = |record, message| StorageError::InvalidProgress { record, message };
+    read_sidecar(path, "progress", invalid, |fields| {
+        progress_update(
+            fields.get("user"),
+            fields.number("id")?,
+            fields.get("progress"),
+            fields.number("value")?,
+            fields.get("on"),
+        )
+    })
+}
+
+// The next id goes on a line of its own before the header: "#next_id=12"
+const NEXT_ID_PREFIX: &str = "#next_id=";
+
+fn write_csv(records: &[Record], next_id: MediaId) -> String {
+    let header: Vec<String> = COLUMNS.iter().map(|column| column.to_string()).collect();
+    let mut text = format!("{}{}\n", NEXT_ID_PREFIX, next_id.0);
+    text.push_str(&csv::write_row(&header));
+    for record in records {
+        text.push_str(&csv::write_row(&record.to_csv()));
+    }
+    text
+}
+
+// The next id (when there's a line for it), and the rows that follow
+fn split_next_id(text: &str) -> Result<(Option<u64>, &str), StorageError> {
+    let Some(rest) = text.strip_prefix(NEXT_ID_PREFIX) else {
+        return Ok((None, text));
+    };
+    // The line break is kept, so that the line numbers of errors stay right
+    let end = rest.find('\n').unwrap_or(rest.len());
+    let value = rest[..end].trim();
+    let next_id = value.parse().map_err(|_| StorageError::Csv {
+        line: 1,
+        message: format!("invalid next id '{}'", value),
+    })?;
+    Ok((Some(next_id), &rest[end..]))
+}
+
+// The first row names the columns: they can come in any order,
+// and the ones we don't know about are ignored.
+// Files written by older versions have no next id line.
+fn read_csv(text: &str) -> Result<(Vec<Record>, Option<u64>), StorageError> {
+    let (next_id, rows) = split_next_id(text)?;
+
+    let rows = csv::parse(rows).map_err(|(line, message)| StorageError::Csv { line, message })?;
+    let Some((header, rows)) = rows.split_first() else {
+        return Ok((vec![], next_id));
+    };
+
+    let records = rows
+        .iter()
+        .enumerate()
+        .map(|(index, row)| {
+            Record::from_csv(header, row).map_err(|message| StorageError::InvalidRecord {
+                record: index + 1,
+                message,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((records, next_id))
+}
+
+// A Media, flattened: the same fields whatever the file format
+#[derive(Debug, Default)]
+struct Record {
+    kind: String,
+    id: Option<u64>,
+    title: String,
+    // Author, director or show
+    creator: String,
+    episode: Option<u32>,
+    year: Option<u16>,
+    genres: Vec<String>,
+    pages: Option<u32>,
+    minutes: Option<u32>,
+    language: Option<String>,
+    rating: Option<u8>,
+    // What's specific to a custom kind, see 'MediaItem::fields'
+    fields: BTreeMap<String, String>,
+}
+
+impl Record {
+    fn from_media(id: MediaId, media: &Media) -> Self {
+        let mut record = Record {
+            kind: media.kind().to_string().to_lowercase(),
+            id: Some(id.0),
+            title: media.title().to_string(),
+            creator: media.creator().unwrap_or("").to_string(),
+            ..Default::default()
+        };
+
+        match media {
+            Media::Podcast(Podcast { episode, .. }) => record.episode = Some(*episode),
+            Media::Custom(item) => record.fields = item.fields(),
+            _ => {}
+        }
+        if let Some(meta) = media.meta() {
+            record.year = meta.year;
+            record.genres = meta.genres.clone();
+            match meta.length {
+                Some(Length::Pages(pages)) => record.pages = Some(pages),
+                Some(Length::Minutes(minutes)) => record.minutes = Some(minutes),
+                None => {}
+            }
+            record.language = meta.language.clone();
+            record.rating = meta.rating;
+        }
+
+        record
+    }
+
+    fn into_media(self, kinds: &MediaRegistry) -> Result<Media, String> {
+        if self.kind == "placeholder" {
+            return Ok(Media::Placeholder);
+        }
+
+        let meta = Metadata {
+            year: self.year,
+            genres: self.genres,
+            length: match (self.pages, self.minutes) {
+                (Some(pages), _) => Some(Length::Pages(pages)),
+                (None, Some(minutes)) => Some(Length::Minutes(minutes)),
+                (None, None) => None,
+            },
+            language: self.language,
+            rating: self.rating.map(|rating| rating.min(5)),
+        };
+        let (title, creator) = (self.title, self.creator);
+
+        match self.kind.as_str() {
+            "book" => Ok(Media::Book(Book {
+                title,
+                author: creator,
+                meta,
+            })),
+            "movie" => Ok(Media::Movie(Movie {
+                title,
+                director: creator,
+                meta,
+            })),
+            "audiobook" => Ok(Media::Audiobook(Audiobook { title, meta })),
+            "podcast" => Ok(Media::Podcast(Podcast {
+                show: creator,
+                episode: self
+                    .episode
+                    .ok_or_else(|| String::from("missing episode"))?,
+                title,
+                meta,
+            })),
+            kind => {
+                let saved = SavedItem {
+                    title,
+                    creator,
+                    meta,
+                    fields: self.fields,
+                };
+                // A kind that isn't registered is kept as it is
+                kinds.build(kind, saved.clone()).unwrap_or_else(|| {
+                    UnknownItem::new(kind, saved).map(|item| Media::Custom(Box::new(item)))
+                })
+            }
+        }
+    }
+
+    // Fields without a value are left out
+    fn to_json(&self) -> Json {
+        let mut fields = vec![
+            ("kind", Json::text(&self.kind)),
+            ("title", Json::text(&self.title)),
+        ];
+        if !self.creator.is_empty() {
+            fields.push(("creator", Json::text(&self.creator)));
+        }
+        let numbers = [
+            ("id", self.id),
+            ("episode", self.episode.map(u64::from)),
+            ("year", self.year.map(u64::from)),
+            ("pages", self.pages.map(u64::from)),
+            ("minutes", self.minutes.map(u64::from)),
+            ("rating", self.rating.map(u64::from)),
+        ];
+        for (key, value) in numbers {
+            if let Some(value) = value {
+                fields.push((key, Json::Number(value as i64)));
+            }
+        }
+        if !self.genres.is_empty() {
+            let genres = self.genres.iter().map(|genre| Json::text(genre)).collect();
+            fields.push(("genres", Json::Array(genres)));
+        }
+        if let Some(language) = &self.language {
+            fields.push(("language", Json::text(language)));
+        }
+        if !self.fields.is_empty() {
+            let custom = self
+                .fields
+                .iter()
+                .map(|(key, value)| (key.clone(), Json::text(value)))
+                .collect();
+            fields.push(("fields", Json::Object(custom)));
+        }
+
+        Json::object(fields)
+    }
+
+    fn from_json(json: &Json) -> Result<Self, String> {
+        let text = |key: &str| json.get(key).and_then(Json::as_str).map(str::to_string);
+
+        Ok(Record {
+            kind: text("kind").ok_or_else(|| String::from("missing kind"))?,
+            id: json_number(json, "id")?,
+            title: text("title").unwrap_or_default(),
+            creator: text("creator").unwrap_or_default(),
+            episode: json_number(json, "episode")?,
+            year: json_number(json, "year")?,
+            genres: json
+                .get("genres")
+                .and_then(Json::as_array)
+                .unwrap_or(&[])
+                .iter()
+                .filter_map(|genre| genre.as_str().map(str::to_string))
+                .collect(),
+            pages: json_number(json, "pages")?,
+            minutes: json_number(json, "minutes")?,
+            language: text("language"),
+            rating: json_number(json, "rating")?,
+            fields: match json.get("fields") {
+                Some(Json::Object(fields)) => fields
+                    .iter()
+                    .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                    .collect(),
+                _ => BTreeMap::new(),
+            },
+        })
+    }
+
+    // In the order of COLUMNS, genres separated by ';', fields as "key=value;key=value"
+    // (a ';', '=' or '\' inside a genre, key or value is escaped with a '\': "rock\;pop")
+    fn to_csv(&self) -> Vec<String> {
+        fn optional<T: ToString>(value: Option<T>) -> String {
+            value.map(|value| value.to_string()).unwrap_or_default()
+        }
+
+        vec![
+            self.kind.clone(),
+            optional(self.id),
+            self.title.clone(),
+            self.creator.clone(),
+            optional(self.episode),
+            optional(self.year),
+            self.genres
+                .iter()
+                .map(|genre| escape(genre))
+                .collect::<Vec<_>>()
+                .join(";"),
+            optional(self.pages),
+            optional(self.minutes),
+            self.language.clone().unwrap_or_default(),
+            optional(self.rating),
+            self.fields
+                .iter()
+                .map(|(key, value)| format!("{}={}", escape(key), escape(value)))
+                .collect::<Vec<_>>()
+                .join(";"),
+        ]
+    }
+
+    fn from_csv(header: &[String], row: &[String]) -> Result<Self, String> {
+        // Genres and fields are kept as they are, spaces included
+        let raw = |name: &str| -> Option<&str> {
+            header
+                .iter()
+                .position(|column| column == name)
+                .and_then(|index| row.get(index))
+                .map(String::as_str)
+                .filter(|value| !value.is_empty())
+        };
+        let field = |name: &str| raw(name).map(str::trim).filter(|value| !value.is_empty());
+        fn number<T: std::str::FromStr>(
+            name: &str,
+            value: Option<&str>,
+        ) -> Result<Option<T>, String> {
+            value
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid {} '{}'", name, value))
+                })
+                .transpose()
+        }
+
+        Ok(Record {
+            kind: field("kind")
+                .ok_or_else(|| String::from("missing kind"))?
+                .to_string(),
+            id: number("id", field("id"))?,
+            title: field("title").unwrap_or("").to_string(),
+            creator: field("creator").unwrap_or("").to_string(),
+            episode: number("episode", field("episode"))?,
+            year: number("year", field("year"))?,
+            genres: raw("genres")
+                .map(|genres| split_escaped(genres, ';').map(unescape).collect())
+                .unwrap_or_default(),
+            pages: number("pages", field("pages"))?,
+            minutes: number("minutes", field("minutes"))?,
+            language: field("language").map(str::to_string),
+            rating: number("rating", field("rating"))?,
+            fields: raw("fields")
+                .map(|fields| {
+                    split_escaped(fields, ';')
+                        .map(|pair| {
+                            let key = split_escaped(pair, '=').next().unwrap_or_default();
+                            // Only the first '=' separates the key from the value
+                            let value = &pair[key.len()..];
+                            match value.strip_prefix('=') {
+                                Some(value) => Ok((unescape(key), unescape(value))),
+                                None => Err(format!("invalid field '{}'", unescape(pair))),
+                            }
+                        })
+                        .collect::<Result<_, _>>()
+                })
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, ';' | '=' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+// The parts of 'text' between the separators that aren't escaped, still escaped
+fn split_escaped(text: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == separator => {
+                parts.push(&text[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts.into_iter()
+}
+
+// A missing number is fine, a number that doesn't fit isn't
+fn json_number<T: TryFrom<i64>>(json: &Json, key: &str) -> Result<Option<T>, String> {
+    match json.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(value) => value
+            .as_i64()
+            .and_then(|value| T::try_from(value).ok())
+            .map(Some)
+            .ok_or_else(|| format!("invalid {}", key)),
+    }
+}
@@ -0,0 +1,80 @@
+// OPML: the subscription lists podcast apps export, one <outline> per feed
+//   <outline type="rss" text="Hardcore History" xmlUrl="https://..." category="History"/>
+// Feeds can be grouped in folders (outlines around other outlines): the folder names
+// become genres. Each feed is imported as a whole show (episode 0, no episode title).
+
+use super::StorageError;
+use super::import::{Entry, language};
+use super::xml::{self, Element};
+use crate::content::media::{Media, Metadata, Podcast};
+
+pub fn read(text: &str) -> Result<Vec<Entry>, StorageError> {
+    let root = xml::parse(text).map_err(|(line, message)| StorageError::Xml { line, message })?;
+    let body = match root.named("body").next() {
+        Some(body) if root.name == "opml" => body,
+        _ => {
+            return Err(StorageError::Xml {
+                line: 1,
+                message: String::from("not an OPML file (no <opml> with a <body>)"),
+            });
+        }
+    };
+
+    let mut entries = vec![];
+    outlines(body, &mut vec![], &mut entries);
+    Ok(entries)
+}
+
+// Goes through the folders, remembering their names
+fn outlines(parent: &Element, folders: &mut Vec<String>, entries: &mut Vec<Entry>) {
+    for outline in parent.named("outline") {
+        let name = outline
+            .attribute("title")
+            .or_else(|| outline.attribute("text"))
+            .unwrap_or("")
+            .trim()
+            .to_string();
+
+        if outline.attribute("xmlUrl").is_some() {
+            entries.push(Entry {
+                label: name.clone(),
+                media: podcast(outline, &name, folders),
+            });
+        } else if outline.named("outline").next().is_some() {
+            folders.push(name);
+            outlines(outline, folders, entries);
+            folders.pop();
+        } else {
+            entries.push(Entry {
+                label: name,
+                media: Err(String::from("no feed URL (xmlUrl)")),
+            });
+        }
+    }
+}
+
+fn podcast(outline: &Element, name: &str, folders: &[String]) -> Result<Media, String> {
+    if name.is_empty() {
+        return Err(String::from("no title"));
+    }
+
+    // category="/Technology/Software,Comedy"
+    let mut genres: Vec<&str> = outline
+        .attribute("category")
+        .unwrap_or("")
+        .split([',', '/'])
+        .map(str::trim)
+        .collect();
+    genres.extend(folders.iter().map(String::as_str));
+    genres.retain(|genre| !genre.is_empty());
+
+    let mut meta = Metadata::new().with_genres(&genres);
+    meta.language = outline.attribute("language").and_then(language);
+
+    Ok(Media::Podcast(Podcast {
+        show: name.to_string(),
+        episode: 0,
+        title: String::new(),
+        meta,
+    }))
+}
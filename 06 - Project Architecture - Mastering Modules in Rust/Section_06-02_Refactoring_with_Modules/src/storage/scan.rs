@@ -0,0 +1,418 @@
+// Builds catalog items from the media files found in a directory (and the ones in it).
+// The extension tells the kind of media; the name tells the title, and sometimes the
+// creator and the year, going by patterns like "{creator} - {title} ({year})".
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::StorageError;
+use super::import::first_number;
+use crate::content::catalog::{Catalog, MediaId};
+use crate::content::dedup::{normalize_creator, normalize_title};
+use crate::content::media::{Audiobook, Book, Media, MediaKind, Metadata, Movie, Podcast};
+
+const BOOKS: [&str; 7] = ["epub", "pdf", "mobi", "azw", "azw3", "djvu", "fb2"];
+const MOVIES: [&str; 7] = ["mp4", "mkv", "avi", "mov", "m4v", "webm", "wmv"];
+const AUDIOBOOKS: [&str; 3] = ["m4b", "aax", "aa"];
+
+// Tried in order, after the ones given to 'with_pattern'
+const PATTERNS: [&str; 4] = [
+    "{creator} - {title} ({year})",
+    "{creator} - {title}",
+    "{title} ({year})",
+    "{title}",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Creator,
+    Year,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+// "{creator} - {title}": fields between braces, the rest has to be there as it is.
+// A pattern with '/' also matches the folders the file is in: "{creator}/{title}".
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    segments: Vec<Segment>,
+}
+
+// What a file name tells about the item
+#[derive(Debug, Default)]
+struct Captures {
+    title: String,
+    creator: Option<String>,
+    year: Option<u16>,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Pattern, String> {
+        let mut segments = vec![];
+        let mut rest = pattern;
+
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                segments.push(Segment::Text(rest.to_string()));
+                break;
+            };
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| String::from("a '{' is never closed"))?;
+            let field = match &rest[start + 1..start + end] {
+                "title" => Field::Title,
+                "creator" => Field::Creator,
+                "year" => Field::Year,
+                name => return Err(format!("unknown field {{{}}}", name)),
+            };
+            if segments.contains(&Segment::Field(field)) {
+                return Err(format!("{:?} appears twice", field));
+            }
+            // "{creator}{title}": there's no telling where one ends
+            if let Some(Segment::Field(_)) = segments.last() {
+                return Err(String::from("two fields need some text between them"));
+            }
+            segments.push(Segment::Field(field));
+            rest = &rest[start + end + 1..];
+        }
+
+        if !segments.contains(&Segment::Field(Field::Title)) {
+            return Err(String::from("{title} is missing"));
+        }
+        Ok(Pattern { segments })
+    }
+
+    // How many folders (and the file) the pattern covers
+    fn depth(&self) -> usize {
+        let slashes: usize = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.matches('/').count(),
+                Segment::Field(_) => 0,
+            })
+            .sum();
+        slashes + 1
+    }
+
+    fn captures(&self, name: &str) -> Option<Captures> {
+        let mut captures = Captures::default();
+        matches(&self.segments, name, &mut captures).then_some(captures)
+    }
+}
+
+// Tries every place a field could end, from the shortest value on
+fn matches(segments: &[Segment], text: &str, captures: &mut Captures) -> bool {
+    let Some((first, rest)) = segments.split_first() else {
+        return text.is_empty();
+    };
+
+    match (first, rest.first()) {
+        (Segment::Text(expected), _) => text
+            .strip_prefix(expected.as_str())
+            .is_some_and(|text| matches(rest, text, captures)),
+        (Segment::Field(field), None) => capture(*field, text, captures),
+        (Segment::Field(field), Some(Segment::Text(next))) => {
+            text.match_indices(next.as_str()).any(|(end, _)| {
+                capture(*field, &text[..end], captures) && matches(rest, &text[end..], captures)
+            })
+        }
+        // Refused by 'parse'
+        (Segment::Field(_), Some(Segment::Field(_))) => false,
+    }
+}
+
+fn capture(field: Field, value: &str, captures: &mut Captures) -> bool {
+    let value = value.trim();
+    if value.is_empty() || value.contains('/') {
+        return false;
+    }
+    match field {
+        Field::Title => captures.title = value.to_string(),
+        Field::Creator => captures.creator = Some(value.to_string()),
+        Field::Year => {
+            if value.len() != 4 {
+                return false;
+            }
+            match value.parse() {
+                Ok(year) => captures.year = Some(year),
+                Err(_) => return false,
+            }
+        }
+    }
+    true
+}
+
+// "The_Hobbit" and "The.Hobbit" (a name without any space) -> "The Hobbit"
+fn clean(name: &str) -> String {
+    let name = name.replace('_', " ");
+    let name = if name.contains(' ') {
+        name
+    } else {
+        name.replace('.', " ")
+    };
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// What to look for, and how to read file names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scanner {
+    // Lowercase extension -> kind
+    extensions: BTreeMap<String, MediaKind>,
+    patterns: Vec<Pattern>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Scanner::new()
+    }
+}
+
+impl Scanner {
+    // Ebooks, videos and audiobooks, with the usual file names
+    pub fn new() -> Self {
+        let mut extensions = BTreeMap::new();
+        let kinds = [
+            (&BOOKS[..], MediaKind::Book),
+            (&MOVIES[..], MediaKind::Movie),
+            (&AUDIOBOOKS[..], MediaKind::Audiobook),
+        ];
+        for (names, kind) in kinds {
+            for name in names {
+                extensions.insert(name.to_string(), kind.clone());
+            }
+        }
+
+        Scanner {
+            extensions,
+            patterns: vec![],
+        }
+    }
+
+    // Also (or no longer, with another kind) looks at files with this extension.
+    // Books, movies, audiobooks and podcasts can be made from a file.
+    pub fn with_extension(mut self, extension: &str, kind: MediaKind) -> Self {
+        let extension = extension.trim_start_matches('.').to_lowercase();
+        self.extensions.insert(extension, kind);
+        self
+    }
+
+    // Tried in the order they're given, before the default patterns
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self, StorageError> {
+        let parsed = Pattern::parse(pattern).map_err(|message| StorageError::InvalidPattern {
+            pattern: pattern.to_string(),
+            message,
+        })?;
+        self.patterns.push(parsed);
+        Ok(self)
+    }
+
+    // The first pattern that matches. 'folders' are the ones between the root and the file.
+    fn read_name(&self, folders: &[String], stem: &str) -> Option<Captures> {
+        let defaults = PATTERNS
+            .iter()
+            .filter_map(|pattern| Pattern::parse(pattern).ok());
+        let mut parts: Vec<String> = folders.iter().map(|folder| clean(folder)).collect();
+        parts.push(clean(stem));
+
+        self.patterns
+            .iter()
+            .cloned()
+            .chain(defaults)
+            .find_map(|pattern| {
+                let depth = pattern.depth();
+                if depth > parts.len() {
+                    return None;
+                }
+                pattern.captures(&parts[parts.len() - depth..].join("/"))
+            })
+    }
+
+    fn media(&self, kind: &MediaKind, captures: Captures) -> Option<Media> {
+        let mut meta = Metadata::new();
+        meta.year = captures.year;
+        let (title, creator) = (captures.title, captures.creator.unwrap_or_default());
+
+        match kind {
+            MediaKind::Book => Some(Media::Book(Book {
+                title,
+                author: creator,
+                meta,
+            })),
+            MediaKind::Movie => Some(Media::Movie(Movie {
+                title,
+                director: creator,
+                meta,
+            })),
+            // An audiobook has no creator: only the title is kept
+            MediaKind::Audiobook => Some(Media::Audiobook(Audiobook { title, meta })),
+            // "Episode 12": the first number of the title is the episode, if there's one
+            MediaKind::Podcast => Some(Media::Podcast(Podcast {
+                show: creator,
+                episode: first_number(&title).unwrap_or(0),
+                title,
+                meta,
+            })),
+            MediaKind::Placeholder | MediaKind::Custom(_) => None,
+        }
+    }
+}
+
+// A file, and the item it stands for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    // From the root that was scanned, with '/' between folders
+    pub path: String,
+    pub id: MediaId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ignored {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanReport {
+    pub added: Vec<Found>,
+    // Already there: the file name told something the catalog didn't know (the year)
+    pub updated: Vec<Found>,
+    // Already there, and nothing new
+    pub unchanged: Vec<Found>,
+    // Files of no known kind are left out: these are the ones that couldn't be read
+    pub ignored: Vec<Ignored>,
+}
+
+impl Catalog {
+    // Adds the media files under 'root' that aren't in the catalog yet. An item is already
+    // there when it's of the same kind, with the same title and creator (see 'dedup').
+    // Hidden files and symbolic links are left alone.
+    pub fn scan_directory(
+        &mut self,
+        root: &str,
+        scanner: &Scanner,
+    ) -> Result<ScanReport, StorageError> {
+        let mut report = ScanReport::default();
+        let mut files = vec![];
+        walk(Path::new(root), &mut vec![], &mut files, &mut report).map_err(|error| {
+            StorageError::Io {
+                path: root.to_string(),
+                message: error.to_string(),
+            }
+        })?;
+
+        for (folders, name) in files {
+            let path = folders
+                .iter()
+                .chain([&name])
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("/");
+            let (stem, extension) = match name.rsplit_once('.') {
+                Some((stem, extension)) if !stem.is_empty() => (stem, extension.to_lowercase()),
+                _ => continue,
+            };
+            let Some(kind) = scanner.extensions.get(&extension) else {
+                continue;
+            };
+
+            let media = scanner
+                .read_name(&folders, stem)
+                .ok_or_else(|| String::from("no pattern matches its name"))
+                .and_then(|captures| {
+                    scanner
+                        .media(kind, captures)
+                        .ok_or_else(|| format!("a {} can't be made from a file", kind))
+                });
+            match media {
+                Ok(media) => self.merge_scanned(path, media, &mut report),
+                Err(reason) => report.ignored.push(Ignored { path, reason }),
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn merge_scanned(&mut self, path: String, media: Media, report: &mut ScanReport) {
+        let title = normalize_title(media.title());
+        let creator = normalize_creator(media.creator().unwrap_or(""));
+        // A file without a creator goes with an item that has one, and the other way round
+        let existing = self.entries().find(|(_, other)| {
+            let other_creator = normalize_creator(other.creator().unwrap_or(""));
+            other.kind() == media.kind()
+                && normalize_title(other.title()) == title
+                && (creator.is_empty() || other_creator.is_empty() || other_creator == creator)
+        });
+
+        let Some((id, other)) = existing else {
+            let id = self.add(media);
+            report.added.push(Found { path, id });
+            return;
+        };
+        let found = Found { path, id };
+        let before = other.meta().cloned();
+        let after = match (before.clone(), media.meta()) {
+            (Some(mut meta), Some(scanned)) => {
+                meta.fill_from(scanned);
+                Some(meta)
+            }
+            _ => None,
+        };
+        match after {
+            Some(meta) if Some(&meta) != before.as_ref() => {
+                let _ = self.update(id, |media| {
+                    if let Some(old) = media.meta_mut() {
+                        *old = meta;
+                    }
+                });
+                report.updated.push(found);
+            }
+            _ => report.unchanged.push(found),
+        }
+    }
+}
+
+// Every file under 'folder', in alphabetical order. Folders that can't be read
+// (but the root) are reported and skipped.
+fn walk(
+    folder: &Path,
+    folders: &mut Vec<String>,
+    files: &mut Vec<(Vec<String>, String)>,
+    report: &mut ScanReport,
+) -> std::io::Result<()> {
+    let mut entries: Vec<fs::DirEntry> = fs::read_dir(folder)?.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if name.starts_with('.') || file_type.is_symlink() {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            folders.push(name);
+            if let Err(error) = walk(&entry.path(), folders, files, report) {
+                report.ignored.push(Ignored {
+                    path: folders.join("/"),
+                    reason: error.to_string(),
+                });
+            }
+            folders.pop();
+        } else if file_type.is_file() {
+            files.push((folders.clone(), name));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,248 @@
+// Just enough XML for the files we import (MARCXML, OPML): elements, attributes, text,
+// comments, CDATA and the predefined entities. No DTD, no validation.
+// Namespace prefixes are dropped: <marc:record> is read as <record>.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // The child elements, whatever their name
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements().filter(move |element| element.name == name)
+    }
+
+    // All the text inside the element, its children's included
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.children {
+            match node {
+                Node::Text(part) => text.push_str(part),
+                Node::Element(element) => text.push_str(&element.text()),
+            }
+        }
+        text
+    }
+}
+
+// The root element. On error: the line where it happened, and what went wrong.
+pub fn parse(text: &str) -> Result<Element, (usize, String)> {
+    let mut parser = Parser { text, position: 0 };
+    let result = parser.document();
+    result.map_err(|message| {
+        let line = text[..parser.position.min(text.len())]
+            .matches('\n')
+            .count()
+            + 1;
+        (line, message)
+    })
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn document(&mut self) -> Result<Element, String> {
+        self.skip_prolog()?;
+        if !self.rest().starts_with('<') {
+            return Err(String::from("no root element"));
+        }
+        let root = self.element()?;
+        self.skip_prolog()?;
+        if !self.rest().is_empty() {
+            return Err(String::from("unexpected content after the root element"));
+        }
+        Ok(root)
+    }
+
+    // Whitespace, <?xml ...?>, comments and <!DOCTYPE ...>
+    fn skip_prolog(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with("<!") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.position = self.text.len() - trimmed.len();
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<&str, String> {
+        match self.rest().find(end) {
+            Some(index) => {
+                let skipped = &self.text[self.position..self.position + index];
+                self.position += index + end.len();
+                Ok(skipped)
+            }
+            None => Err(format!("missing '{}'", end)),
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.rest().starts_with(c) {
+            self.position += c.len_utf8();
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", c))
+        }
+    }
+
+    // "marc:record" -> "record"
+    fn name(&mut self) -> Result<String, String> {
+        let length = self
+            .rest()
+            .find(|c: char| c.is_whitespace() || matches!(c, '>' | '/' | '='))
+            .unwrap_or(self.rest().len());
+        if length == 0 {
+            return Err(String::from("expected a name"));
+        }
+
+        let name = &self.text[self.position..self.position + length];
+        self.position += length;
+        Ok(name.rsplit(':').next().unwrap_or(name).to_string())
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        self.expect('<')?;
+        let mut element = Element {
+            name: self.name()?,
+            attributes: vec![],
+            children: vec![],
+        };
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.position += 1;
+                break;
+            }
+
+            let key = self.name()?;
+            self.skip_whitespace();
+            self.expect('=')?;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(quote @ ('"' | '\'')) => quote,
+                _ => return Err(format!("the value of '{}' should be quoted", key)),
+            };
+            self.position += 1;
+            let value = decode(self.skip_past(&quote.to_string())?);
+            element.attributes.push((key, value));
+        }
+
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Err(format!("<{}> is never closed", element.name));
+            } else if rest.starts_with("</") {
+                self.position += 2;
+                let name = self.name()?;
+                if name != element.name {
+                    return Err(format!("<{}> is closed by </{}>", element.name, name));
+                }
+                self.skip_whitespace();
+                self.expect('>')?;
+                return Ok(element);
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.position += "<![CDATA[".len();
+                let text = self.skip_past("]]>")?.to_string();
+                element.children.push(Node::Text(text));
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with('<') {
+                element.children.push(Node::Element(self.element()?));
+            } else {
+                let length = rest.find('<').unwrap_or(rest.len());
+                let text = decode(&rest[..length]);
+                self.position += length;
+                element.children.push(Node::Text(text));
+            }
+        }
+    }
+}
+
+// "&lt;3 &amp; &#233;" -> "<3 & é". Unknown entities are kept as they are.
+fn decode(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|decimal| decimal.parse()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}